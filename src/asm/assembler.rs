@@ -0,0 +1,449 @@
+use std::convert::TryFrom;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use crate::asm::isa::*;
+use crate::asm::preprocess::{preprocess, Location, SourceLine};
+use crate::asm::tokenizer::{tokenize, Line};
+use crate::debug::{self, TaskDebug};
+use crate::target::{Operand, Target, HALT};
+
+/// First word of every task: `GROUP_ID|TASK_ID|FREQ|LENGTH`, where `LENGTH`
+/// is the number of code words, i.e. the offset of the data segment.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Header {
+  pub group_id: u8,
+  pub task_id: u8,
+  pub freq: u8,
+  pub length: u8,
+}
+
+impl Header {
+  pub fn word(&self) -> Word {
+    pack4x8to32(self.group_id, self.task_id, self.freq, self.length)
+  }
+}
+
+/// A single assembled task: header, code segment (ending with `OP_HLT`)
+/// and data segment.
+#[derive(Clone, Debug)]
+pub struct Task {
+  pub header: Header,
+  pub code: Vec<Word>,
+  pub data: Vec<Word>,
+  /// Data labels declared by the task.
+  pub symbols: Vec<Symbol>,
+  /// References to other tasks' data labels, still to be resolved.
+  pub relocations: Vec<Relocation>,
+  /// The source line of every word, in the order of `words()`.
+  pub sources: Vec<SourceLine>,
+  /// Where the words come from in the task list file the task was compiled
+  /// from, and what its data words hold.
+  pub debug: Option<TaskDebug>,
+}
+
+impl Task {
+  pub fn symbol(&self, name: &str) -> Option<&Symbol> {
+    self.symbols.iter().find(|symbol| symbol.name == name)
+  }
+
+  /// All words of the task in executable order.
+  pub fn words(&self) -> Vec<Word> {
+    let mut words = Vec::with_capacity(1 + self.code.len() + self.data.len());
+
+    words.push(self.header.word());
+    words.extend_from_slice(&self.code);
+    words.extend_from_slice(&self.data);
+
+    words
+  }
+}
+
+/// Data addresses are a single byte and start from 1.
+pub const MAX_DATA_WORDS: usize = 255;
+
+/// A named data word; addresses start from 1 like the data segment itself.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Symbol {
+  pub name: String,
+  pub address: u8,
+}
+
+/// A reference to a data label of another task, e.g. `t2.m11`. The address
+/// byte of the instruction is left as zero until the linker patches it.
+#[derive(Clone, Debug)]
+pub struct Relocation {
+  /// 0-based index of the instruction within the code segment.
+  pub instruction: usize,
+  pub location: Location,
+  pub task_id: u8,
+  pub label: String,
+}
+
+#[derive(Debug)]
+pub struct AsmError {
+  pub location: Location,
+  pub message: String,
+}
+
+impl fmt::Display for AsmError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}: {}", self.location, self.message)
+  }
+}
+
+impl std::error::Error for AsmError {}
+
+struct Assembler<'a> {
+  target: &'a Target,
+  header: Header,
+  data_len: usize,
+  symbols: &'a [Symbol],
+  relocations: Vec<Relocation>,
+}
+
+/// Assembles the `.splc` source of a single task.
+///
+/// The header length field (`GROUP,TASK,FREQ,LENGTH`) may be omitted, in
+/// which case it is computed; if present it has to match the number of code
+/// words. The code section has to end with exactly one `OP_HLT`, and own data
+/// addressed by `OP_LEA`/`OP_MOV` has to lie within the data section.
+///
+/// Data words may be labelled (`alt: 0.0f`), and `OP_LEA`/`OP_MOV` address
+/// operands may name a label instead of a `TASK_ID, ADDRESS` pair, either of
+/// the task itself (`self.alt`) or of another task in the group (`t2.m11`).
+/// References to other tasks are left in `Task::relocations` for the linker.
+///
+/// Include files are looked up relative to the working directory.
+pub fn assemble(source: &str) -> Result<Task, AsmError> {
+  assemble_source(Path::new("<source>"), source)
+}
+
+/// Reads and assembles a `.splc` file; include files are looked up relative
+/// to its directory.
+pub fn assemble_file(path: &Path) -> Result<Task, AsmError> {
+  assemble_file_for(Target::opssat(), path)
+}
+
+/// Assembles `.splc` source that is not read from disk, e.g. compiler
+/// output, as if it were the file at `path`.
+pub fn assemble_source(path: &Path, source: &str) -> Result<Task, AsmError> {
+  assemble_for(Target::opssat(), path, source)
+}
+
+/// Like `assemble_file`, for the ISA of `target`.
+pub fn assemble_file_for(target: &Target, path: &Path) -> Result<Task, AsmError> {
+  let source = fs::read_to_string(path).map_err(|err| AsmError {
+    location: Location {
+      file: path.display().to_string().into(),
+      line: 0,
+      expanded_from: None,
+    },
+    message: err.to_string(),
+  })?;
+
+  let mut task = assemble_for(target, path, &source)?;
+  let error = |message: String| AsmError {
+    location: Location {
+      file: debug::sidecar(path).display().to_string().into(),
+      line: 0,
+      expanded_from: None,
+    },
+    message,
+  };
+
+  // the debug information the compiler wrote next to the file
+  if let Some(info) = debug::read(path).map_err(error)? {
+    let debug = info
+      .task(task.header.group_id, task.header.task_id)
+      .ok_or_else(|| error(format!("no debug information for task {}.{}", task.header.group_id, task.header.task_id)))?;
+
+    task.debug = Some(debug.assembled(&task.sources));
+  }
+
+  Ok(task)
+}
+
+/// Like `assemble_source`, for the ISA of `target`.
+pub fn assemble_for(target: &Target, path: &Path, source: &str) -> Result<Task, AsmError> {
+  let source = preprocess(path, source)?;
+  let mut lines = tokenize(&source)?.into_iter();
+
+  let header_line = lines.next().ok_or_else(|| AsmError {
+    location: Location {
+      file: path.display().to_string().into(),
+      line: 0,
+      expanded_from: None,
+    },
+    message: "missing task header".to_string(),
+  })?;
+  let mut header = Header {
+    group_id: header_line.number(0)?,
+    task_id: header_line.number(1)?,
+    freq: header_line.number(2)?,
+    length: 0,
+  };
+
+  if header_line.label.is_some() || !(3..=4).contains(&header_line.values.len()) {
+    return Err(header_line.error("expected a `GROUP,TASK,FREQ[,LENGTH]` header".to_string()));
+  }
+
+  let mut code_lines = Vec::new();
+  let mut halted = false;
+  for line in lines.by_ref() {
+    if line.label.is_some() {
+      return Err(line.error("labels are only allowed on data lines".to_string()));
+    }
+
+    halted = line.mnemonic().eq_ignore_ascii_case(HALT);
+    code_lines.push(line);
+
+    if halted {
+      break;
+    }
+  }
+
+  if !halted {
+    return Err(code_lines.last().unwrap_or(&header_line).error("the code section must end with OP_HLT".to_string()));
+  }
+
+  header.length = match u8::try_from(code_lines.len()) {
+    Ok(length) => length,
+    Err(_) => {
+      return Err(header_line.error(format!(
+        "the code section has {} instructions, at most 255 are allowed",
+        code_lines.len()
+      )))
+    },
+  };
+
+  // the length field may be left out, but if it is given it has to be right
+  if header_line.values.len() > 3 {
+    let length = header_line.number(3)?;
+
+    if length != header.length {
+      return Err(header_line.error(format!(
+        "header length is {}, expected {} (the number of code words including OP_HLT)",
+        length, header.length
+      )));
+    }
+  }
+
+  let mut data = Vec::new();
+  let mut data_sources = Vec::new();
+  let mut symbols: Vec<Symbol> = Vec::new();
+  for line in lines {
+    if line.is_instruction() {
+      return Err(line.error(
+        "instruction after OP_HLT; exactly one OP_HLT has to end the code section".to_string(),
+      ));
+    }
+
+    if line.values.len() != 1 {
+      return Err(line.error("expected a single data value".to_string()));
+    }
+
+    if let Some(name) = line.label {
+      if symbols.iter().any(|symbol| symbol.name == name) {
+        return Err(line.error(format!("data label `{}` is declared twice", name)));
+      }
+
+      symbols.push(Symbol {
+        name: name.to_string(),
+        address: (data.len() + 1) as u8,
+      });
+    }
+
+    data.push(assemble_data(&line, line.values[0])?);
+    data_sources.push(line.source.clone());
+
+    if data.len() > MAX_DATA_WORDS {
+      return Err(line.error(format!("the data section is limited to {} words", MAX_DATA_WORDS)));
+    }
+  }
+
+  let mut assembler = Assembler {
+    target,
+    header,
+    data_len: data.len(),
+    symbols: &symbols,
+    relocations: Vec::new(),
+  };
+  let code = code_lines
+    .iter()
+    .enumerate()
+    .map(|(index, line)| assembler.instruction(index, line))
+    .collect::<Result<Vec<_>, _>>()?;
+  let relocations = assembler.relocations;
+
+  let mut sources = vec![header_line.source.clone()];
+  sources.extend(code_lines.iter().map(|line| line.source.clone()));
+  sources.extend(data_sources);
+
+  Ok(Task {
+    header,
+    code,
+    data,
+    symbols,
+    relocations,
+    sources,
+    debug: None,
+  })
+}
+
+impl<'a> Assembler<'a> {
+  fn own_address(&self, line: &Line, label: &str) -> Result<u8, AsmError> {
+    self
+      .symbols
+      .iter()
+      .find(|symbol| symbol.name == label)
+      .map(|symbol| symbol.address)
+      .ok_or_else(|| line.error(format!("undefined data label `self.{}`", label)))
+  }
+
+  /// Data addresses start from 1, so the task's own data area is
+  /// `1..=data_len`.
+  fn check_address(&self, line: &Line, address: u8) -> Result<u8, AsmError> {
+    if address == 0 || usize::from(address) > self.data_len {
+      return Err(line.error(format!(
+        "data address {} is outside the task's data area ({} words)",
+        address, self.data_len
+      )));
+    }
+
+    Ok(address)
+  }
+
+  /// Resolves a `TASK_ID, ADDRESS` operand pair starting at operand `index`,
+  /// written as two numbers or as one `task.label`, with the number of
+  /// operands it takes.
+  fn data_reference(&mut self, instruction: usize, line: &Line, index: usize) -> Result<(u8, u8, usize), AsmError> {
+    match line.label_reference(index)? {
+      Some((Some(task_id), label)) if task_id != self.header.task_id => {
+        self.relocations.push(Relocation {
+          instruction,
+          location: line.location.clone(),
+          task_id,
+          label: label.to_string(),
+        });
+
+        Ok((task_id, 0, 1))
+      },
+      Some((_, label)) => Ok((self.header.task_id, self.own_address(line, label)?, 1)),
+      None => {
+        let task_id = line.number(index)?;
+        let address = line.number(index + 1)?;
+
+        if task_id == self.header.task_id {
+          self.check_address(line, address)?;
+        }
+
+        Ok((task_id, address, 2))
+      },
+    }
+  }
+
+  /// Resolves an address of the task's own data, e.g. the destination of
+  /// `OP_MOV, PRE_MOV_RAM`: tasks can only write their own data.
+  fn own_data(&self, line: &Line, index: usize) -> Result<u8, AsmError> {
+    match line.label_reference(index)? {
+      Some((Some(task_id), _)) if task_id != self.header.task_id => {
+        Err(line.error(format!("a task can only write its own data, not task {}'s", task_id)))
+      },
+      Some((_, label)) => self.own_address(line, label),
+      None => self.check_address(line, line.number(index)?),
+    }
+  }
+
+  /// Encodes an instruction as the operands of its opcode lay it out.
+  fn instruction(&mut self, index: usize, line: &Line) -> Result<Word, AsmError> {
+    let target = self.target;
+    let opcode = line.decode(0, "opcode", |mnemonic| target.opcode(mnemonic))?;
+
+    line.expect_operands(&opcode.operand_counts())?;
+
+    let mut bytes = [opcode.value, 0, 0, 0];
+    let mut byte = 1;
+    let mut field = 1;
+    let mut instrument = None;
+    let mut selected = Operand::Register;
+
+    for operand in &opcode.operands {
+      let operand = match operand {
+        Operand::Selected => selected,
+        operand => *operand,
+      };
+
+      match operand {
+        Operand::Unused => {},
+        Operand::Data => {
+          let (task_id, address, written) = self.data_reference(index, line, field)?;
+
+          bytes[byte] = task_id;
+          bytes[byte + 1] = address;
+          field += written;
+        },
+        _ => {
+          bytes[byte] = match operand {
+            Operand::Register => line.decode(field, "register", |name| target.registers.value(name))?,
+            Operand::Prefix => {
+              let prefix = line.decode(field, "prefix", |mnemonic| opcode.prefix(mnemonic))?;
+
+              selected = prefix.selects;
+              prefix.value
+            },
+            Operand::Operator => {
+              let operator = line.decode(field, "operator", |mnemonic| target.operator(mnemonic))?;
+
+              selected = operator.selects;
+              operator.value
+            },
+            Operand::Instrument => {
+              let found = line.decode(field, "instrument", |mnemonic| target.instrument(mnemonic))?;
+
+              instrument = Some(found);
+              found.value
+            },
+            Operand::Parameter => {
+              let instrument = instrument.expect("targets are checked to have an instrument before a parameter");
+
+              line.decode(field, &format!("parameter of {}", instrument.mnemonic), |mnemonic| instrument.parameter(mnemonic))?.value
+            },
+            Operand::Action => {
+              let instrument = instrument.expect("targets are checked to have an instrument before an action");
+
+              line.decode(field, &format!("action of {}", instrument.mnemonic), |mnemonic| instrument.action(mnemonic))?.1
+            },
+            Operand::Address => self.own_data(line, field)?,
+            _ => line.number(field)?,
+          };
+          field += 1;
+        },
+      }
+
+      byte += operand.width();
+    }
+
+    Ok(pack4x8to32(bytes[0], bytes[1], bytes[2], bytes[3]))
+  }
+}
+
+/// Data words are written as a number followed by a type suffix:
+/// `i` for a signed integer, `f` for a single-precision float.
+fn assemble_data(line: &Line, text: &str) -> Result<Word, AsmError> {
+  let split = text.char_indices().last().map_or(0, |(index, _)| index);
+  let (value, suffix) = text.split_at(split);
+
+  match suffix {
+    "i" | "I" => value
+      .parse::<i32>()
+      .map(|value| value as Word)
+      .map_err(|_| line.error(format!("invalid integer `{}`", value))),
+    "f" | "F" => value
+      .parse::<f32>()
+      .map(f32::to_bits)
+      .map_err(|_| line.error(format!("invalid float `{}`", value))),
+    _ => Err(line.error(format!("unrecognized data type in `{}`", text))),
+  }
+}
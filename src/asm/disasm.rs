@@ -0,0 +1,165 @@
+use std::fmt;
+
+use crate::asm::isa::unpack32to4x8;
+use crate::asm::Task;
+use crate::compiler::ast::Type;
+use crate::target::{Operand, Target};
+
+/// A word that no instruction encodes.
+#[derive(Debug)]
+pub struct DisasmError {
+  /// Index of the word within the task, the header being word 0.
+  pub word: usize,
+  pub message: String,
+}
+
+impl fmt::Display for DisasmError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "word {}: {}", self.word, self.message)
+  }
+}
+
+impl std::error::Error for DisasmError {}
+
+/// Turns a task back into `.splc` source that assembles to the same words.
+///
+/// Data addresses are written as `TASK_ID, ADDRESS` pairs. The type of a data
+/// word is the type of the registers `OP_LEA` loads it into or `OP_MOV`
+/// stores to it, an integer when the code does not tell; data labels are kept
+/// when the task still has its symbols.
+///
+/// With debug information, data words are labelled and typed as the
+/// variables they hold, and every instruction that starts a new source line
+/// is preceded by a `; FILE:LINE:COLUMN` comment.
+pub fn disassemble(task: &Task) -> Result<String, DisasmError> {
+  disassemble_for(Target::opssat(), task)
+}
+
+/// Like `disassemble`, for the ISA of `target`.
+pub fn disassemble_for(target: &Target, task: &Task) -> Result<String, DisasmError> {
+  let header = task.header;
+  let mut floats = vec![false; task.data.len()];
+  let mut output = format!("{},{},{},{}\n", header.group_id, header.task_id, header.freq, header.length);
+  let mut line = None;
+
+  for (index, word) in task.code.iter().enumerate() {
+    let bytes = unpack32to4x8(*word);
+    let error = |what: &str, value: u8| DisasmError {
+      word: index + 1,
+      message: format!("invalid {} 0x{:02x}", what, value),
+    };
+    let opcode = target.opcode_of(bytes[0]).ok_or_else(|| error("opcode", bytes[0]))?;
+    // the data words an instruction loads or stores take the type of its register
+    let register = opcode
+      .operands
+      .iter()
+      .position(|operand| *operand == Operand::Register)
+      .map(|position| bytes[1 + position]);
+    let mut mark = |address: u8| {
+      if let (Some(float), Some(register)) = (floats.get_mut(usize::from(address).wrapping_sub(1)), register) {
+        *float = target.registers.is_float(register);
+      }
+    };
+
+    let mut fields = vec![opcode.mnemonic.clone()];
+    let mut byte = 1;
+    let mut instrument = None;
+    let mut selected = Operand::Register;
+
+    for operand in &opcode.operands {
+      let operand = match operand {
+        Operand::Selected => selected,
+        operand => *operand,
+      };
+      let value = bytes[byte];
+
+      match operand {
+        Operand::Unused => {},
+        Operand::Data => {
+          let address = bytes[byte + 1];
+
+          if value == header.task_id {
+            mark(address);
+          }
+          fields.push(value.to_string());
+          fields.push(address.to_string());
+        },
+        Operand::Address => {
+          mark(value);
+          fields.push(value.to_string());
+        },
+        Operand::Task => fields.push(value.to_string()),
+        Operand::Register => {
+          fields.push(target.registers.name_of(value).ok_or_else(|| error("register", value))?.to_string())
+        },
+        Operand::Prefix => {
+          let prefix = opcode.prefix_of(value).ok_or_else(|| error("prefix", value))?;
+
+          selected = prefix.selects;
+          fields.push(prefix.mnemonic.clone());
+        },
+        Operand::Operator => {
+          let operator = target.operator_of(value).ok_or_else(|| error("operator", value))?;
+
+          selected = operator.selects;
+          fields.push(operator.mnemonic.clone());
+        },
+        Operand::Instrument => {
+          let found = target.instrument_of(value).ok_or_else(|| error("instrument", value))?;
+
+          instrument = Some(found);
+          fields.push(found.mnemonic.clone());
+        },
+        Operand::Parameter => {
+          let parameter = instrument.and_then(|instrument| instrument.parameter_of(value));
+
+          fields.push(parameter.ok_or_else(|| error("parameter", value))?.mnemonic.clone());
+        },
+        Operand::Action => {
+          let action = instrument.and_then(|instrument| instrument.action_of(value));
+
+          fields.push(action.ok_or_else(|| error("action", value))?.0.clone());
+        },
+        Operand::Selected => unreachable!("selected operands are resolved above"),
+      }
+
+      byte += operand.width();
+    }
+
+    let instruction = fields.join(", ");
+
+    if let Some(debug) = &task.debug {
+      let source = debug.span(index + 1).map(|span| span.start.line);
+
+      if source.is_some() && source != line {
+        output.push_str(&format!("; {}\n", debug.location(index + 1).unwrap_or_default()));
+      }
+      line = source;
+    }
+
+    output.push_str(&instruction);
+    output.push('\n');
+  }
+
+  for (index, (word, float)) in task.data.iter().zip(&floats).enumerate() {
+    let address = (index + 1) as u8;
+    let variable = task.debug.as_ref().and_then(|debug| debug.variable(address));
+    let float = variable.map_or(*float, |variable| variable.ty == Type::F32);
+
+    if let Some(symbol) = task.symbols.iter().find(|symbol| symbol.address == address) {
+      output.push_str(&format!("{}: ", symbol.name));
+    }
+    else if let Some(variable) = variable {
+      output.push_str(&format!("{}: ", variable.name));
+    }
+
+    if float {
+      output.push_str(&format!("{:?}f\n", f32::from_bits(*word)));
+    }
+    else {
+      output.push_str(&format!("{}i\n", *word as i32));
+    }
+  }
+
+  Ok(output)
+}
@@ -0,0 +1,79 @@
+use crate::asm::tokenizer::{is_identifier, strip_comment};
+
+/// Formats `.splc` source canonically: fields separated by `, `, no trailing
+/// comma, `label: value` data lines, directive arguments separated the same
+/// way, one space before a trailing comment, no indentation, at most one
+/// blank line in a row and none at the start or the end.
+///
+/// Only whitespace and a trailing comma change, so the formatted source
+/// assembles to the same words; comments are kept as they are.
+pub fn format(source: &str) -> String {
+  let mut lines: Vec<String> = Vec::new();
+
+  for line in source.lines() {
+    let code = strip_comment(line);
+    let comment = line[code.len()..].trim_end();
+    let code = format_code(code.trim());
+
+    let formatted = match (code.is_empty(), comment.is_empty()) {
+      (true, true) => String::new(),
+      (true, false) => comment.to_string(),
+      (false, true) => code,
+      (false, false) => format!("{} {}", code, comment),
+    };
+
+    if formatted.is_empty() && matches!(lines.last().map(String::as_str), None | Some("")) {
+      continue;
+    }
+    lines.push(formatted);
+  }
+
+  while lines.last().is_some_and(String::is_empty) {
+    lines.pop();
+  }
+
+  let mut output = lines.join("\n");
+  if !output.is_empty() {
+    output.push('\n');
+  }
+
+  output
+}
+
+fn format_code(code: &str) -> String {
+  if code.is_empty() {
+    return String::new();
+  }
+
+  // `.include "file name"` keeps its argument as written
+  if code.starts_with('.') {
+    return match code.split_once(char::is_whitespace) {
+      Some((directive, rest)) if rest.contains('"') => format!("{} {}", directive, rest.trim()),
+      Some((directive, rest)) => format!("{} {}", directive, fields(rest)),
+      None => code.to_string(),
+    };
+  }
+
+  if let Some((label, value)) = code.split_once(':') {
+    if is_identifier(label.trim()) {
+      return format!("{}: {}", label.trim(), fields(value));
+    }
+  }
+
+  fields(code)
+}
+
+/// `a,b , c,` as `a, b, c`, with the runs of whitespace inside a field, e.g.
+/// between a macro name and its first argument, reduced to a space.
+fn fields(text: &str) -> String {
+  let mut fields: Vec<String> = text
+    .split(',')
+    .map(|field| field.split_whitespace().collect::<Vec<_>>().join(" "))
+    .collect();
+
+  if fields.len() > 1 && fields.last().is_some_and(String::is_empty) {
+    fields.pop();
+  }
+
+  fields.join(", ")
+}
@@ -0,0 +1,264 @@
+/// A single 32-bit VM machine word: an instruction, a header or a data value.
+pub type Word = u32;
+
+/* ASSEMBLY OPCODES*/
+pub const OP_NOP: u8 = 0x00; // No action
+pub const OP_MOV: u8 = 0x01; // OPCODE|  PREFIX|  REG_A|   DEST|
+pub const OP_LEA: u8 = 0x02; // OPCODE|  REG_ID|TASK_ID|ADDRESS| (REG_ID can be float or int)
+pub const OP_CMP: u8 = 0x03; // OPCODE|OPERATOR|  REG_A|  REG_B| (compare register values)
+                             // OPCODE|OPERATOR|TASK_ID| REG_ID| (check task execution status)
+pub const OP_SET: u8 = 0x04; // OPCODE| INST_ID|  PARAM| REG_ID| (sets instrument parameter to register)
+                             // OPCODE|INST_REG|  CONST| REG_ID| (sets a register to fixed const)
+pub const OP_GET: u8 = 0x05; // OPCODE| INST_ID|  PARAM| REG_ID| (gets instrument parameter from register)
+pub const OP_ACT: u8 = 0x06; // OPCODE| INST_ID| ACTION| REG_ID|
+pub const OP_HLT: u8 = 0x07; // Stop execution
+pub const OP_STR: u8 = 0x08; // OPCODE|  PREFIX| UNUSED| REG_ID| provides some measure of return data? write to file?
+pub const OP_FMA: u8 = 0x09; // OPCODE|   REG_A|  REG_B|  REG_C| fused multiply-add: REG_C = REG_C*REG_B + REG_A
+pub const OP_FSD: u8 = 0x0A; // OPCODE|   REG_A|  REG_B|  REG_C| fused divide-subtract: REG_C = REG_C/REG_B - REG_A
+pub const OP_SIN: u8 = 0x0B; // OPCODE|  PREFIX|  REG_A|  REG_B| sine:   REG_B = sin(REG_A) ..and arcsin
+pub const OP_COS: u8 = 0x0C; // OPCODE|  PREFIX|  REG_A|  REG_B| cosine: REG_B = cos(REG_A) ...and arccos
+pub const OP_TAN: u8 = 0x0D; // OPCODE|  PREFIX|  REG_A|  REG_B| tan/atan: REG_B = tan(REG_A)
+pub const OP_POW: u8 = 0x0E; // OPCODE|  PREFIX|  REG_A|  REG_B| power: log and roots can be done as well
+pub const OP_NOR: u8 = 0x0F; // OPCODE|   REG_A|  REG_B|  REG_C| REG_C = REG_A NOR NEG_B (or NAND if neccessary)
+
+//cmp operators
+pub const TSX_EQ: u8 = 0x0D; //task result is equal ...
+pub const TSX_NE: u8 = 0x0E; //task result is not equal to ...
+
+//prefixes
+pub const PRE_MOV_REG: u8 = 0x01;
+pub const PRE_MOV_RAM: u8 = 0x02;
+pub const PRE_STR_ALU: u8 = 0x01;
+pub const PRE_STR_FPU: u8 = 0x02;
+pub const PRE_STR_BIN: u8 = 0x03;
+//pub const PRE_MOV_IND: u8 = 0x03;
+
+//instruments
+pub const INST_ADC: u8 = 0x01;
+pub const INST_GPS: u8 = 0x02;
+pub const INST_IMG: u8 = 0x03;
+pub const INST_FPU: u8 = 0x04; //load a constant to FPU register
+pub const INST_SDR: u8 = 0x05; //not supported yet
+pub const INST_NMF: u8 = 0x06; //set or get NMF-related parameter
+pub const INST_VXM: u8 = 0x07; //set or get internal VM parameter
+
+//instrument parameters
+pub const P_ADC_MODE: u8 = 0x01;
+pub const P_ADC_MAGX: u8 = 0x02;
+pub const P_ADC_MAGY: u8 = 0x03;
+pub const P_ADC_MAGZ: u8 = 0x04;
+pub const P_ADC_SUNX: u8 = 0x05;
+pub const P_ADC_SUNY: u8 = 0x06;
+pub const P_ADC_SUNZ: u8 = 0x07;
+pub const P_ADC_ANGX: u8 = 0x08;
+pub const P_ADC_ANGY: u8 = 0x09;
+pub const P_ADC_ANGZ: u8 = 0x0A;
+pub const P_ADC_QTNA: u8 = 0x0B;
+pub const P_ADC_QTNB: u8 = 0x0C;
+pub const P_ADC_QTNC: u8 = 0x0D;
+pub const P_ADC_QTND: u8 = 0x0E;
+pub const P_ADC_MTQX: u8 = 0x0F;
+pub const P_ADC_MTQY: u8 = 0x10;
+pub const P_ADC_MTQZ: u8 = 0x11;
+pub const P_IMG_GAIN_R: u8 = 0x01;
+pub const P_IMG_GAIN_G: u8 = 0x02;
+pub const P_IMG_GAIN_B: u8 = 0x03;
+pub const P_IMG_EXPOSE: u8 = 0x04;
+pub const P_IMG_STATUS: u8 = 0x05; //not to be used?
+pub const P_IMG_NUMBER: u8 = 0x06;
+pub const P_GPS_LATT: u8 = 0x01;
+pub const P_GPS_LONG: u8 = 0x02;
+pub const P_GPS_ALTT: u8 = 0x03;
+pub const P_GPS_TIME: u8 = 0x04;
+pub const P_NMF_TIME: u8 = 0x01;
+pub const P_VXM_TIME: u8 = 0x01;
+pub const P_VXM_PRSN: u8 = 0x02;
+pub const P_VXM_TLSC: u8 = 0x03;
+pub const P_VXM_DBUG: u8 = 0x04;
+pub const P_FPU_NIL: u8 = 0x00;
+pub const P_FPU_ONE: u8 = 0x01;
+pub const P_FPU_EXP: u8 = 0x02;
+pub const P_FPU_PIE: u8 = 0x03;
+
+//instrument actions
+pub const A_IMG_DO_JPG: u8 = 0x07;
+pub const A_IMG_DO_RAW: u8 = 0x08;
+pub const A_IMG_DO_BMP: u8 = 0x09;
+pub const A_IMG_DO_PNG: u8 = 0x0A;
+pub const A_ADC_NADIR: u8 = 0x05;
+pub const A_ADC_TOSUN: u8 = 0x06;
+pub const A_ADC_BDOTT: u8 = 0x07;
+pub const A_ADC_TRACK: u8 = 0x08;
+pub const A_ADC_UNSET: u8 = 0x09;
+
+/// Mnemonic/value pairs. Values are only unique within an instrument or
+/// opcode family, e.g. `P_GPS_LATT` and `P_ADC_MODE` are both `0x01`.
+pub type Table = &'static [(&'static str, u8)];
+
+pub const OPCODES: Table = &[
+  ("OP_NOP", OP_NOP),
+  ("OP_MOV", OP_MOV),
+  ("OP_LEA", OP_LEA),
+  ("OP_CMP", OP_CMP),
+  ("OP_SET", OP_SET),
+  ("OP_GET", OP_GET),
+  ("OP_ACT", OP_ACT),
+  ("OP_HLT", OP_HLT),
+  ("OP_STR", OP_STR),
+  ("OP_FMA", OP_FMA),
+  ("OP_FSD", OP_FSD),
+  ("OP_SIN", OP_SIN),
+  ("OP_COS", OP_COS),
+  ("OP_TAN", OP_TAN),
+  ("OP_POW", OP_POW),
+  ("OP_NOR", OP_NOR),
+];
+
+pub const PREFIXES: Table = &[
+  ("PRE_MOV_REG", 0x01),
+  ("PRE_MOV_RAM", 0x02),
+  ("PRE_MOV_IND", 0x03),
+  ("PRE_STR_ALU", PRE_STR_ALU),
+  ("PRE_STR_FPU", PRE_STR_FPU),
+  ("PRE_STR_BIN", PRE_STR_BIN),
+  ("PRE_NORMAL", 0x01),
+  ("PRE_INVERT", 0x02),
+];
+
+pub const OPERATORS: Table = &[
+  ("ALU_EQ", 0x01),
+  ("ALU_NE", 0x02),
+  ("ALU_GT", 0x03),
+  ("ALU_LT", 0x04),
+  ("ALU_GE", 0x05),
+  ("ALU_LE", 0x06), //lesser or equal
+  ("FPU_EQ", 0x07), //same but for FPU
+  ("FPU_NE", 0x08),
+  ("FPU_GT", 0x09),
+  ("FPU_LT", 0x0A),
+  ("TSX_EQ", TSX_EQ),
+  ("TSX_NE", TSX_NE),
+];
+
+pub const ACTIONS: Table = &[
+  ("A_IMG_DO_JPG", A_IMG_DO_JPG),
+  ("A_IMG_DO_RAW", A_IMG_DO_RAW),
+  ("A_IMG_DO_BMP", A_IMG_DO_BMP),
+  ("A_IMG_DO_PNG", A_IMG_DO_PNG),
+  ("A_ADC_NADIR", A_ADC_NADIR),
+  ("A_ADC_TOSUN", A_ADC_TOSUN),
+  ("A_ADC_BDOTT", A_ADC_BDOTT),
+  ("A_ADC_TRACK", A_ADC_TRACK),
+  ("A_ADC_UNSET", A_ADC_UNSET),
+];
+
+pub const INSTRUMENTS: Table = &[
+  ("INST_ADC", INST_ADC),
+  ("INST_GPS", INST_GPS),
+  ("INST_IMG", INST_IMG),
+  ("INST_FPU", INST_FPU),
+  ("INST_SDR", INST_SDR),
+  ("INST_NMF", INST_NMF),
+  ("INST_VXM", INST_VXM),
+];
+
+pub const PARAMETERS: Table = &[
+  ("P_ADC_MODE", P_ADC_MODE),
+  ("P_ADC_MAGX", P_ADC_MAGX),
+  ("P_ADC_MAGY", P_ADC_MAGY),
+  ("P_ADC_MAGZ", P_ADC_MAGZ),
+  ("P_ADC_SUNX", P_ADC_SUNX),
+  ("P_ADC_SUNY", P_ADC_SUNY),
+  ("P_ADC_SUNZ", P_ADC_SUNZ),
+  ("P_ADC_ANGX", P_ADC_ANGX),
+  ("P_ADC_ANGY", P_ADC_ANGY),
+  ("P_ADC_ANGZ", P_ADC_ANGZ),
+  ("P_ADC_QTNA", P_ADC_QTNA),
+  ("P_ADC_QTNB", P_ADC_QTNB),
+  ("P_ADC_QTNC", P_ADC_QTNC),
+  ("P_ADC_QTND", P_ADC_QTND),
+  ("P_ADC_MTQX", P_ADC_MTQX),
+  ("P_ADC_MTQY", P_ADC_MTQY),
+  ("P_ADC_MTQZ", P_ADC_MTQZ),
+  ("P_IMG_GAIN_R", P_IMG_GAIN_R),
+  ("P_IMG_GAIN_G", P_IMG_GAIN_G),
+  ("P_IMG_GAIN_B", P_IMG_GAIN_B),
+  ("P_IMG_EXPOSE", P_IMG_EXPOSE),
+  ("P_IMG_STATUS", P_IMG_STATUS),
+  ("P_IMG_NUMBER", P_IMG_NUMBER),
+  ("P_GPS_LATT", P_GPS_LATT),
+  ("P_GPS_LONG", P_GPS_LONG),
+  ("P_GPS_ALTT", P_GPS_ALTT),
+  ("P_GPS_TIME", P_GPS_TIME),
+  ("P_NMF_TIME", P_NMF_TIME),
+  ("P_VXM_TIME", P_VXM_TIME),
+  ("P_VXM_PRSN", P_VXM_PRSN),
+  ("P_VXM_TLSC", P_VXM_TLSC),
+  ("P_VXM_DBUG", P_VXM_DBUG),
+  ("P_FPU_NIL", P_FPU_NIL),
+  ("P_FPU_ONE", P_FPU_ONE),
+  ("P_FPU_EXP", P_FPU_EXP),
+  ("P_FPU_PIE", P_FPU_PIE),
+];
+
+pub const REGISTERS: Table = &[
+  //ALU registers
+  ("IREG_A", 0x00),
+  ("IREG_B", 0x01),
+  ("IREG_C", 0x02),
+  ("IREG_D", 0x03),
+  ("IREG_E", 0x04),
+  ("IREG_F", 0x05),
+  ("IREG_G", 0x06),
+  ("IREG_H", 0x07),
+  ("IREG_I", 0x08),
+  ("IREG_J", 0x09),
+  ("IREG_K", 0x0A),
+  ("IREG_L", 0x0B),
+  ("IREG_M", 0x0C),
+  ("IREG_N", 0x0D),
+  ("IREG_P", 0x0E),
+  ("IREG_U", 0x0F),
+  // FPU registers
+  ("FREG_A", 0x10),
+  ("FREG_B", 0x11),
+  ("FREG_C", 0x12),
+  ("FREG_D", 0x13),
+  ("FREG_E", 0x14),
+  ("FREG_F", 0x15),
+  ("FREG_G", 0x16),
+  ("FREG_H", 0x17),
+  ("FREG_I", 0x18),
+  ("FREG_J", 0x19),
+  ("FREG_K", 0x1A),
+  ("FREG_L", 0x1B),
+  ("FREG_M", 0x1C),
+  ("FREG_N", 0x1D),
+  ("FREG_P", 0x1E),
+  ("FREG_U", 0x1F),
+];
+
+/// Looks up the value of `name` in `table`, ignoring case.
+pub fn lookup(table: Table, name: &str) -> Option<u8> {
+  table
+    .iter()
+    .find(|(mnemonic, _)| mnemonic.eq_ignore_ascii_case(name))
+    .map(|(_, value)| *value)
+}
+
+/// The mnemonic of `value` in `table` among those starting with `prefix`,
+/// e.g. `name_of(PARAMETERS, "P_IMG_", 4)` is `P_IMG_EXPOSE`.
+pub fn name_of(table: Table, prefix: &str, value: u8) -> Option<&'static str> {
+  table
+    .iter()
+    .find(|(mnemonic, candidate)| *candidate == value && mnemonic.starts_with(prefix))
+    .map(|(mnemonic, _)| *mnemonic)
+}
+
+pub fn pack4x8to32(a: u8, b: u8, c: u8, d: u8) -> Word {
+  (Word::from(a) << 24) | (Word::from(b) << 16) | (Word::from(c) << 8) | Word::from(d)
+}
+
+pub fn unpack32to4x8(word: Word) -> [u8; 4] {
+  word.to_be_bytes()
+}
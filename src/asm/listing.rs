@@ -0,0 +1,63 @@
+use crate::asm::isa::unpack32to4x8;
+use crate::asm::Task;
+
+/// Renders a side-by-side listing of an assembled task: for every word its
+/// index, its encoding as a whole and split into the four byte fields of
+/// `pack4x8to32`, the source line and the source text, followed by the data
+/// label symbol table.
+pub fn listing(task: &Task, name: &str) -> String {
+  let header = task.header;
+  let mut output = format!(
+    "; splasm listing of {}\n; group {}, task {}, frequency {}, {} code words, {} data words\n\n",
+    name,
+    header.group_id,
+    header.task_id,
+    header.freq,
+    task.code.len(),
+    task.data.len()
+  );
+
+  output.push_str("WORD  ENCODING  B0 B1 B2 B3  LINE  SOURCE\n");
+
+  for (index, (word, source)) in task.words().iter().zip(&task.sources).enumerate() {
+    let [b0, b1, b2, b3] = unpack32to4x8(*word);
+    let location = &source.location;
+
+    output.push_str(&format!(
+      "{:>4}  {:08x}  {:02x} {:02x} {:02x} {:02x}  {:>4}  {}",
+      index, word, b0, b1, b2, b3, location.line, source.original
+    ));
+
+    if location.expanded_from.is_some() || *location.file != *name {
+      output.push_str(&format!("  ; {}", location));
+    }
+
+    output.push('\n');
+  }
+
+  if !task.symbols.is_empty() {
+    let data_start = 1 + task.code.len();
+
+    output.push_str("\nSYMBOLS\nADDRESS  WORD  NAME\n");
+
+    for symbol in &task.symbols {
+      let word = data_start + usize::from(symbol.address) - 1;
+      output.push_str(&format!("{:>7}  {:>4}  {}\n", symbol.address, word, symbol.name));
+    }
+  }
+
+  if !task.relocations.is_empty() {
+    output.push_str("\nRELOCATIONS (resolved by splink)\nWORD  REFERENCE\n");
+
+    for relocation in &task.relocations {
+      output.push_str(&format!(
+        "{:>4}  t{}.{}\n",
+        1 + relocation.instruction,
+        relocation.task_id,
+        relocation.label
+      ));
+    }
+  }
+
+  output
+}
@@ -0,0 +1,10 @@
+pub mod assembler;
+pub mod disasm;
+pub mod format;
+pub mod isa;
+pub mod listing;
+pub mod preprocess;
+pub mod tokenizer;
+
+pub use assembler::*;
+pub use isa::Word;
@@ -0,0 +1,311 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use crate::asm::tokenizer::{is_identifier, strip_comment};
+use crate::asm::AsmError;
+
+/// Macros can invoke other macros and include files, up to this depth.
+const MAX_DEPTH: usize = 32;
+
+/// Where a line of assembly comes from. Lines produced by a macro point at
+/// the macro body and remember where the macro was invoked.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Location {
+  pub file: Rc<str>,
+  /// 1-based line, or 0 for errors about the file as a whole.
+  pub line: usize,
+  pub expanded_from: Option<Rc<Location>>,
+}
+
+impl fmt::Display for Location {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}", self.file)?;
+
+    if self.line > 0 {
+      write!(f, ":{}", self.line)?;
+    }
+
+    if let Some(invocation) = &self.expanded_from {
+      write!(f, " (expanded from {})", invocation)?;
+    }
+
+    Ok(())
+  }
+}
+
+/// A line of assembly after preprocessing.
+#[derive(Clone, Debug)]
+pub struct SourceLine {
+  pub location: Location,
+  /// The text to assemble: comment removed, constants and macro arguments
+  /// substituted.
+  pub text: String,
+  /// The line as written, comment included; for lines produced by a macro,
+  /// the body line with the arguments substituted.
+  pub original: String,
+}
+
+struct Macro {
+  params: Vec<String>,
+  body: Vec<SourceLine>,
+}
+
+#[derive(Default)]
+struct Preprocessor {
+  constants: HashMap<String, String>,
+  /// Keyed by upper-case name, as macros are invoked like mnemonics.
+  macros: HashMap<String, Macro>,
+  includes: Vec<PathBuf>,
+  output: Vec<SourceLine>,
+}
+
+/// Expands the `.include`, `.equ` and `.macro` directives of the assembly
+/// source read from `path`:
+///
+/// * `.include "file.splc"` inserts another file, relative to the including one
+/// * `.equ NAME, VALUE` replaces the identifier `NAME` by `VALUE` on later lines
+/// * `.macro NAME p1, p2` ... `.endm` defines a macro, invoked as `NAME a1, a2`,
+///   whose body has every parameter identifier replaced by its argument
+pub fn preprocess(path: &Path, source: &str) -> Result<Vec<SourceLine>, AsmError> {
+  let mut preprocessor = Preprocessor::default();
+
+  preprocessor
+    .includes
+    .push(fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf()));
+  preprocessor.file(path, source, 0)?;
+
+  Ok(preprocessor.output)
+}
+
+fn error(location: &Location, message: String) -> AsmError {
+  AsmError {
+    location: location.clone(),
+    message,
+  }
+}
+
+/// Replaces every identifier of `text` for which `replacement` returns a value.
+/// Runs starting with a digit, like the `0f` of `1.0f`, are not identifiers.
+fn substitute<'a>(text: &str, replacement: impl Fn(&str) -> Option<&'a str>) -> String {
+  let mut output = String::with_capacity(text.len());
+  let mut rest = text;
+
+  while let Some(start) = rest.find(|ch: char| ch.is_ascii_alphanumeric() || ch == '_') {
+    output.push_str(&rest[..start]);
+    rest = &rest[start..];
+
+    let end = rest
+      .find(|ch: char| !ch.is_ascii_alphanumeric() && ch != '_')
+      .unwrap_or(rest.len());
+    let word = &rest[..end];
+
+    match replacement(word) {
+      Some(value) if is_identifier(word) => output.push_str(value),
+      _ => output.push_str(word),
+    }
+
+    rest = &rest[end..];
+  }
+
+  output.push_str(rest);
+  output
+}
+
+fn split_arguments(text: &str) -> Vec<String> {
+  if text.trim().is_empty() {
+    return Vec::new();
+  }
+
+  text.split(',').map(|argument| argument.trim().to_string()).collect()
+}
+
+impl Preprocessor {
+  fn file(&mut self, path: &Path, source: &str, depth: usize) -> Result<(), AsmError> {
+    let file: Rc<str> = path.display().to_string().into();
+    let mut definition: Option<(String, Location, Macro)> = None;
+
+    for (index, original) in source.lines().enumerate() {
+      let location = Location {
+        file: file.clone(),
+        line: index + 1,
+        expanded_from: None,
+      };
+      let text = strip_comment(original).trim();
+      let (directive, rest) = match text.split_once(char::is_whitespace) {
+        Some((directive, rest)) => (directive, rest.trim()),
+        None => (text, ""),
+      };
+
+      if let Some((_, _, body)) = definition.as_mut() {
+        match directive.to_ascii_lowercase().as_str() {
+          ".endm" => {
+            if let Some((name, _, body)) = definition.take() {
+              self.macros.insert(name, body);
+            }
+          },
+          ".macro" => return Err(error(&location, "macros cannot be defined inside a macro".to_string())),
+          _ => body.body.push(SourceLine {
+            location,
+            text: text.to_string(),
+            original: original.trim_end().to_string(),
+          }),
+        }
+        continue;
+      }
+
+      if directive.eq_ignore_ascii_case(".macro") {
+        let (name, params) = match rest.split_once(char::is_whitespace) {
+          Some((name, params)) => (name, split_arguments(params)),
+          None => (rest, Vec::new()),
+        };
+
+        if !is_identifier(name) || !params.iter().all(|param| is_identifier(param)) {
+          return Err(error(&location, format!("invalid macro definition `{}`", rest)));
+        }
+
+        let key = name.to_ascii_uppercase();
+        if self.macros.contains_key(&key) {
+          return Err(error(&location, format!("macro `{}` is defined twice", name)));
+        }
+
+        definition = Some((key, location, Macro { params, body: Vec::new() }));
+        continue;
+      }
+
+      let line = SourceLine {
+        location,
+        text: text.to_string(),
+        original: original.trim_end().to_string(),
+      };
+      self.line(path, line, depth)?;
+    }
+
+    match definition {
+      Some((name, location, _)) => Err(error(&location, format!("macro `{}` is missing its .endm", name))),
+      None => Ok(()),
+    }
+  }
+
+  fn line(&mut self, path: &Path, line: SourceLine, depth: usize) -> Result<(), AsmError> {
+    let SourceLine { location, text, original } = line;
+    let text = text.as_str();
+
+    if text.is_empty() {
+      return Ok(());
+    }
+
+    if depth > MAX_DEPTH {
+      return Err(error(&location, "macros or includes are nested too deeply".to_string()));
+    }
+
+    let (word, rest) = match text.split_once(char::is_whitespace) {
+      Some((word, rest)) => (word, rest.trim()),
+      None => (text, ""),
+    };
+
+    match word.to_ascii_lowercase().as_str() {
+      ".include" => self.include(path, &location, rest, depth),
+      ".equ" => self.constant(&location, rest),
+      ".endm" => Err(error(&location, ".endm without .macro".to_string())),
+      directive if directive.starts_with('.') => Err(error(&location, format!("unknown directive `{}`", word))),
+      _ => match self.macros.get(&word.to_ascii_uppercase()) {
+        Some(definition) => {
+          let arguments = split_arguments(rest);
+
+          if arguments.len() != definition.params.len() {
+            return Err(error(
+              &location,
+              format!(
+                "macro `{}` takes {} arguments, found {}",
+                word,
+                definition.params.len(),
+                arguments.len()
+              ),
+            ));
+          }
+
+          let invocation = Rc::new(location);
+          let argument = |word: &str| {
+            definition
+              .params
+              .iter()
+              .position(|param| param == word)
+              .map(|index| arguments[index].as_str())
+          };
+          let expansion: Vec<SourceLine> = definition
+            .body
+            .iter()
+            .map(|line| SourceLine {
+              location: Location {
+                expanded_from: Some(invocation.clone()),
+                ..line.location.clone()
+              },
+              text: substitute(&line.text, argument),
+              original: substitute(&line.original, argument),
+            })
+            .collect();
+
+          for line in expansion {
+            self.line(path, line, depth + 1)?;
+          }
+
+          Ok(())
+        },
+        None => {
+          let constants = &self.constants;
+
+          self.output.push(SourceLine {
+            text: substitute(text, |word| constants.get(word).map(String::as_str)),
+            location,
+            original,
+          });
+
+          Ok(())
+        },
+      },
+    }
+  }
+
+  fn include(&mut self, path: &Path, location: &Location, argument: &str, depth: usize) -> Result<(), AsmError> {
+    let name = argument
+      .strip_prefix('"')
+      .and_then(|name| name.strip_suffix('"'))
+      .ok_or_else(|| error(location, format!("expected `.include \"file\"`, found `{}`", argument)))?;
+    let included = path.parent().unwrap_or_else(|| Path::new("")).join(name);
+    let canonical = fs::canonicalize(&included).unwrap_or_else(|_| included.clone());
+
+    if self.includes.contains(&canonical) {
+      return Err(error(location, format!("`{}` includes itself", included.display())));
+    }
+
+    let source = fs::read_to_string(&included)
+      .map_err(|err| error(location, format!("cannot include `{}`: {}", included.display(), err)))?;
+
+    self.includes.push(canonical);
+    self.file(&included, &source, depth + 1)?;
+    self.includes.pop();
+
+    Ok(())
+  }
+
+  fn constant(&mut self, location: &Location, argument: &str) -> Result<(), AsmError> {
+    let (name, value) = argument
+      .split_once(',')
+      .map(|(name, value)| (name.trim(), value.trim()))
+      .filter(|(name, value)| is_identifier(name) && !value.is_empty())
+      .ok_or_else(|| error(location, format!("expected `.equ NAME, VALUE`, found `{}`", argument)))?;
+
+    if self.constants.contains_key(name) {
+      return Err(error(location, format!("constant `{}` is defined twice", name)));
+    }
+
+    let constants = &self.constants;
+    let value = substitute(value, |word| constants.get(word).map(String::as_str));
+    self.constants.insert(name.to_string(), value);
+
+    Ok(())
+  }
+}
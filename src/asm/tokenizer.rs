@@ -0,0 +1,235 @@
+use crate::asm::preprocess::{Location, SourceLine};
+use crate::asm::AsmError;
+
+/// A non-empty `.splc` source line split into its comma-separated fields.
+///
+/// Comments start with `;` or `//` and run to the end of the line. Blank and
+/// comment-only lines produce no `Line`, whitespace around fields is ignored
+/// and a single trailing comma (`OP_HLT,`) is accepted.
+#[derive(Clone, Debug)]
+pub struct Line<'a> {
+  pub source: &'a SourceLine,
+  pub location: &'a Location,
+  /// Label of a data line (`alt: 0.0f`).
+  pub label: Option<&'a str>,
+  pub values: Vec<&'a str>,
+}
+
+/// Splits preprocessed source lines into lines of fields.
+pub fn tokenize(source: &[SourceLine]) -> Result<Vec<Line<'_>>, AsmError> {
+  let mut lines = Vec::new();
+
+  for line in source {
+    if let Some(line) = tokenize_line(line)? {
+      lines.push(line);
+    }
+  }
+
+  Ok(lines)
+}
+
+pub fn strip_comment(text: &str) -> &str {
+  let end = [text.find(';'), text.find("//")]
+    .iter()
+    .flatten()
+    .min()
+    .copied()
+    .unwrap_or(text.len());
+
+  &text[..end]
+}
+
+pub fn is_identifier(text: &str) -> bool {
+  let mut chars = text.chars();
+
+  match chars.next() {
+    Some(ch) if ch.is_ascii_alphabetic() || ch == '_' => chars.all(|ch| ch.is_ascii_alphanumeric() || ch == '_'),
+    _ => false,
+  }
+}
+
+pub fn tokenize_line(source: &SourceLine) -> Result<Option<Line<'_>>, AsmError> {
+  let location = &source.location;
+  let mut text = strip_comment(&source.text).trim();
+
+  if text.is_empty() {
+    return Ok(None);
+  }
+
+  let mut label = None;
+  if let Some((name, rest)) = text.split_once(':') {
+    let name = name.trim();
+
+    if !is_identifier(name) {
+      return Err(AsmError {
+        location: location.clone(),
+        message: format!("invalid label `{}`", name),
+      });
+    }
+
+    label = Some(name);
+    text = rest.trim();
+  }
+
+  let mut values: Vec<&str> = text.split(',').map(str::trim).collect();
+
+  if values.len() > 1 && values.last() == Some(&"") {
+    values.pop();
+  }
+
+  if values.iter().any(|value| value.is_empty()) {
+    return Err(AsmError {
+      location: location.clone(),
+      message: "empty field".to_string(),
+    });
+  }
+
+  Ok(Some(Line {
+    source,
+    location,
+    label,
+    values,
+  }))
+}
+
+impl<'a> Line<'a> {
+  pub fn error(&self, message: String) -> AsmError {
+    AsmError {
+      location: self.location.clone(),
+      message,
+    }
+  }
+
+  /// The mnemonic of an instruction line.
+  pub fn mnemonic(&self) -> &'a str {
+    self.values[0]
+  }
+
+  pub fn is_instruction(&self) -> bool {
+    self.label.is_none() && self.mnemonic().get(..3).is_some_and(|prefix| prefix.eq_ignore_ascii_case("OP_"))
+  }
+
+  /// Number of operands following the mnemonic.
+  pub fn operand_count(&self) -> usize {
+    self.values.len() - 1
+  }
+
+  /// Checks that the instruction has one of the `expected` operand counts.
+  pub fn expect_operands(&self, expected: &[usize]) -> Result<(), AsmError> {
+    if expected.contains(&self.operand_count()) {
+      return Ok(());
+    }
+
+    let counts = expected.iter().map(usize::to_string).collect::<Vec<_>>().join(" or ");
+
+    Err(self.error(format!(
+      "{} takes {} operands, found {}",
+      self.mnemonic().to_ascii_uppercase(),
+      counts,
+      self.operand_count()
+    )))
+  }
+
+  pub fn operand(&self, index: usize) -> Result<&'a str, AsmError> {
+    self
+      .values
+      .get(index)
+      .copied()
+      .ok_or_else(|| self.error(format!("missing operand {}", index)))
+  }
+
+  /// Looks up the mnemonic of operand `index`, `what` naming it in errors.
+  pub fn decode<T>(&self, index: usize, what: &str, lookup: impl FnOnce(&'a str) -> Option<T>) -> Result<T, AsmError> {
+    let operand = self.operand(index)?;
+
+    lookup(operand).ok_or_else(|| self.error(format!("unknown {} `{}`", what, operand)))
+  }
+
+  pub fn number(&self, index: usize) -> Result<u8, AsmError> {
+    let operand = self.operand(index)?;
+
+    operand
+      .parse()
+      .map_err(|_| self.error(format!("expected a number from 0 to 255, found `{}`", operand)))
+  }
+
+  /// A `task.label` operand, where `task` is either `self` or `t<TASK_ID>`.
+  pub fn label_reference(&self, index: usize) -> Result<Option<(Option<u8>, &'a str)>, AsmError> {
+    let operand = self.operand(index)?;
+
+    let (task, label) = match operand.split_once('.') {
+      Some(parts) => parts,
+      None => return Ok(None),
+    };
+
+    let task_id = if task.eq_ignore_ascii_case("self") {
+      None
+    }
+    else {
+      Some(
+        task
+          .strip_prefix(|ch| ch == 't' || ch == 'T')
+          .and_then(|id| id.parse().ok())
+          .ok_or_else(|| self.error(format!("expected `self` or `t<TASK_ID>`, found `{}`", task)))?,
+      )
+    };
+
+    Ok(Some((task_id, label)))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn line(text: &str) -> SourceLine {
+    SourceLine {
+      location: Location {
+        file: "test.splc".into(),
+        line: 1,
+        expanded_from: None,
+      },
+      text: text.to_string(),
+      original: text.to_string(),
+    }
+  }
+
+  fn values(text: &str) -> Result<Option<Vec<String>>, String> {
+    let source = line(text);
+
+    tokenize_line(&source)
+      .map(|line| line.map(|line| line.values.iter().map(|value| value.to_string()).collect()))
+      .map_err(|err| err.message)
+  }
+
+  #[test]
+  fn accepts_a_single_trailing_comma() {
+    assert_eq!(values("OP_HLT,"), Ok(Some(vec!["OP_HLT".to_string()])));
+    let expected = ["OP_STR", "PRE_STR_FPU", "FREG_F"].map(String::from).to_vec();
+
+    assert_eq!(values(" OP_STR , PRE_STR_FPU,FREG_F , "), Ok(Some(expected)));
+  }
+
+  #[test]
+  fn rejects_empty_fields() {
+    assert_eq!(values("OP_HLT,,"), Err("empty field".to_string()));
+    assert_eq!(values("OP_STR,, FREG_F"), Err("empty field".to_string()));
+    assert_eq!(values(","), Err("empty field".to_string()));
+  }
+
+  #[test]
+  fn skips_comments_and_blank_lines() {
+    assert_eq!(values("   "), Ok(None));
+    assert_eq!(values("; OP_HLT"), Ok(None));
+    assert_eq!(values("OP_HLT, // halts"), Ok(Some(vec!["OP_HLT".to_string()])));
+  }
+
+  #[test]
+  fn splits_off_the_label_of_a_data_line() {
+    let source = line("alt: 0.0f,");
+    let line = tokenize_line(&source).unwrap().unwrap();
+
+    assert_eq!(line.label, Some("alt"));
+    assert_eq!(line.values, ["0.0f"]);
+  }
+}
@@ -0,0 +1,214 @@
+use std::fmt;
+
+use crate::vm::scheduler::Frequency;
+
+/// A position in a source file, 0-based like lexeme locations.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Position {
+  pub line: usize,
+  pub column: usize,
+}
+
+/// Shown 1-based, like editors do.
+impl fmt::Display for Position {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}:{}", self.line + 1, self.column + 1)
+  }
+}
+
+/// The source range of a node, `end` being exclusive.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Span {
+  pub start: Position,
+  pub end: Position,
+}
+
+impl Span {
+  pub fn to(self, other: Span) -> Span {
+    Span {
+      start: self.start,
+      end: other.end,
+    }
+  }
+
+  pub fn contains(self, position: Position) -> bool {
+    self.start <= position && position < self.end
+  }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Name {
+  pub text: String,
+  pub span: Span,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Type {
+  I32,
+  F32,
+}
+
+impl fmt::Display for Type {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Type::I32 => write!(f, "i32"),
+      Type::F32 => write!(f, "f32"),
+    }
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Literal {
+  Int(i32),
+  Float(f32),
+}
+
+impl Literal {
+  pub fn ty(self) -> Type {
+    match self {
+      Literal::Int(_) => Type::I32,
+      Literal::Float(_) => Type::F32,
+    }
+  }
+}
+
+/// A task list file: `group NAME { task NAME { ... } ... } ...`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Program {
+  pub groups: Vec<Group>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Group {
+  pub name: Name,
+  pub tasks: Vec<Task>,
+  pub span: Span,
+}
+
+/// `task NAME { data { ... } freq: ...; preq { ... } exec { ... } }`; every
+/// section but `exec` may be left out.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Task {
+  pub name: Name,
+  pub data: Vec<Variable>,
+  pub freq: Frequency,
+  pub preq: Vec<Condition>,
+  pub exec: Vec<Statement>,
+  pub span: Span,
+}
+
+/// `var NAME: TYPE = LITERAL;` or `const NAME: TYPE = LITERAL;` in a data
+/// section.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Variable {
+  pub name: Name,
+  pub ty: Type,
+  pub constant: bool,
+  pub value: Literal,
+  pub span: Span,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Comparison {
+  Equal,
+  NotEqual,
+  Greater,
+  Less,
+  GreaterEqual,
+  LessEqual,
+}
+
+impl fmt::Display for Comparison {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.write_str(match self {
+      Comparison::Equal => "==",
+      Comparison::NotEqual => "<>",
+      Comparison::Greater => ">",
+      Comparison::Less => "<",
+      Comparison::GreaterEqual => ">=",
+      Comparison::LessEqual => "<=",
+    })
+  }
+}
+
+/// A prerequisite: the task only runs on when it holds.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Condition {
+  pub left: Expr,
+  pub comparison: Comparison,
+  pub right: Expr,
+  pub span: Span,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Statement {
+  /// `NAME = EXPR;` or `INSTRUMENT.PROPERTY = EXPR;`.
+  Assign { target: Place, value: Expr, span: Span },
+  /// `INSTRUMENT.ACTION();` or `INSTRUMENT.ACTION(EXPR);`.
+  Action {
+    instrument: Name,
+    action: Name,
+    argument: Option<Expr>,
+    span: Span,
+  },
+  /// `return EXPR, ...;` outputs the values.
+  Return { values: Vec<Expr>, span: Span },
+}
+
+impl Statement {
+  pub fn span(&self) -> Span {
+    match self {
+      Statement::Assign { span, .. } | Statement::Action { span, .. } | Statement::Return { span, .. } => *span,
+    }
+  }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Place {
+  Variable(Name),
+  Property(Name, Name),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BinaryOp {
+  Add,
+  Sub,
+  Mul,
+  Div,
+}
+
+impl fmt::Display for BinaryOp {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.write_str(match self {
+      BinaryOp::Add => "+",
+      BinaryOp::Sub => "-",
+      BinaryOp::Mul => "*",
+      BinaryOp::Div => "/",
+    })
+  }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expr {
+  Literal(Literal, Span),
+  /// A variable of the task.
+  Name(Name),
+  /// `INSTRUMENT.PROPERTY`, `TASK.VARIABLE` of a task in the same group or
+  /// `TASK.status` in prerequisites.
+  Member(Name, Name),
+  Negate(Box<Expr>, Span),
+  Binary(BinaryOp, Box<Expr>, Box<Expr>),
+  /// A built-in function such as `sin(x)` or `pow(x, y)`.
+  Call(Name, Vec<Expr>, Span),
+}
+
+impl Expr {
+  pub fn span(&self) -> Span {
+    match self {
+      Expr::Literal(_, span) | Expr::Negate(_, span) | Expr::Call(_, _, span) => *span,
+      Expr::Name(name) => name.span,
+      Expr::Member(left, right) => left.span.to(right.span),
+      Expr::Binary(_, left, right) => left.span().to(right.span()),
+    }
+  }
+}
@@ -0,0 +1,103 @@
+//! The names the task language gives to the instruments of the ISA, their
+//! properties and actions, and the built-in functions.
+
+use crate::asm::isa::*;
+use crate::compiler::ast::Type;
+
+/// `gps` for `INST_GPS`.
+pub fn instrument(name: &str) -> Option<u8> {
+  lookup(INSTRUMENTS, &format!("INST_{}", name)).filter(|_| is_lower(name))
+}
+
+pub fn instrument_name(id: u8) -> Option<String> {
+  name_of(INSTRUMENTS, "INST_", id).map(|mnemonic| mnemonic["INST_".len()..].to_ascii_lowercase())
+}
+
+fn is_lower(name: &str) -> bool {
+  !name.chars().any(|ch| ch.is_ascii_uppercase())
+}
+
+fn prefix(table: &str, instrument: u8) -> String {
+  format!("{}_{}_", table, instrument_name(instrument).unwrap_or_default().to_ascii_uppercase())
+}
+
+/// `gps.altt` for `P_GPS_ALTT`: the parameter and its mnemonic.
+pub fn property(instrument: u8, name: &str) -> Option<(u8, &'static str)> {
+  let mnemonic = format!("{}{}", prefix("P", instrument), name.to_ascii_uppercase());
+
+  PARAMETERS
+    .iter()
+    .find(|(candidate, _)| *candidate == mnemonic)
+    .filter(|_| is_lower(name))
+    .map(|(mnemonic, value)| (*value, *mnemonic))
+}
+
+/// `img.do_jpg` for `A_IMG_DO_JPG`: the action and its mnemonic.
+pub fn action(instrument: u8, name: &str) -> Option<(u8, &'static str)> {
+  let mnemonic = format!("{}{}", prefix("A", instrument), name.to_ascii_uppercase());
+
+  ACTIONS
+    .iter()
+    .find(|(candidate, _)| *candidate == mnemonic)
+    .filter(|_| is_lower(name))
+    .map(|(mnemonic, value)| (*value, *mnemonic))
+}
+
+/// The property names of an instrument, e.g. `latt` for `gps`.
+pub fn properties(instrument: u8) -> Vec<String> {
+  let prefix = prefix("P", instrument);
+
+  PARAMETERS
+    .iter()
+    .filter(|(mnemonic, _)| mnemonic.starts_with(&prefix))
+    .map(|(mnemonic, _)| mnemonic[prefix.len()..].to_ascii_lowercase())
+    .collect()
+}
+
+/// The action names of an instrument, e.g. `nadir` for `adc`.
+pub fn actions(instrument: u8) -> Vec<String> {
+  let prefix = prefix("A", instrument);
+
+  ACTIONS
+    .iter()
+    .filter(|(mnemonic, _)| mnemonic.starts_with(&prefix))
+    .map(|(mnemonic, _)| mnemonic[prefix.len()..].to_ascii_lowercase())
+    .collect()
+}
+
+/// The type of a property, by its mnemonic: the instruments read counters,
+/// modes and clocks as integers and everything else as floats.
+pub fn property_type(mnemonic: &str) -> Type {
+  match mnemonic {
+    "P_ADC_MODE" | "P_IMG_STATUS" | "P_IMG_NUMBER" | "P_NMF_TIME" | "P_VXM_TIME" | "P_VXM_TLSC" | "P_VXM_DBUG" => {
+      Type::I32
+    },
+    _ => Type::F32,
+  }
+}
+
+/// A built-in function: its name, parameter count and what it computes.
+pub struct Function {
+  pub name: &'static str,
+  pub arity: usize,
+  pub doc: &'static str,
+}
+
+pub const FUNCTIONS: &[Function] = &[
+  Function { name: "sin", arity: 1, doc: "sine of an angle in radians" },
+  Function { name: "cos", arity: 1, doc: "cosine of an angle in radians" },
+  Function { name: "tan", arity: 1, doc: "tangent of an angle in radians" },
+  Function { name: "asin", arity: 1, doc: "arcsine, in radians" },
+  Function { name: "acos", arity: 1, doc: "arccosine, in radians" },
+  Function { name: "atan", arity: 1, doc: "arctangent, in radians" },
+  Function { name: "pow", arity: 2, doc: "`pow(x, y)`, x to the power of y" },
+  Function { name: "log", arity: 2, doc: "`log(x, b)`, the logarithm of x in base b" },
+  Function { name: "sqrt", arity: 1, doc: "square root" },
+  Function { name: "exp", arity: 1, doc: "e to the power of x" },
+  Function { name: "i32", arity: 1, doc: "converts to an integer" },
+  Function { name: "f32", arity: 1, doc: "converts to a float" },
+];
+
+pub fn function(name: &str) -> Option<&'static Function> {
+  FUNCTIONS.iter().find(|function| function.name == name)
+}
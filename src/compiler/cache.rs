@@ -0,0 +1,645 @@
+//! Incremental compilation. Tasks are compiled one by one, so the `.splc`
+//! assembly, the debug information and the assembled words of every task are
+//! kept in a cache directory, keyed by a fingerprint of everything its code
+//! depends on:
+//!
+//! * the compiler version, the file, group and task names and ids
+//! * the target profile it is compiled for
+//! * the lines of the task section, and where it starts
+//! * the `.spli` files it is compiled against, those next to the file by default
+//! * the id and data declarations of every sibling task it reads
+//!
+//! The `index` of the cache holds the fingerprint each task was last compiled
+//! with, to explain why a task is compiled again:
+//!
+//! ```text
+//! task imaging position 1.1 src/tools/examples/orbit.spl
+//! compiler 0.1.0
+//! target opssat 9d2c4e61f08a7b35
+//! at 3:3
+//! source 5c3e0d2b1a7f9e44
+//! instruments cbf29ce484222325
+//! reads shoot 0f1e2d3c4b5a6978
+//! ```
+//!
+//! Every entry is a `KEY.splc`, a `KEY.spld` with the spans by line and by
+//! word, and, unless the task reads other tasks' data and has to be relinked,
+//! a `KEY.splx` with its words.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::asm::{self, Symbol, Task};
+use crate::compiler::ast::{self, Expr, Group, Place, Position, Statement};
+use crate::compiler::codegen::{self, Unit};
+use crate::compiler::parser;
+use crate::debug::{DebugInfo, TaskDebug};
+use crate::splx;
+use crate::target::Target;
+
+/// The cache directory, in the output directory.
+pub const DIRECTORY: &str = ".splice-cache";
+
+const INDEX: &str = "index";
+
+/// What the code of a task depends on.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Fingerprint {
+  pub file: String,
+  pub group: String,
+  pub task: String,
+  pub group_id: u8,
+  pub task_id: u8,
+  pub compiler: String,
+  /// The name of the target, and a hash of its profile.
+  pub target: (String, u64),
+  /// Where the task section starts.
+  pub position: Position,
+  /// A hash of the lines of the task section.
+  pub source: u64,
+  /// A hash of the `.spli` files.
+  pub instruments: u64,
+  /// The sibling tasks read, each with a hash of its id and data declarations.
+  pub reads: Vec<(String, u64)>,
+}
+
+impl fmt::Display for Fingerprint {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    writeln!(f, "task {} {} {}.{} {}", self.group, self.task, self.group_id, self.task_id, self.file)?;
+    writeln!(f, "compiler {}", self.compiler)?;
+    writeln!(f, "target {} {:016x}", self.target.0, self.target.1)?;
+    writeln!(f, "at {}", self.position)?;
+    writeln!(f, "source {:016x}", self.source)?;
+    writeln!(f, "instruments {:016x}", self.instruments)?;
+
+    for (task, hash) in &self.reads {
+      writeln!(f, "reads {} {:016x}", task, hash)?;
+    }
+
+    Ok(())
+  }
+}
+
+impl Fingerprint {
+  /// The name of the cache entry.
+  pub fn key(&self) -> String {
+    format!("{:016x}", hash(&self.to_string()))
+  }
+
+  fn same_task(&self, other: &Fingerprint) -> bool {
+    self.file == other.file && self.group == other.group && self.task == other.task
+  }
+
+  /// What changed since `previous`, in words.
+  pub fn changes(&self, previous: &Fingerprint) -> Vec<String> {
+    let mut changes = Vec::new();
+
+    if self.compiler != previous.compiler {
+      changes.push(format!("the compiler changed from {} to {}", previous.compiler, self.compiler));
+    }
+    if self.target.0 != previous.target.0 {
+      changes.push(format!("the target changed from {} to {}", previous.target.0, self.target.0));
+    }
+    else if self.target.1 != previous.target.1 {
+      changes.push(format!("the profile of target {} changed", self.target.0));
+    }
+    if self.source != previous.source {
+      changes.push("its source changed".to_string());
+    }
+    if (self.group_id, self.task_id) != (previous.group_id, previous.task_id) {
+      changes.push(format!(
+        "its id changed from {}.{} to {}.{}",
+        previous.group_id, previous.task_id, self.group_id, self.task_id
+      ));
+    }
+    if self.position != previous.position {
+      changes.push(format!("it moved from {} to {}", previous.position, self.position));
+    }
+    if self.instruments != previous.instruments {
+      changes.push("the instrument definitions changed".to_string());
+    }
+
+    for (task, hash) in &self.reads {
+      match previous.reads.iter().find(|(candidate, _)| candidate == task) {
+        None => changes.push(format!("it now reads task `{}`", task)),
+        Some((_, previous)) if previous != hash => changes.push(format!("the data of task `{}` changed", task)),
+        Some(_) => {},
+      }
+    }
+    for (task, _) in &previous.reads {
+      if !self.reads.iter().any(|(candidate, _)| candidate == task) {
+        changes.push(format!("it no longer reads task `{}`", task));
+      }
+    }
+
+    changes
+  }
+}
+
+/// Parses the fingerprints of an index; `None` if it is not one.
+fn parse_index(text: &str) -> Option<Vec<Fingerprint>> {
+  let mut fingerprints: Vec<Fingerprint> = Vec::new();
+
+  for line in text.lines().filter(|line| !line.trim().is_empty()) {
+    let (keyword, rest) = line.split_once(' ')?;
+
+    if keyword == "task" {
+      let mut fields = rest.splitn(4, ' ');
+      let group = fields.next()?.to_string();
+      let task = fields.next()?.to_string();
+      let (group_id, task_id) = fields.next()?.split_once('.')?;
+
+      fingerprints.push(Fingerprint {
+        file: fields.next()?.to_string(),
+        group,
+        task,
+        group_id: group_id.parse().ok()?,
+        task_id: task_id.parse().ok()?,
+        compiler: String::new(),
+        target: (String::new(), 0),
+        position: Position::default(),
+        source: 0,
+        instruments: 0,
+        reads: Vec::new(),
+      });
+      continue;
+    }
+
+    let fingerprint = fingerprints.last_mut()?;
+    let hex = |text: &str| u64::from_str_radix(text, 16).ok();
+
+    match keyword {
+      "compiler" => fingerprint.compiler = rest.to_string(),
+      "target" => {
+        let (name, hash) = rest.split_once(' ')?;
+
+        fingerprint.target = (name.to_string(), hex(hash)?);
+      },
+      "at" => {
+        let (line, column) = rest.split_once(':')?;
+
+        fingerprint.position = Position {
+          line: line.parse::<usize>().ok()?.checked_sub(1)?,
+          column: column.parse::<usize>().ok()?.checked_sub(1)?,
+        };
+      },
+      "source" => fingerprint.source = hex(rest)?,
+      "instruments" => fingerprint.instruments = hex(rest)?,
+      "reads" => {
+        let (task, hash) = rest.split_once(' ')?;
+
+        fingerprint.reads.push((task.to_string(), hex(hash)?));
+      },
+      _ => return None,
+    }
+  }
+
+  Some(fingerprints)
+}
+
+/// A cache directory. Entries are only written by `save`.
+#[derive(Debug)]
+pub struct Cache {
+  directory: PathBuf,
+  index: Vec<Fingerprint>,
+  /// Entries to write, by key: the assembly, the debug information and the
+  /// words if they can be reused as they are.
+  pending: Vec<(String, String, String, Option<String>)>,
+}
+
+/// A task compiled and assembled, or taken from the cache.
+#[derive(Clone, Debug)]
+pub struct Compiled {
+  pub unit: Unit,
+  pub task: Task,
+  /// Why the task was compiled, `None` when it comes from the cache.
+  pub reason: Option<String>,
+}
+
+impl Cache {
+  /// Opens the cache in `directory`, empty if there is none or if its index
+  /// cannot be read.
+  pub fn open(directory: &Path) -> Cache {
+    let index = fs::read_to_string(directory.join(INDEX))
+      .ok()
+      .and_then(|text| parse_index(&text))
+      .unwrap_or_default();
+
+    Cache {
+      directory: directory.to_path_buf(),
+      index,
+      pending: Vec::new(),
+    }
+  }
+
+  fn path(&self, key: &str, extension: &str) -> PathBuf {
+    self.directory.join(format!("{}.{}", key, extension))
+  }
+
+  fn previous(&self, fingerprint: &Fingerprint) -> Option<&Fingerprint> {
+    self.index.iter().find(|previous| previous.same_task(fingerprint))
+  }
+
+  /// The unit of an entry, and the task assembled from it.
+  fn load(&self, target: &Target, key: &str, directory: &Path) -> Option<(Unit, Task)> {
+    let assembly = fs::read_to_string(self.path(key, "splc")).ok()?;
+    let info = DebugInfo::parse(&fs::read_to_string(self.path(key, "spld")).ok()?).ok()?;
+    let debug = info.tasks.into_iter().next()?;
+    let unit = Unit {
+      group: debug.group.clone(),
+      task: debug.task.clone(),
+      group_id: debug.group_id,
+      task_id: debug.task_id,
+      assembly,
+      debug: TaskDebug {
+        words: Vec::new(),
+        ..debug.clone()
+      },
+    };
+
+    let task = match fs::read_to_string(self.path(key, "splx")) {
+      Ok(text) => {
+        let mut task = splx::parse_for(target, &text).ok()?.into_iter().next()?;
+
+        // only variables can be read by other tasks
+        task.symbols = debug
+          .variables
+          .iter()
+          .map(|variable| Symbol {
+            name: variable.name.clone(),
+            address: variable.address,
+          })
+          .collect();
+        task.debug = Some(TaskDebug {
+          lines: Vec::new(),
+          ..debug
+        });
+        task
+      },
+      Err(_) => assemble(target, &unit, directory).ok()?,
+    };
+
+    Some((unit, task))
+  }
+
+  /// Records the fingerprint a task was compiled with, and its entry if it
+  /// was not taken from the cache.
+  fn update(&mut self, target: &Target, fingerprint: Fingerprint, compiled: &Compiled) {
+    if compiled.reason.is_some() {
+      let debug = TaskDebug {
+        words: compiled.task.debug.as_ref().map_or_else(Vec::new, |debug| debug.words.clone()),
+        ..compiled.unit.debug.clone()
+      };
+      let words = Some(splx::format_task_for(target, &compiled.task.words())).filter(|_| compiled.task.relocations.is_empty());
+
+      self.pending.push((
+        fingerprint.key(),
+        compiled.unit.assembly.clone(),
+        DebugInfo { tasks: vec![debug] }.to_string(),
+        words,
+      ));
+    }
+
+    self.index.push(fingerprint);
+  }
+
+  /// Writes the new entries and the index, and removes the entries no task
+  /// refers to anymore.
+  pub fn save(&mut self) -> io::Result<()> {
+    fs::create_dir_all(&self.directory)?;
+
+    for (key, assembly, debug, words) in self.pending.drain(..) {
+      fs::write(self.directory.join(format!("{}.splc", key)), assembly)?;
+      fs::write(self.directory.join(format!("{}.spld", key)), debug)?;
+      if let Some(words) = words {
+        fs::write(self.directory.join(format!("{}.splx", key)), words + "\n")?;
+      }
+    }
+
+    let index: String = self.index.iter().map(|fingerprint| fingerprint.to_string()).collect();
+    fs::write(self.directory.join(INDEX), index)?;
+
+    let keys: HashSet<String> = self.index.iter().map(Fingerprint::key).collect();
+    for entry in fs::read_dir(&self.directory)? {
+      let path = entry?.path();
+      let stale = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .is_some_and(|stem| stem != INDEX && !keys.contains(stem));
+
+      if stale {
+        fs::remove_file(path)?;
+      }
+    }
+
+    Ok(())
+  }
+}
+
+/// FNV-1a, which unlike `DefaultHasher` is the same for every build.
+fn hash(text: &str) -> u64 {
+  text
+    .bytes()
+    .fold(0xcbf2_9ce4_8422_2325, |hash, byte| (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3))
+}
+
+/// Assembles a unit as if it had been written to its `.splc` file in
+/// `directory`.
+fn assemble(target: &Target, unit: &Unit, directory: &Path) -> Result<Task, String> {
+  let mut task =
+    asm::assemble_for(target, &directory.join(unit.file_name()), &unit.assembly).map_err(|err| err.to_string())?;
+
+  task.debug = Some(unit.debug.assembled(&task.sources));
+  Ok(task)
+}
+
+/// The sibling tasks `task` reads data or the status of, with their index.
+fn reads<'a>(group: &'a Group, task: &ast::Task) -> Vec<(usize, &'a ast::Task)> {
+  fn expr<'e>(node: &'e Expr, members: &mut Vec<&'e str>) {
+    match node {
+      Expr::Literal(..) | Expr::Name(_) => {},
+      Expr::Member(left, _) => members.push(&left.text),
+      Expr::Negate(operand, _) => expr(operand, members),
+      Expr::Binary(_, left, right) => {
+        expr(left, members);
+        expr(right, members);
+      },
+      Expr::Call(_, arguments, _) => arguments.iter().for_each(|argument| expr(argument, members)),
+    }
+  }
+
+  let mut members = Vec::new();
+
+  for condition in &task.preq {
+    expr(&condition.left, &mut members);
+    expr(&condition.right, &mut members);
+  }
+  for statement in &task.exec {
+    match statement {
+      Statement::Assign { target, value, .. } => {
+        if let Place::Property(left, _) = target {
+          members.push(&left.text);
+        }
+        expr(value, &mut members);
+      },
+      Statement::Action { argument, .. } => argument.iter().for_each(|argument| expr(argument, &mut members)),
+      Statement::Return { values, .. } => values.iter().for_each(|value| expr(value, &mut members)),
+    }
+  }
+
+  group
+    .tasks
+    .iter()
+    .enumerate()
+    .filter(|(_, sibling)| members.contains(&sibling.name.text.as_str()))
+    .collect()
+}
+
+/// Compiles a task list file like `compiler::compile`, for `target`, and
+/// assembles its tasks as if written to `directory`, taking the tasks whose
+/// fingerprint did not change from `cache` and adding the others to it.
+/// `instruments` are the `.spli` files the tasks are compiled against,
+/// usually `spli::files(file)`.
+pub fn compile(
+  target: &Target,
+  file: &str,
+  source: &str,
+  instruments: &[PathBuf],
+  directory: &Path,
+  cache: &mut Cache,
+) -> Result<Vec<Compiled>, String> {
+  let failed = |errors: Vec<crate::compiler::CompileError>| {
+    let mut message: String = errors.iter().map(|error| format!("error: {}\n", error)).collect();
+
+    message.push_str(&format!("compilation failed with {} error(s)", errors.len()));
+    message
+  };
+  let program = parser::parse(file, source).map_err(|error| failed(vec![error]))?;
+
+  let mut definitions = String::new();
+  for path in instruments {
+    let text = fs::read_to_string(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+
+    definitions.push_str(&format!("{}\n{}\n", path.display(), text));
+  }
+  let instruments = hash(&definitions);
+  let profile = (target.name.clone(), hash(&format!("{:?}", target)));
+
+  let mut fingerprints = Vec::new();
+  let units = codegen::generate_with(target, file, source, &program, |group, task, (group_id, task_id)| {
+    let lines: Vec<&str> = source
+      .lines()
+      .skip(task.span.start.line)
+      .take(task.span.end.line - task.span.start.line + 1)
+      .collect();
+    let reads = reads(group, task)
+      .into_iter()
+      .map(|(index, sibling)| {
+        let data: String = sibling
+          .data
+          .iter()
+          .map(|variable| format!("{}:{}:{};", variable.name.text, variable.ty, variable.constant))
+          .collect();
+
+        (sibling.name.text.clone(), hash(&format!("{} {}", index + 1, data)))
+      })
+      .collect();
+    let fingerprint = Fingerprint {
+      file: file.to_string(),
+      group: group.name.text.clone(),
+      task: task.name.text.clone(),
+      group_id,
+      task_id,
+      compiler: env!("CARGO_PKG_VERSION").to_string(),
+      target: profile.clone(),
+      position: task.span.start,
+      source: hash(&lines.join("\n")),
+      instruments,
+      reads,
+    };
+    let reason = match cache.previous(&fingerprint) {
+      None => "it was not compiled before".to_string(),
+      Some(previous) => match fingerprint.changes(previous) {
+        changes if changes.is_empty() => "its cache entry is missing".to_string(),
+        changes => changes.join(", "),
+      },
+    };
+    let hit = cache.load(target, &fingerprint.key(), directory);
+    let unit = hit.as_ref().map(|(unit, _)| unit.clone());
+
+    fingerprints.push((fingerprint, hit.map(|(_, task)| task).ok_or(reason)));
+    unit
+  });
+  let units = units.map_err(failed)?;
+
+  // tasks removed from the file are forgotten
+  cache.index.retain(|previous| previous.file != file);
+
+  let mut compiled = Vec::with_capacity(units.len());
+  for (unit, (fingerprint, hit)) in units.into_iter().zip(fingerprints) {
+    let entry = match hit {
+      Ok(task) => Compiled {
+        unit,
+        task,
+        reason: None,
+      },
+      Err(reason) => Compiled {
+        task: assemble(target, &unit, directory)?,
+        unit,
+        reason: Some(reason),
+      },
+    };
+
+    cache.update(target, fingerprint, &entry);
+    compiled.push(entry);
+  }
+
+  Ok(compiled)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const SOURCE: &str = "group g {
+  task a {
+    data { var x: f32 = 0.0; }
+    freq: 10s;
+    exec { x = gps.latt; }
+  }
+
+  task b {
+    freq: 1m;
+    preq { a.x > 1.0; }
+    exec { img.do_jpg(); }
+  }
+}
+";
+
+  /// A scratch output directory with a `.spli` file, removed when dropped.
+  struct Project {
+    directory: PathBuf,
+    spli: PathBuf,
+  }
+
+  impl Project {
+    fn new(name: &str) -> Project {
+      let directory = std::env::temp_dir().join(format!("splice-cache-{}-{}", std::process::id(), name));
+      let spli = directory.join("opssat.spli");
+
+      let _ = fs::remove_dir_all(&directory);
+      fs::create_dir_all(&directory).unwrap();
+      fs::write(&spli, "inst gps { prop latt: f32; }\n").unwrap();
+
+      Project { directory, spli }
+    }
+
+    /// Compiles `source` with the cache of the previous build, and returns
+    /// why every task was compiled, `None` for the tasks from the cache.
+    fn build(&self, target: &Target, source: &str) -> Vec<Option<String>> {
+      let mut cache = Cache::open(&self.directory.join(DIRECTORY));
+      let instruments = std::slice::from_ref(&self.spli);
+      let compiled = compile(target, "test.spl", source, instruments, &self.directory, &mut cache).unwrap();
+
+      cache.save().unwrap();
+      compiled.into_iter().map(|compiled| compiled.reason).collect()
+    }
+  }
+
+  impl Drop for Project {
+    fn drop(&mut self) {
+      let _ = fs::remove_dir_all(&self.directory);
+    }
+  }
+
+  fn reason(text: &str) -> Option<String> {
+    Some(text.to_string())
+  }
+
+  #[test]
+  fn reuses_the_tasks_of_an_unchanged_file() {
+    let project = Project::new("unchanged");
+    let target = Target::opssat();
+
+    let new = reason("it was not compiled before");
+    assert_eq!(project.build(target, SOURCE), [new.clone(), new]);
+    assert_eq!(project.build(target, SOURCE), [None, None]);
+  }
+
+  #[test]
+  fn compiles_a_task_again_when_its_source_changes() {
+    let project = Project::new("source");
+    let target = Target::opssat();
+    let changed = SOURCE.replace("x = gps.latt;", "x = gps.latt * 2;");
+
+    project.build(target, SOURCE);
+    assert_eq!(project.build(target, &changed), [reason("its source changed"), None]);
+    assert_eq!(project.build(target, &changed), [None, None]);
+  }
+
+  #[test]
+  fn compiles_the_readers_of_a_task_again_when_its_data_changes() {
+    let project = Project::new("data");
+    let target = Target::opssat();
+    let changed = SOURCE.replace("var x: f32 = 0.0;", "var x: f32 = 0.0; var y: i32 = 0;");
+
+    project.build(target, SOURCE);
+    assert_eq!(
+      project.build(target, &changed),
+      [reason("its source changed"), reason("the data of task `a` changed")]
+    );
+  }
+
+  #[test]
+  fn compiles_every_task_again_when_the_instruments_change() {
+    let project = Project::new("instruments");
+    let target = Target::opssat();
+
+    project.build(target, SOURCE);
+    fs::write(&project.spli, "inst gps { prop latt: f32; prop long: f32; }\n").unwrap();
+
+    let changed = reason("the instrument definitions changed");
+    assert_eq!(project.build(target, SOURCE), [changed.clone(), changed]);
+  }
+
+  #[test]
+  fn compiles_every_task_again_for_another_target() {
+    let project = Project::new("target");
+    let reference = Target::builtin("reference").unwrap();
+    let costly = Target {
+      costs: "reference".to_string(),
+      ..Target::opssat().clone()
+    };
+
+    project.build(Target::opssat(), SOURCE);
+
+    let changed = reason("the target changed from opssat to reference");
+    assert_eq!(project.build(&reference, SOURCE), [changed.clone(), changed]);
+
+    project.build(Target::opssat(), SOURCE);
+
+    let changed = reason("the profile of target opssat changed");
+    assert_eq!(project.build(&costly, SOURCE), [changed.clone(), changed]);
+  }
+
+  #[test]
+  fn compiles_a_task_again_when_its_entry_is_missing() {
+    let project = Project::new("missing");
+    let target = Target::opssat();
+
+    project.build(target, SOURCE);
+    for entry in fs::read_dir(project.directory.join(DIRECTORY)).unwrap() {
+      let path = entry.unwrap().path();
+
+      if path.extension().is_some_and(|extension| extension == "spld") {
+        fs::remove_file(path).unwrap();
+      }
+    }
+
+    let missing = reason("its cache entry is missing");
+    assert_eq!(project.build(target, SOURCE), [missing.clone(), missing]);
+  }
+}
@@ -0,0 +1,818 @@
+use std::collections::HashSet;
+
+use crate::compiler::ast::*;
+use crate::compiler::builtins;
+use crate::compiler::CompileError;
+use crate::debug::{self, DebugVariable, TaskDebug};
+use crate::target::{Instrument, Operand, Parameter, Target};
+
+/// Groups and tasks per group the VM can address.
+pub const MAX_GROUPS: usize = 16;
+pub const MAX_TASKS: usize = 16;
+
+/// The assembly of one task.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Unit {
+  pub group: String,
+  pub task: String,
+  pub group_id: u8,
+  pub task_id: u8,
+  /// `.splc` source.
+  pub assembly: String,
+  /// The span of every instruction by `.splc` line, and the variables.
+  pub debug: TaskDebug,
+}
+
+impl Unit {
+  /// `GROUP_TASK.splc`.
+  pub fn file_name(&self) -> String {
+    format!("{}_{}.splc", self.group, self.task)
+  }
+
+  /// `GROUP_TASK.spld`, the debug information sidecar.
+  pub fn debug_file_name(&self) -> String {
+    format!("{}_{}.{}", self.group, self.task, debug::EXTENSION)
+  }
+}
+
+/// A register of the register file of the target, `index` counting from
+/// the first register of its type.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Register<'a> {
+  ty: Type,
+  index: usize,
+  mnemonic: &'a str,
+}
+
+impl<'a> Register<'a> {
+  fn name(self) -> &'a str {
+    self.mnemonic
+  }
+}
+
+/// What a `TASK.NAME` or `INSTRUMENT.NAME` member refers to.
+enum Member<'a> {
+  Data { task_id: Option<u8>, variable: &'a Variable },
+  Property { instrument: &'a Instrument, parameter: &'a Parameter },
+  Status { task_id: u8 },
+}
+
+/// Generates the assembly of the tasks of a program for OPS-SAT. Group and
+/// task ids are given in declaration order, from 1.
+pub fn generate(file: &str, source: &str, program: &Program) -> Result<Vec<Unit>, Vec<CompileError>> {
+  generate_with(Target::opssat(), file, source, program, |_, _, _| None)
+}
+
+/// Like `generate`, for `target`, but takes the unit of a task from
+/// `cached`, given the group, the task and its ids, instead of generating it
+/// when there is one. The checks over the whole program are still made.
+pub fn generate_with(
+  target: &Target,
+  file: &str,
+  source: &str,
+  program: &Program,
+  mut cached: impl FnMut(&Group, &Task, (u8, u8)) -> Option<Unit>,
+) -> Result<Vec<Unit>, Vec<CompileError>> {
+  let mut errors = Vec::new();
+  let mut units = Vec::new();
+  let error = |span: Span, message: String| CompileError {
+    file: file.to_string(),
+    span,
+    message,
+  };
+
+  if program.groups.len() > MAX_GROUPS {
+    errors.push(error(program.groups[MAX_GROUPS].name.span, format!("at most {} groups are allowed", MAX_GROUPS)));
+  }
+
+  let mut group_names = HashSet::new();
+  for (group_index, group) in program.groups.iter().enumerate() {
+    if !group_names.insert(&group.name.text) {
+      errors.push(error(group.name.span, format!("group `{}` is declared twice", group.name.text)));
+    }
+    if group.tasks.len() > MAX_TASKS {
+      errors.push(error(group.tasks[MAX_TASKS].name.span, format!("at most {} tasks are allowed in a group", MAX_TASKS)));
+    }
+
+    let mut task_names = HashSet::new();
+    for (task_index, task) in group.tasks.iter().enumerate() {
+      if !task_names.insert(&task.name.text) {
+        errors.push(error(task.name.span, format!("task `{}` is declared twice in group `{}`", task.name.text, group.name.text)));
+      }
+      if target.instrument_named(&task.name.text).is_some() {
+        errors.push(error(task.name.span, format!("task `{}` would hide the instrument of that name", task.name.text)));
+      }
+
+      let ids = ((group_index + 1) as u8, (task_index + 1) as u8);
+      if let Some(unit) = cached(group, task, ids) {
+        units.push(unit);
+        continue;
+      }
+
+      let mut generator = Generator::new(target, file, source, group, task, ids.0, ids.1);
+
+      match generator.task() {
+        Ok(assembly) => units.push(Unit {
+          group: group.name.text.clone(),
+          task: task.name.text.clone(),
+          group_id: generator.group_id,
+          task_id: generator.task_id,
+          assembly,
+          debug: generator.debug(),
+        }),
+        Err(task_errors) => errors.extend(task_errors),
+      }
+    }
+  }
+
+  if errors.is_empty() {
+    Ok(units)
+  }
+  else {
+    Err(errors)
+  }
+}
+
+/// The type of an expression of `task`, without generating it.
+pub fn infer(file: &str, group: &Group, task: &Task, expr: &Expr) -> Result<Type, CompileError> {
+  Generator::new(Target::opssat(), file, "", group, task, 1, 1).infer(expr)
+}
+
+struct Generator<'a> {
+  target: &'a Target,
+  file: &'a str,
+  source: &'a str,
+  group: &'a Group,
+  task: &'a Task,
+  group_id: u8,
+  task_id: u8,
+  lines: Vec<String>,
+  /// The span of the node being generated, and of every instruction by its
+  /// index in `lines`.
+  span: Span,
+  spans: Vec<(usize, Span)>,
+  /// Literals kept in the data section, by label.
+  constants: Vec<(String, Literal)>,
+  /// The next free register of each type.
+  ints: usize,
+  floats: usize,
+}
+
+type Generated<T> = Result<T, CompileError>;
+
+impl<'a> Generator<'a> {
+  fn new(
+    target: &'a Target,
+    file: &'a str,
+    source: &'a str,
+    group: &'a Group,
+    task: &'a Task,
+    group_id: u8,
+    task_id: u8,
+  ) -> Generator<'a> {
+    Generator {
+      target,
+      file,
+      source,
+      group,
+      task,
+      group_id,
+      task_id,
+      lines: Vec::new(),
+      span: Span::default(),
+      spans: Vec::new(),
+      constants: Vec::new(),
+      ints: 0,
+      floats: 0,
+    }
+  }
+
+  fn error<T>(&self, span: Span, message: String) -> Generated<T> {
+    Err(CompileError {
+      file: self.file.to_string(),
+      span,
+      message,
+    })
+  }
+
+  fn emit(&mut self, line: String) {
+    self.spans.push((self.lines.len(), self.span));
+    self.lines.push(line);
+  }
+
+  /// A comment with the location and first source line of a node, which the
+  /// instructions that follow are generated from.
+  fn comment(&mut self, span: Span) {
+    let text = self.source.lines().nth(span.start.line).unwrap_or("").trim();
+
+    self.span = span;
+    self.lines.push(format!("; {}:{} {}", self.file, span.start.line + 1, text));
+  }
+
+  fn task(&mut self) -> Result<String, Vec<CompileError>> {
+    let mut errors = Vec::new();
+    let task = self.task;
+
+    let mut names = HashSet::new();
+    for variable in &task.data {
+      if !names.insert(&variable.name.text) {
+        errors.push(CompileError {
+          file: self.file.to_string(),
+          span: variable.name.span,
+          message: format!("variable `{}` is declared twice", variable.name.text),
+        });
+      }
+      if !variable.name.text.is_ascii() {
+        errors.push(CompileError {
+          file: self.file.to_string(),
+          span: variable.name.span,
+          message: "variable names have to be ASCII".to_string(),
+        });
+      }
+    }
+
+    for condition in &task.preq {
+      self.reset();
+      self.comment(condition.span);
+      if let Err(error) = self.condition(condition) {
+        errors.push(error);
+      }
+    }
+
+    let mut returned = false;
+    for statement in &task.exec {
+      if returned {
+        errors.push(CompileError {
+          file: self.file.to_string(),
+          span: statement.span(),
+          message: "statement after `return`".to_string(),
+        });
+        break;
+      }
+
+      self.reset();
+      self.comment(statement.span());
+      returned = matches!(statement, Statement::Return { .. });
+      if let Err(error) = self.statement(statement) {
+        errors.push(error);
+      }
+    }
+
+    if !errors.is_empty() {
+      return Err(errors);
+    }
+
+    let byte = task.freq.to_byte().unwrap_or(0);
+    let mut text = format!(
+      "; group {}, task {} ({}:{})\n{},{},{}\n",
+      self.group.name.text,
+      task.name.text,
+      self.file,
+      task.span.start.line + 1,
+      self.group_id,
+      self.task_id,
+      byte
+    );
+
+    for line in &self.lines {
+      text.push_str(line);
+      text.push('\n');
+    }
+    text.push_str("OP_HLT\n");
+
+    for variable in &task.data {
+      text.push_str(&format!("{}: {}\n", variable.name.text, data_value(variable.value)));
+    }
+    for (label, value) in &self.constants {
+      text.push_str(&format!("{}: {}\n", label, data_value(*value)));
+    }
+
+    Ok(text)
+  }
+
+  /// The debug information of the task, once generated: the assembly starts
+  /// with a comment and the header, so `lines[0]` is on line 3.
+  fn debug(&self) -> TaskDebug {
+    let variables = self
+      .task
+      .data
+      .iter()
+      .enumerate()
+      .map(|(index, variable)| DebugVariable {
+        address: (index + 1) as u8,
+        name: variable.name.text.clone(),
+        ty: variable.ty,
+        constant: variable.constant,
+      })
+      .collect();
+
+    TaskDebug {
+      group_id: self.group_id,
+      task_id: self.task_id,
+      group: self.group.name.text.clone(),
+      task: self.task.name.text.clone(),
+      file: self.file.to_string(),
+      lines: self.spans.iter().map(|(index, span)| (index + 3, *span)).collect(),
+      words: Vec::new(),
+      variables,
+    }
+  }
+
+  fn reset(&mut self) {
+    self.ints = 0;
+    self.floats = 0;
+  }
+
+  fn allocate(&mut self, ty: Type, span: Span) -> Generated<Register<'a>> {
+    let registers = &self.target.registers;
+    let next = match ty {
+      Type::I32 => &mut self.ints,
+      Type::F32 => &mut self.floats,
+    };
+
+    let index = *next;
+    match registers.name(ty, index) {
+      Some(mnemonic) => {
+        *next += 1;
+        Ok(Register { ty, index, mnemonic })
+      },
+      None => self.error(span, format!("the expression needs more than {} {} registers", registers.count(ty), ty)),
+    }
+  }
+
+  /// Frees every register of the type of `register` above it.
+  fn release_above(&mut self, register: Register<'a>) {
+    match register.ty {
+      Type::I32 => self.ints = register.index + 1,
+      Type::F32 => self.floats = register.index + 1,
+    }
+  }
+
+  /// The data label of a literal.
+  fn constant(&mut self, value: Literal) -> String {
+    let same = |candidate: &Literal| match (candidate, value) {
+      (Literal::Int(a), Literal::Int(b)) => *a == b,
+      (Literal::Float(a), Literal::Float(b)) => a.to_bits() == b.to_bits(),
+      _ => false,
+    };
+
+    if let Some((label, _)) = self.constants.iter().find(|(_, candidate)| same(candidate)) {
+      return label.clone();
+    }
+
+    let label = format!("_k{}", self.constants.len() + 1);
+    self.constants.push((label.clone(), value));
+    label
+  }
+
+  fn load_constant(&mut self, value: Literal, span: Span) -> Generated<Register<'a>> {
+    let register = self.allocate(value.ty(), span)?;
+    let label = self.constant(value);
+
+    self.emit(format!("OP_LEA, {}, self.{}", register.name(), label));
+    Ok(register)
+  }
+
+  fn variable(&self, name: &Name) -> Generated<&'a Variable> {
+    match self.task.data.iter().find(|variable| variable.name.text == name.text) {
+      Some(variable) => Ok(variable),
+      None => self.error(name.span, format!("undefined variable `{}`", name.text)),
+    }
+  }
+
+  fn member(&self, left: &Name, right: &Name) -> Generated<Member<'a>> {
+    let sibling = self
+      .group
+      .tasks
+      .iter()
+      .position(|task| task.name.text == left.text);
+
+    if let Some(index) = sibling {
+      let task = &self.group.tasks[index];
+      let task_id = (index + 1) as u8;
+
+      if right.text == "status" && !task.data.iter().any(|variable| variable.name.text == "status") {
+        return Ok(Member::Status { task_id });
+      }
+
+      return match task.data.iter().find(|variable| variable.name.text == right.text) {
+        Some(variable) => Ok(Member::Data {
+          task_id: Some(task_id).filter(|id| *id != self.task_id),
+          variable,
+        }),
+        None => self.error(right.span, format!("task `{}` has no variable `{}`", left.text, right.text)),
+      };
+    }
+
+    match self.target.instrument_named(&left.text) {
+      Some(instrument) => match instrument.property(&right.text) {
+        Some(parameter) => Ok(Member::Property { instrument, parameter }),
+        None => self.error(
+          right.span,
+          format!(
+            "instrument `{}` has no property `{}`; it has {}",
+            left.text,
+            right.text,
+            instrument.properties().join(", ")
+          ),
+        ),
+      },
+      None => self.error(
+        left.span,
+        format!("`{}` is neither a task of group `{}` nor an instrument", left.text, self.group.name.text),
+      ),
+    }
+  }
+
+  pub fn infer(&self, expr: &Expr) -> Generated<Type> {
+    match expr {
+      Expr::Literal(value, _) => Ok(value.ty()),
+      Expr::Name(name) => Ok(self.variable(name)?.ty),
+      Expr::Member(left, right) => match self.member(left, right)? {
+        Member::Data { variable, .. } => Ok(variable.ty),
+        Member::Property { parameter, .. } => Ok(parameter.ty),
+        Member::Status { .. } => Ok(Type::I32),
+      },
+      Expr::Negate(operand, _) => self.infer(operand),
+      Expr::Binary(_, left, right) => Ok(join(self.infer(left)?, self.infer(right)?)),
+      Expr::Call(name, arguments, span) => {
+        let function = match builtins::function(&name.text) {
+          Some(function) => function,
+          None => return self.error(name.span, format!("unknown function `{}`", name.text)),
+        };
+
+        if arguments.len() != function.arity {
+          return self.error(
+            *span,
+            format!("`{}` takes {} argument(s), {} given", name.text, function.arity, arguments.len()),
+          );
+        }
+        for argument in arguments {
+          self.infer(argument)?;
+        }
+
+        Ok(if function.name == "i32" { Type::I32 } else { Type::F32 })
+      },
+    }
+  }
+
+  /// Generates `expr` into a new register, which is the topmost register of
+  /// its type in use when this returns; registers of the other type are
+  /// left as they were.
+  fn expr(&mut self, expr: &Expr) -> Generated<Register<'a>> {
+    let outer = std::mem::replace(&mut self.span, expr.span());
+    let register = self.expr_code(expr);
+
+    self.span = outer;
+    register
+  }
+
+  fn expr_code(&mut self, expr: &Expr) -> Generated<Register<'a>> {
+    match expr {
+      Expr::Literal(value, span) => self.load_constant(*value, *span),
+      Expr::Name(name) => {
+        let variable = self.variable(name)?;
+        let register = self.allocate(variable.ty, name.span)?;
+
+        self.emit(format!("OP_LEA, {}, self.{}", register.name(), variable.name.text));
+        Ok(register)
+      },
+      Expr::Member(left, right) => match self.member(left, right)? {
+        Member::Data { task_id, variable } => {
+          let register = self.allocate(variable.ty, right.span)?;
+          let task = task_id.map_or_else(|| "self".to_string(), |id| format!("t{}", id));
+
+          self.emit(format!("OP_LEA, {}, {}.{}", register.name(), task, variable.name.text));
+          Ok(register)
+        },
+        Member::Property { instrument, parameter } => {
+          let register = self.allocate(parameter.ty, right.span)?;
+
+          self.emit(format!("OP_GET, {}, {}, {}", instrument.mnemonic, parameter.mnemonic, register.name()));
+          Ok(register)
+        },
+        Member::Status { .. } => self.error(
+          left.span.to(right.span),
+          "the status of a task can only be compared in `preq`, e.g. `task.status == 255`".to_string(),
+        ),
+      },
+      Expr::Negate(operand, span) => {
+        // 0/1 - x
+        let ty = self.infer(operand)?;
+        let result = self.load_constant(zero(ty), *span)?;
+        let value = self.expr_as(operand, ty)?;
+        let one = self.load_constant(one(ty), *span)?;
+
+        self.emit(format!("OP_FSD, {}, {}, {}", value.name(), one.name(), result.name()));
+        self.release_above(result);
+        Ok(result)
+      },
+      Expr::Binary(op, left, right) => {
+        let ty = join(self.infer(left)?, self.infer(right)?);
+        let result = self.expr_as(left, ty)?;
+        let value = self.expr_as(right, ty)?;
+        let span = expr.span();
+
+        // FMA: C = C*B + A, FSD: C = C/B - A
+        match op {
+          BinaryOp::Add | BinaryOp::Sub => {
+            let one = self.load_constant(one(ty), span)?;
+            let opcode = if *op == BinaryOp::Add { "OP_FMA" } else { "OP_FSD" };
+
+            self.emit(format!("{}, {}, {}, {}", opcode, value.name(), one.name(), result.name()));
+          },
+          BinaryOp::Mul | BinaryOp::Div => {
+            let zero = self.load_constant(zero(ty), span)?;
+            let opcode = if *op == BinaryOp::Mul { "OP_FMA" } else { "OP_FSD" };
+
+            self.emit(format!("{}, {}, {}, {}", opcode, zero.name(), value.name(), result.name()));
+          },
+        }
+
+        self.release_above(result);
+        Ok(result)
+      },
+      Expr::Call(name, arguments, span) => {
+        self.infer(expr)?;
+
+        match name.text.as_str() {
+          "i32" => self.expr_as(&arguments[0], Type::I32),
+          "f32" => self.expr_as(&arguments[0], Type::F32),
+          "sin" | "cos" | "tan" | "asin" | "acos" | "atan" => {
+            let value = self.expr_as(&arguments[0], Type::F32)?;
+            let (opcode, prefix) = match name.text.as_str() {
+              "sin" => ("OP_SIN", "PRE_NORMAL"),
+              "cos" => ("OP_COS", "PRE_NORMAL"),
+              "tan" => ("OP_TAN", "PRE_NORMAL"),
+              "asin" => ("OP_SIN", "PRE_INVERT"),
+              "acos" => ("OP_COS", "PRE_INVERT"),
+              _ => ("OP_TAN", "PRE_INVERT"),
+            };
+
+            self.emit(format!("{}, {}, {}, {}", opcode, prefix, value.name(), value.name()));
+            Ok(value)
+          },
+          // POW: B = B^A with PRE_NORMAL, B = log_A(B) with PRE_INVERT
+          "pow" | "log" => {
+            let value = self.expr_as(&arguments[0], Type::F32)?;
+            let operand = self.expr_as(&arguments[1], Type::F32)?;
+            let prefix = if name.text == "pow" { "PRE_NORMAL" } else { "PRE_INVERT" };
+
+            self.emit(format!("OP_POW, {}, {}, {}", prefix, operand.name(), value.name()));
+            self.release_above(value);
+            Ok(value)
+          },
+          "sqrt" => {
+            let value = self.expr_as(&arguments[0], Type::F32)?;
+            let half = self.load_constant(Literal::Float(0.5), *span)?;
+
+            self.emit(format!("OP_POW, PRE_NORMAL, {}, {}", half.name(), value.name()));
+            self.release_above(value);
+            Ok(value)
+          },
+          _ => {
+            // exp
+            let result = self.allocate(Type::F32, *span)?;
+            self.emit(format!("OP_GET, INST_FPU, P_FPU_EXP, {}", result.name()));
+            let value = self.expr_as(&arguments[0], Type::F32)?;
+
+            self.emit(format!("OP_POW, PRE_NORMAL, {}, {}", value.name(), result.name()));
+            self.release_above(result);
+            Ok(result)
+          },
+        }
+      },
+    }
+  }
+
+  /// Generates `expr` converted to `ty`.
+  fn expr_as(&mut self, expr: &Expr, ty: Type) -> Generated<Register<'a>> {
+    let outer = std::mem::replace(&mut self.span, expr.span());
+    let register = self.expr_as_code(expr, ty);
+
+    self.span = outer;
+    register
+  }
+
+  fn expr_as_code(&mut self, expr: &Expr, ty: Type) -> Generated<Register<'a>> {
+    // literals are converted at compile time
+    if let Expr::Literal(value, span) = expr {
+      let value = match (*value, ty) {
+        (Literal::Int(value), Type::F32) => Literal::Float(value as f32),
+        (Literal::Float(value), Type::I32) => Literal::Int(value as i32),
+        (value, _) => value,
+      };
+
+      return self.load_constant(value, *span);
+    }
+
+    let value = self.expr(expr)?;
+
+    if value.ty == ty {
+      return Ok(value);
+    }
+
+    let converted = self.allocate(ty, expr.span())?;
+    self.emit(format!("OP_MOV, PRE_MOV_REG, {}, {}", value.name(), converted.name()));
+
+    match value.ty {
+      Type::I32 => self.ints = value.index,
+      Type::F32 => self.floats = value.index,
+    }
+    Ok(converted)
+  }
+
+  fn condition(&mut self, condition: &Condition) -> Generated<()> {
+    let status = |expr: &Expr| match expr {
+      Expr::Member(left, right) => match self.member(left, right) {
+        Ok(Member::Status { task_id }) => Some(task_id),
+        _ => None,
+      },
+      _ => None,
+    };
+
+    let task_status = match (status(&condition.left), status(&condition.right)) {
+      (Some(task_id), _) => Some((task_id, &condition.right)),
+      (_, Some(task_id)) => Some((task_id, &condition.left)),
+      _ => None,
+    };
+
+    if let Some((task_id, other)) = task_status {
+      let operator = match self.target.comparison(Operand::Task, Type::I32, condition.comparison) {
+        Some(operator) => &operator.mnemonic,
+        None => {
+          return self.error(
+            condition.span,
+            format!("a task status can only be compared with {}", self.comparisons(Operand::Task, Type::I32, "or")),
+          )
+        },
+      };
+      let value = self.expr_as(other, Type::I32)?;
+
+      self.emit(format!("OP_CMP, {}, {}, {}", operator, task_id, value.name()));
+      return Ok(());
+    }
+
+    let ty = join(self.infer(&condition.left)?, self.infer(&condition.right)?);
+    let operator = match self.target.comparison(Operand::Register, ty, condition.comparison) {
+      Some(operator) => &operator.mnemonic,
+      None => {
+        return self.error(
+          condition.span,
+          format!(
+            "the VM cannot compare {} values with `{}`, only with {}",
+            ty,
+            condition.comparison,
+            self.comparisons(Operand::Register, ty, "and")
+          ),
+        )
+      },
+    };
+    let left = self.expr_as(&condition.left, ty)?;
+    let right = self.expr_as(&condition.right, ty)?;
+
+    self.emit(format!("OP_CMP, {}, {}, {}", operator, left.name(), right.name()));
+    Ok(())
+  }
+
+  /// The comparisons the operators of the target make, e.g. "`==` or `<>`".
+  fn comparisons(&self, selects: Operand, ty: Type, conjunction: &str) -> String {
+    let comparisons = self
+      .target
+      .operators
+      .iter()
+      .filter(|operator| operator.selects == selects && (selects == Operand::Task || operator.ty == ty))
+      .map(|operator| format!("`{}`", operator.comparison))
+      .collect::<Vec<_>>();
+
+    match comparisons.split_last() {
+      None => "none".to_string(),
+      Some((last, [])) => last.clone(),
+      Some((last, rest)) => format!("{} {} {}", rest.join(", "), conjunction, last),
+    }
+  }
+
+  fn statement(&mut self, statement: &Statement) -> Generated<()> {
+    match statement {
+      Statement::Assign {
+        target: Place::Variable(name),
+        value,
+        ..
+      } => {
+        let variable = self.variable(name)?;
+        if variable.constant {
+          return self.error(name.span, format!("`{}` is a constant", name.text));
+        }
+
+        let register = self.expr_as(value, variable.ty)?;
+        self.emit(format!("OP_MOV, PRE_MOV_RAM, {}, self.{}", register.name(), name.text));
+      },
+      Statement::Assign {
+        target: Place::Property(left, right),
+        value,
+        ..
+      } => match self.member(left, right)? {
+        Member::Property { instrument, parameter } => {
+          let register = self.expr_as(value, parameter.ty)?;
+
+          self.emit(format!("OP_SET, {}, {}, {}", instrument.mnemonic, parameter.mnemonic, register.name()));
+        },
+        Member::Data { task_id: None, variable } => {
+          if variable.constant {
+            return self.error(right.span, format!("`{}` is a constant", right.text));
+          }
+
+          let register = self.expr_as(value, variable.ty)?;
+          self.emit(format!("OP_MOV, PRE_MOV_RAM, {}, self.{}", register.name(), right.text));
+        },
+        _ => {
+          return self.error(
+            left.span.to(right.span),
+            format!("a task can only write its own data, not task `{}`'s", left.text),
+          )
+        },
+      },
+      Statement::Action {
+        instrument,
+        action,
+        argument,
+        span,
+      } => {
+        let target = self.target;
+        let id = match target.instrument_named(&instrument.text) {
+          Some(id) => id,
+          None => return self.error(instrument.span, format!("unknown instrument `{}`", instrument.text)),
+        };
+        let mnemonic = match id.action_named(&action.text) {
+          Some(mnemonic) => mnemonic,
+          None => {
+            let actions = id.action_names();
+            let known = if actions.is_empty() {
+              "it has none".to_string()
+            }
+            else {
+              format!("it has {}", actions.join(", "))
+            };
+
+            return self.error(action.span, format!("instrument `{}` has no action `{}`; {}", instrument.text, action.text, known));
+          },
+        };
+        // the register is passed along, unused by actions without argument
+        let register = match argument {
+          Some(argument) => self.expr(argument)?,
+          None => self.allocate(Type::I32, *span)?,
+        };
+
+        self.emit(format!("OP_ACT, {}, {}, {}", id.mnemonic, mnemonic, register.name()));
+      },
+      Statement::Return { values, .. } => {
+        for value in values {
+          let register = self.expr(value)?;
+          let prefix = match register.ty {
+            Type::I32 => "PRE_STR_ALU",
+            Type::F32 => "PRE_STR_FPU",
+          };
+          // every value is output at its own span
+          let statement = std::mem::replace(&mut self.span, value.span());
+
+          self.emit(format!("OP_STR, {}, {}", prefix, register.name()));
+          self.span = statement;
+          self.reset();
+        }
+      },
+    }
+
+    Ok(())
+  }
+}
+
+fn join(left: Type, right: Type) -> Type {
+  if left == Type::F32 || right == Type::F32 {
+    Type::F32
+  }
+  else {
+    Type::I32
+  }
+}
+
+fn zero(ty: Type) -> Literal {
+  match ty {
+    Type::I32 => Literal::Int(0),
+    Type::F32 => Literal::Float(0.0),
+  }
+}
+
+fn one(ty: Type) -> Literal {
+  match ty {
+    Type::I32 => Literal::Int(1),
+    Type::F32 => Literal::Float(1.0),
+  }
+}
+
+/// A data word as written in `.splc`.
+fn data_value(value: Literal) -> String {
+  match value {
+    Literal::Int(value) => format!("{}i", value),
+    Literal::Float(value) => format!("{:?}f", value),
+  }
+}
@@ -0,0 +1,215 @@
+use crate::compiler::ast::{Position, Span};
+use crate::compiler::parser;
+use crate::compiler::CompileError;
+use crate::lexer::{Lexeme, LexemeKind, Lexer};
+
+const INDENT: &str = "  ";
+
+/// Formats a task list file canonically:
+///
+/// * every `{` ends a line and every `}` is on a line of its own, with the
+///   lines between indented by two spaces per level
+/// * every statement, declaration, condition and `freq` ends its line with
+///   its `;`
+/// * binary operators, `=` and comparisons are surrounded by a space; `,` and
+///   `:` are followed by one; nothing surrounds `.`, the inside of
+///   parentheses or a unary minus, and `10s` stays together
+/// * comments are kept, at the end of their line or on their own line, and
+///   blank lines are kept, at most one in a row
+///
+/// Files that do not parse are left alone.
+pub fn format(file: &str, source: &str) -> Result<String, CompileError> {
+  parser::parse(file, source)?;
+
+  let mut formatter = Formatter {
+    output: String::new(),
+    depth: 0,
+    parens: 0,
+    line_start: true,
+    previous: None,
+  };
+  let mut newlines = 0;
+
+  for lexeme in Lexer::new(source) {
+    match lexeme.kind {
+      LexemeKind::Eof => break,
+      LexemeKind::Whitespace => newlines += lexeme.code_span.matches('\n').count(),
+      LexemeKind::Comment => {
+        formatter.comment(lexeme.code_span.trim_end(), newlines);
+        newlines = 0;
+      },
+      _ => {
+        formatter.token(lexeme, newlines);
+        newlines = 0;
+      },
+    }
+  }
+
+  let mut output = formatter.output;
+  if !output.is_empty() {
+    output.push('\n');
+  }
+
+  // the formatter may only move whitespace around
+  if significant(source) != significant(&output) {
+    return Err(CompileError {
+      file: file.to_string(),
+      span: Span {
+        start: Position::default(),
+        end: Position::default(),
+      },
+      message: "formatting would change the meaning of the file".to_string(),
+    });
+  }
+
+  Ok(output)
+}
+
+fn significant(source: &str) -> Vec<(LexemeKind, String)> {
+  Lexer::new(source)
+    .filter(|lexeme| lexeme.kind != LexemeKind::Whitespace)
+    .map(|lexeme| (lexeme.kind, lexeme.code_span.trim_end().to_string()))
+    .collect()
+}
+
+struct Formatter<'a> {
+  output: String,
+  depth: usize,
+  /// Open parentheses; a `;` inside them does not end the line.
+  parens: usize,
+  line_start: bool,
+  previous: Option<Lexeme<'a>>,
+}
+
+impl<'a> Formatter<'a> {
+  /// Ends the current line; it is written when the next one starts, so that
+  /// a comment after it can still join it.
+  fn newline(&mut self) {
+    self.line_start = true;
+  }
+
+  /// Starts a line, after a blank one if the source had one there, except at
+  /// the start of the file or of a block.
+  fn start_line(&mut self, newlines: usize) {
+    let after_brace = self.previous.is_some_and(|previous| previous.kind == LexemeKind::LeftBrace);
+
+    if !self.output.is_empty() {
+      self.output.push('\n');
+      if newlines > 1 && !after_brace {
+        self.output.push('\n');
+      }
+    }
+
+    for _ in 0..self.depth {
+      self.output.push_str(INDENT);
+    }
+    self.line_start = false;
+  }
+
+  fn comment(&mut self, text: &str, newlines: usize) {
+    if newlines > 0 || self.output.is_empty() {
+      self.start_line(newlines);
+    }
+    else {
+      self.output.push(' ');
+    }
+
+    self.output.push_str(text);
+    self.newline();
+  }
+
+  fn token(&mut self, lexeme: Lexeme<'a>, newlines: usize) {
+    use LexemeKind::*;
+
+    match lexeme.kind {
+      LeftBrace => {
+        if self.line_start {
+          self.start_line(newlines);
+        }
+        else {
+          self.output.push(' ');
+        }
+        self.output.push('{');
+        self.depth += 1;
+        self.previous = Some(lexeme);
+        self.newline();
+        return;
+      },
+      RightBrace => {
+        self.depth = self.depth.saturating_sub(1);
+        self.newline();
+        self.start_line(0);
+        self.output.push('}');
+        self.previous = Some(lexeme);
+        self.newline();
+        return;
+      },
+      _ => {},
+    }
+
+    if self.line_start {
+      self.start_line(newlines);
+    }
+    else if self.space_before(lexeme) {
+      self.output.push(' ');
+    }
+
+    self.output.push_str(lexeme.code_span);
+
+    match lexeme.kind {
+      LeftParen => self.parens += 1,
+      RightParen => self.parens = self.parens.saturating_sub(1),
+      _ => {},
+    }
+
+    let unary = self.is_unary(lexeme);
+    self.previous = Some(lexeme);
+
+    if lexeme.kind == Semicolon && self.parens == 0 {
+      self.newline();
+    }
+    if unary {
+      // marks the minus so that its operand follows without a space
+      self.previous = Some(Lexeme {
+        kind: Unknown,
+        ..lexeme
+      });
+    }
+  }
+
+  /// Whether a minus negates its operand rather than subtracts it.
+  fn is_unary(&self, lexeme: Lexeme) -> bool {
+    use LexemeKind::*;
+
+    lexeme.kind == Minus
+      && match self.previous {
+        None => true,
+        Some(previous) => match previous.kind {
+          Identifier => previous.code_span == "return",
+          Integer | Float | RightParen => false,
+          _ => true,
+        },
+      }
+  }
+
+  fn space_before(&self, lexeme: Lexeme) -> bool {
+    use LexemeKind::*;
+
+    let previous = match self.previous {
+      Some(previous) => previous,
+      None => return false,
+    };
+
+    match (previous.kind, lexeme.kind) {
+      (_, Comma) | (_, Semicolon) | (_, Colon) | (_, RightParen) | (_, Dot) => false,
+      (LeftParen, _) | (Dot, _) => false,
+      // a unary minus, see `token`
+      (Unknown, _) if previous.code_span == "-" => false,
+      // `sin(x)`, `i32(x)`, `img.do_jpg()`
+      (Identifier, LeftParen) => previous.code_span == "return",
+      // `10s`, `1m`
+      (Integer, Identifier) => false,
+      _ => true,
+    }
+  }
+}
@@ -0,0 +1,44 @@
+pub mod ast;
+pub mod builtins;
+pub mod cache;
+pub mod codegen;
+pub mod format;
+pub mod parser;
+pub mod repl;
+pub mod spli;
+
+use std::fmt;
+
+pub use codegen::Unit;
+
+use crate::compiler::ast::Span;
+use crate::target::Target;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CompileError {
+  pub file: String,
+  pub span: Span,
+  pub message: String,
+}
+
+impl fmt::Display for CompileError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}:{}: {}", self.file, self.span.start, self.message)
+  }
+}
+
+impl std::error::Error for CompileError {}
+
+/// Compiles a task list file (`.spl`) into the assembly of every task, one
+/// `.splc` unit each. Parse errors stop at the first one; semantic errors are
+/// collected over the whole program.
+pub fn compile(file: &str, source: &str) -> Result<Vec<Unit>, Vec<CompileError>> {
+  compile_for(Target::opssat(), file, source)
+}
+
+/// Like `compile`, for `target`.
+pub fn compile_for(target: &Target, file: &str, source: &str) -> Result<Vec<Unit>, Vec<CompileError>> {
+  let program = parser::parse(file, source).map_err(|error| vec![error])?;
+
+  codegen::generate_with(target, file, source, &program, |_, _, _| None)
+}
@@ -0,0 +1,702 @@
+use crate::compiler::ast::*;
+use crate::compiler::CompileError;
+use crate::lexer::{Lexeme, LexemeKind, Lexer};
+use crate::vm::scheduler::Frequency;
+
+/// Words that cannot name a group, task or variable.
+pub const KEYWORDS: &[&str] = &[
+  "group", "task", "data", "freq", "preq", "exec", "var", "const", "f32", "i32", "return",
+];
+
+/// A lexeme with its span; whitespace and comments are dropped.
+#[derive(Clone, Copy, Debug)]
+struct Token<'a> {
+  kind: LexemeKind,
+  text: &'a str,
+  span: Span,
+}
+
+fn tokens(source: &str) -> Vec<Token<'_>> {
+  Lexer::new(source)
+    .filter(|lexeme| lexeme.kind != LexemeKind::Whitespace && lexeme.kind != LexemeKind::Comment)
+    .map(|lexeme: Lexeme| {
+      let (line, column) = lexeme.location;
+
+      Token {
+        kind: lexeme.kind,
+        text: lexeme.code_span,
+        span: Span {
+          start: Position { line, column },
+          end: Position {
+            line,
+            column: column + lexeme.code_span.len(),
+          },
+        },
+      }
+    })
+    .collect()
+}
+
+struct Parser<'a> {
+  file: &'a str,
+  tokens: Vec<Token<'a>>,
+  index: usize,
+}
+
+type Parsed<T> = Result<T, CompileError>;
+
+/// Parses a task list file, stopping at the first syntax error.
+pub fn parse(file: &str, source: &str) -> Result<Program, CompileError> {
+  let mut parser = Parser {
+    file,
+    tokens: tokens(source),
+    index: 0,
+  };
+  let mut program = Program::default();
+
+  while !parser.at(LexemeKind::Eof) {
+    program.groups.push(parser.group()?);
+  }
+
+  Ok(program)
+}
+
+/// Parses a single expression, e.g. a line of the REPL.
+pub fn parse_expression(file: &str, source: &str) -> Result<Expr, CompileError> {
+  let mut parser = Parser {
+    file,
+    tokens: tokens(source),
+    index: 0,
+  };
+  let expr = parser.expression()?;
+
+  parser.expect(LexemeKind::Eof, "the end of the expression")?;
+  Ok(expr)
+}
+
+/// A line of the REPL.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Input {
+  /// `var NAME = EXPR` or `const NAME: TYPE = EXPR`; the type may be left
+  /// out, and the value is any expression.
+  Declaration {
+    name: Name,
+    ty: Option<Type>,
+    constant: bool,
+    value: Expr,
+    span: Span,
+  },
+  Statement(Statement),
+  Condition(Condition),
+  Expression(Expr),
+}
+
+/// Parses a line of the REPL: a declaration, a statement, a comparison or an
+/// expression. The final `;` may be left out.
+pub fn parse_input(file: &str, source: &str) -> Result<Input, CompileError> {
+  let mut tokens = tokens(source);
+  let eof = tokens.len() - 1;
+
+  if eof > 0 && tokens[eof - 1].kind != LexemeKind::Semicolon {
+    let span = tokens[eof].span;
+
+    // The `;` has no text, so that errors on it read as the end of the line.
+    tokens.insert(eof, Token {
+      kind: LexemeKind::Semicolon,
+      text: "",
+      span,
+    });
+  }
+
+  let mut parser = Parser { file, tokens, index: 0 };
+  let kind = |offset: usize| parser.peek_at(offset).kind;
+  let is_statement = parser.at_keyword("return")
+    || (kind(0) == LexemeKind::Identifier && kind(1) == LexemeKind::Assign)
+    || (kind(0) == LexemeKind::Identifier
+      && kind(1) == LexemeKind::Dot
+      && (kind(3) == LexemeKind::Assign || kind(3) == LexemeKind::LeftParen));
+
+  let input = if parser.at_keyword("var") || parser.at_keyword("const") {
+    let start = parser.advance();
+    let name = parser.name("a variable name")?;
+    let ty = if parser.at(LexemeKind::Colon) {
+      parser.advance();
+      let ty = match parser.peek().text {
+        "i32" => Type::I32,
+        "f32" => Type::F32,
+        _ => return parser.unexpected("`i32` or `f32`"),
+      };
+      parser.advance();
+      Some(ty)
+    }
+    else {
+      None
+    };
+
+    parser.expect(LexemeKind::Assign, "`=` and a value")?;
+    let value = parser.expression()?;
+    let end = parser.expect(LexemeKind::Semicolon, "`;`")?.span;
+
+    Input::Declaration {
+      name,
+      ty,
+      constant: start.text == "const",
+      value,
+      span: start.span.to(end),
+    }
+  }
+  else if is_statement {
+    Input::Statement(parser.statement()?)
+  }
+  else {
+    let expr = parser.expression()?;
+
+    if parser.at(LexemeKind::Semicolon) {
+      parser.advance();
+      Input::Expression(expr)
+    }
+    else {
+      parser.index = 0;
+      let condition = parser.condition()?;
+      parser.expect(LexemeKind::Semicolon, "the end of the line")?;
+
+      Input::Condition(condition)
+    }
+  };
+
+  parser.expect(LexemeKind::Eof, "the end of the line")?;
+  Ok(input)
+}
+
+impl<'a> Parser<'a> {
+  fn peek(&self) -> Token<'a> {
+    self.tokens[self.index.min(self.tokens.len() - 1)]
+  }
+
+  fn peek_at(&self, offset: usize) -> Token<'a> {
+    self.tokens[(self.index + offset).min(self.tokens.len() - 1)]
+  }
+
+  fn at(&self, kind: LexemeKind) -> bool {
+    self.peek().kind == kind
+  }
+
+  fn at_keyword(&self, keyword: &str) -> bool {
+    let token = self.peek();
+
+    token.kind == LexemeKind::Identifier && token.text == keyword
+  }
+
+  fn advance(&mut self) -> Token<'a> {
+    let token = self.peek();
+
+    if self.index < self.tokens.len() - 1 {
+      self.index += 1;
+    }
+
+    token
+  }
+
+  fn error<T>(&self, span: Span, message: String) -> Parsed<T> {
+    Err(CompileError {
+      file: self.file.to_string(),
+      span,
+      message,
+    })
+  }
+
+  fn unexpected<T>(&self, expected: &str) -> Parsed<T> {
+    let token = self.peek();
+    let found = match token.kind {
+      LexemeKind::Eof => "the end of the file".to_string(),
+      LexemeKind::Semicolon if token.text.is_empty() => {
+        return self.error(token.span, format!("unexpected end of line, expected {}", expected));
+      },
+      _ => format!("`{}`", token.text),
+    };
+
+    self.error(token.span, format!("expected {}, found {}", expected, found))
+  }
+
+  fn expect(&mut self, kind: LexemeKind, expected: &str) -> Parsed<Token<'a>> {
+    if self.at(kind) {
+      Ok(self.advance())
+    }
+    else {
+      self.unexpected(expected)
+    }
+  }
+
+  fn keyword(&mut self, keyword: &str) -> Parsed<Token<'a>> {
+    if self.at_keyword(keyword) {
+      Ok(self.advance())
+    }
+    else {
+      self.unexpected(&format!("`{}`", keyword))
+    }
+  }
+
+  fn name(&mut self, what: &str) -> Parsed<Name> {
+    let token = self.peek();
+
+    if token.kind != LexemeKind::Identifier || KEYWORDS.contains(&token.text) {
+      return self.unexpected(what);
+    }
+
+    self.advance();
+    Ok(Name {
+      text: token.text.to_string(),
+      span: token.span,
+    })
+  }
+
+  fn group(&mut self) -> Parsed<Group> {
+    let start = self.keyword("group")?.span;
+    let name = self.name("a group name")?;
+    let mut tasks = Vec::new();
+
+    self.expect(LexemeKind::LeftBrace, "`{`")?;
+    while !self.at(LexemeKind::RightBrace) {
+      tasks.push(self.task()?);
+    }
+    let end = self.advance().span;
+
+    Ok(Group {
+      name,
+      tasks,
+      span: start.to(end),
+    })
+  }
+
+  fn task(&mut self) -> Parsed<Task> {
+    let start = self.keyword("task")?.span;
+    let name = self.name("a task name")?;
+    let mut data = None;
+    let mut freq = None;
+    let mut preq = None;
+    let mut exec = None;
+
+    self.expect(LexemeKind::LeftBrace, "`{`")?;
+    while !self.at(LexemeKind::RightBrace) {
+      let token = self.peek();
+      let duplicate = match token.text {
+        "data" if token.kind == LexemeKind::Identifier => data.replace(self.data()?).is_some(),
+        "freq" if token.kind == LexemeKind::Identifier => freq.replace(self.freq()?).is_some(),
+        "preq" if token.kind == LexemeKind::Identifier => preq.replace(self.preq()?).is_some(),
+        "exec" if token.kind == LexemeKind::Identifier => exec.replace(self.exec()?).is_some(),
+        _ => return self.unexpected("a `data`, `freq`, `preq` or `exec` section"),
+      };
+
+      if duplicate {
+        return self.error(token.span, format!("the task already has a `{}` section", token.text));
+      }
+    }
+    let end = self.advance().span;
+
+    Ok(Task {
+      name,
+      data: data.unwrap_or_default(),
+      freq: freq.unwrap_or(Frequency::Once),
+      preq: preq.unwrap_or_default(),
+      exec: match exec {
+        Some(exec) => exec,
+        None => return self.error(start.to(end), "the task has no `exec` section".to_string()),
+      },
+      span: start.to(end),
+    })
+  }
+
+  fn data(&mut self) -> Parsed<Vec<Variable>> {
+    let mut variables = Vec::new();
+
+    self.keyword("data")?;
+    self.expect(LexemeKind::LeftBrace, "`{`")?;
+    while !self.at(LexemeKind::RightBrace) {
+      variables.extend(self.declaration()?);
+    }
+    self.advance();
+
+    Ok(variables)
+  }
+
+  /// `var a, b: f32 = 0.0;`; the `var` keyword may be left out.
+  fn declaration(&mut self) -> Parsed<Vec<Variable>> {
+    let start = self.peek().span;
+    let constant = self.at_keyword("const");
+
+    if constant || self.at_keyword("var") {
+      self.advance();
+    }
+
+    let mut names = vec![self.name("a variable name")?];
+    while self.at(LexemeKind::Comma) {
+      self.advance();
+      names.push(self.name("a variable name")?);
+    }
+
+    self.expect(LexemeKind::Colon, "`:` and a type")?;
+    let ty = match self.peek().text {
+      "i32" => Type::I32,
+      "f32" => Type::F32,
+      _ => return self.unexpected("`i32` or `f32`"),
+    };
+    self.advance();
+
+    self.expect(LexemeKind::Assign, "`=` and an initial value")?;
+    let value_span = self.peek().span;
+    let value = match (ty, self.literal()?) {
+      (Type::I32, Literal::Float(_)) => {
+        return self.error(value_span, "an i32 variable needs an integer value".to_string())
+      },
+      (Type::F32, Literal::Int(value)) => Literal::Float(value as f32),
+      (_, value) => value,
+    };
+    let end = self.expect(LexemeKind::Semicolon, "`;`")?.span;
+
+    Ok(
+      names
+        .into_iter()
+        .map(|name| Variable {
+          name,
+          ty,
+          constant,
+          value,
+          span: start.to(end),
+        })
+        .collect(),
+    )
+  }
+
+  /// A number, optionally negative.
+  fn literal(&mut self) -> Parsed<Literal> {
+    let negative = self.at(LexemeKind::Minus);
+    if negative {
+      self.advance();
+    }
+
+    let token = self.peek();
+    let text = if negative {
+      format!("-{}", token.text)
+    }
+    else {
+      token.text.to_string()
+    };
+
+    let literal = match token.kind {
+      LexemeKind::Integer => text.parse().map(Literal::Int).ok(),
+      LexemeKind::Float => text.parse().map(Literal::Float).ok(),
+      _ => return self.unexpected("a number"),
+    };
+
+    match literal {
+      Some(literal) => {
+        self.advance();
+        Ok(literal)
+      },
+      None => self.error(token.span, format!("`{}` does not fit in 32 bits", text)),
+    }
+  }
+
+  /// `freq: once;`, `freq: always;` or `freq: N;` with a unit of `s`, `m` or
+  /// `h`.
+  fn freq(&mut self) -> Parsed<Frequency> {
+    self.keyword("freq")?;
+    self.expect(LexemeKind::Colon, "`:`")?;
+
+    let token = self.peek();
+    let freq = match (token.kind, token.text) {
+      (LexemeKind::Identifier, "once") => {
+        self.advance();
+        Frequency::Once
+      },
+      (LexemeKind::Identifier, "always") => {
+        self.advance();
+        Frequency::Always
+      },
+      (LexemeKind::Integer, _) => {
+        self.advance();
+        let count: u32 = token.text.parse().unwrap_or(u32::MAX);
+        let unit = self.peek();
+        let scale = match unit.text {
+          "s" => 1,
+          "m" => 60,
+          "h" => 3600,
+          _ => return self.unexpected("a unit, `s`, `m` or `h`"),
+        };
+        self.advance();
+
+        let freq = Frequency::Every(count.saturating_mul(scale));
+        if freq.to_byte().is_none() {
+          return self.error(
+            token.span.to(unit.span),
+            "the VM runs tasks every 1 to 59 seconds, 1 to 59 minutes or 1 to 8 hours".to_string(),
+          );
+        }
+
+        freq
+      },
+      _ => return self.unexpected("`once`, `always` or an interval such as `10s`"),
+    };
+    self.expect(LexemeKind::Semicolon, "`;`")?;
+
+    Ok(freq)
+  }
+
+  fn preq(&mut self) -> Parsed<Vec<Condition>> {
+    let mut conditions = Vec::new();
+
+    self.keyword("preq")?;
+    self.expect(LexemeKind::LeftBrace, "`{`")?;
+    while !self.at(LexemeKind::RightBrace) {
+      conditions.push(self.condition()?);
+      self.expect(LexemeKind::Semicolon, "`;`")?;
+    }
+    self.advance();
+
+    Ok(conditions)
+  }
+
+  pub fn condition(&mut self) -> Parsed<Condition> {
+    let left = self.expression()?;
+    let comparison = match self.peek().kind {
+      LexemeKind::Equal => Comparison::Equal,
+      LexemeKind::NotEqual => Comparison::NotEqual,
+      LexemeKind::Greater => Comparison::Greater,
+      LexemeKind::Less => Comparison::Less,
+      LexemeKind::GreaterEqual => Comparison::GreaterEqual,
+      LexemeKind::LessEqual => Comparison::LessEqual,
+      _ => return self.unexpected("a comparison, `==`, `<>`, `>`, `<`, `>=` or `<=`"),
+    };
+    self.advance();
+    let right = self.expression()?;
+
+    Ok(Condition {
+      span: left.span().to(right.span()),
+      left,
+      comparison,
+      right,
+    })
+  }
+
+  fn exec(&mut self) -> Parsed<Vec<Statement>> {
+    let mut statements = Vec::new();
+
+    self.keyword("exec")?;
+    self.expect(LexemeKind::LeftBrace, "`{`")?;
+    while !self.at(LexemeKind::RightBrace) {
+      statements.push(self.statement()?);
+    }
+    self.advance();
+
+    Ok(statements)
+  }
+
+  fn statement(&mut self) -> Parsed<Statement> {
+    let start = self.peek().span;
+
+    if self.at_keyword("return") {
+      self.advance();
+      let mut values = vec![self.expression()?];
+      while self.at(LexemeKind::Comma) {
+        self.advance();
+        values.push(self.expression()?);
+      }
+      let end = self.expect(LexemeKind::Semicolon, "`;`")?.span;
+
+      return Ok(Statement::Return {
+        values,
+        span: start.to(end),
+      });
+    }
+
+    let first = self.name("a statement")?;
+    let statement = if self.at(LexemeKind::Dot) {
+      self.advance();
+      let member = self.name("a property or action name")?;
+
+      if self.at(LexemeKind::LeftParen) {
+        self.advance();
+        let argument = if self.at(LexemeKind::RightParen) {
+          None
+        }
+        else {
+          Some(self.expression()?)
+        };
+        self.expect(LexemeKind::RightParen, "`)`")?;
+        let end = self.expect(LexemeKind::Semicolon, "`;`")?.span;
+
+        Statement::Action {
+          instrument: first,
+          action: member,
+          argument,
+          span: start.to(end),
+        }
+      }
+      else {
+        self.expect(LexemeKind::Assign, "`=` or `(`")?;
+        let value = self.expression()?;
+        let end = self.expect(LexemeKind::Semicolon, "`;`")?.span;
+
+        Statement::Assign {
+          target: Place::Property(first, member),
+          value,
+          span: start.to(end),
+        }
+      }
+    }
+    else {
+      self.expect(LexemeKind::Assign, "`=`")?;
+      let value = self.expression()?;
+      let end = self.expect(LexemeKind::Semicolon, "`;`")?.span;
+
+      Statement::Assign {
+        target: Place::Variable(first),
+        value,
+        span: start.to(end),
+      }
+    };
+
+    Ok(statement)
+  }
+
+  pub fn expression(&mut self) -> Parsed<Expr> {
+    let mut expr = self.factor()?;
+
+    loop {
+      let op = match self.peek().kind {
+        LexemeKind::Plus => BinaryOp::Add,
+        LexemeKind::Minus => BinaryOp::Sub,
+        _ => return Ok(expr),
+      };
+      self.advance();
+      expr = Expr::Binary(op, Box::new(expr), Box::new(self.factor()?));
+    }
+  }
+
+  fn factor(&mut self) -> Parsed<Expr> {
+    let mut expr = self.unary()?;
+
+    loop {
+      let op = match self.peek().kind {
+        LexemeKind::Asterisk => BinaryOp::Mul,
+        LexemeKind::Slash => BinaryOp::Div,
+        _ => return Ok(expr),
+      };
+      self.advance();
+      expr = Expr::Binary(op, Box::new(expr), Box::new(self.unary()?));
+    }
+  }
+
+  fn unary(&mut self) -> Parsed<Expr> {
+    if !self.at(LexemeKind::Minus) {
+      return self.term();
+    }
+
+    let start = self.advance().span;
+
+    // `2147483648` only fits once negated, so only right after a unary minus
+    let token = self.peek();
+    if token.kind == LexemeKind::Integer && token.text == "2147483648" {
+      self.advance();
+      return Ok(Expr::Literal(Literal::Int(i32::MIN), start.to(token.span)));
+    }
+
+    let operand = self.unary()?;
+    let span = start.to(operand.span());
+
+    // `-1` is a literal, so that `-2147483648` fits
+    Ok(match operand {
+      Expr::Literal(Literal::Int(value), _) => Expr::Literal(Literal::Int(value.wrapping_neg()), span),
+      Expr::Literal(Literal::Float(value), _) => Expr::Literal(Literal::Float(-value), span),
+      operand => Expr::Negate(Box::new(operand), span),
+    })
+  }
+
+  fn term(&mut self) -> Parsed<Expr> {
+    let token = self.peek();
+
+    match token.kind {
+      LexemeKind::Integer | LexemeKind::Float => {
+        let literal = match (token.kind, token.text.parse::<i32>()) {
+          (LexemeKind::Integer, Ok(value)) => Literal::Int(value),
+          (LexemeKind::Integer, Err(_)) => {
+            return self.error(token.span, format!("`{}` does not fit in 32 bits", token.text))
+          },
+          _ => Literal::Float(token.text.parse().unwrap_or(f32::INFINITY)),
+        };
+        self.advance();
+
+        Ok(Expr::Literal(literal, token.span))
+      },
+      LexemeKind::LeftParen => {
+        self.advance();
+        let expr = self.expression()?;
+        self.expect(LexemeKind::RightParen, "`)`")?;
+
+        Ok(expr)
+      },
+      // the conversions `i32(x)` and `f32(x)` are named by keywords
+      LexemeKind::Identifier if (token.text == "i32" || token.text == "f32") && self.peek_at(1).kind == LexemeKind::LeftParen => {
+        self.advance();
+        self.call(Name {
+          text: token.text.to_string(),
+          span: token.span,
+        })
+      },
+      LexemeKind::Identifier => {
+        let name = self.name("an expression")?;
+
+        if self.at(LexemeKind::Dot) {
+          self.advance();
+          let member = self.name("a property or variable name")?;
+
+          Ok(Expr::Member(name, member))
+        }
+        else if self.at(LexemeKind::LeftParen) {
+          self.call(name)
+        }
+        else {
+          Ok(Expr::Name(name))
+        }
+      },
+      _ => self.unexpected("an expression"),
+    }
+  }
+
+  fn call(&mut self, name: Name) -> Parsed<Expr> {
+    let mut arguments = Vec::new();
+
+    self.expect(LexemeKind::LeftParen, "`(`")?;
+    if !self.at(LexemeKind::RightParen) {
+      arguments.push(self.expression()?);
+      while self.at(LexemeKind::Comma) {
+        self.advance();
+        arguments.push(self.expression()?);
+      }
+    }
+    let end = self.expect(LexemeKind::RightParen, "`)`")?.span;
+
+    Ok(Expr::Call(name.clone(), arguments, name.span.to(end)))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn negates_the_smallest_integer() {
+    let expr = parse_expression("test.spl", "-2147483648").unwrap();
+
+    assert!(matches!(expr, Expr::Literal(Literal::Int(i32::MIN), _)), "{:?}", expr);
+  }
+
+  #[test]
+  fn rejects_the_smallest_integer_without_its_sign() {
+    for source in ["2147483648", "a - 2147483648", "1 -2147483648", "-(2147483648)"] {
+      let err = parse_expression("test.spl", source).unwrap_err();
+
+      assert_eq!(err.message, "`2147483648` does not fit in 32 bits", "{}", source);
+    }
+  }
+}
@@ -0,0 +1,217 @@
+//! Evaluation of single lines of the task language, for the REPL.
+//!
+//! Every line is compiled into a task holding the variables declared so far,
+//! which is assembled and run on a machine with the simulated instruments, so
+//! that values are computed exactly as a task would compute them on the VM.
+
+use std::path::Path;
+
+use crate::asm;
+use crate::compiler::ast::*;
+use crate::compiler::codegen;
+use crate::compiler::parser::{self, Input};
+use crate::vm::machine::{Machine, OutputValue, Status, TaskState};
+use crate::vm::scheduler::Frequency;
+
+const FILE: &str = "<repl>";
+
+/// What a line evaluated to.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Outcome {
+  /// The values of an expression or a `return`.
+  Values(Vec<OutputValue>),
+  /// A variable declared or assigned, with its new value.
+  Variable(Variable),
+  /// Whether a comparison holds.
+  Condition(bool),
+  /// An action or a property write.
+  Done,
+}
+
+pub struct Session {
+  /// The variables declared so far, with their current values.
+  pub variables: Vec<Variable>,
+  /// The assembly of the last line compiled.
+  pub assembly: Option<String>,
+  /// Keeps the instruments, and what was written to them, between lines.
+  machine: Machine,
+}
+
+impl Default for Session {
+  fn default() -> Session {
+    Session::new()
+  }
+}
+
+impl Session {
+  pub fn new() -> Session {
+    Session {
+      variables: Vec::new(),
+      assembly: None,
+      machine: Machine::new(Vec::new()).expect("an empty machine always loads"),
+    }
+  }
+
+  /// Compiles and runs a line.
+  pub fn eval(&mut self, line: &str) -> Result<Outcome, String> {
+    let input = parser::parse_input(FILE, line).map_err(|error| error.to_string())?;
+    let mut data = self.variables.clone();
+    let mut preq = Vec::new();
+    let mut exec = Vec::new();
+    // the variable whose value is read back after the run
+    let mut updated = None;
+
+    match input {
+      Input::Declaration {
+        name,
+        ty,
+        constant,
+        value,
+        span,
+      } => {
+        data.retain(|variable| variable.name.text != name.text);
+        let ty = match ty {
+          Some(ty) => ty,
+          None => self.infer(&data, &value)?,
+        };
+
+        data.push(Variable {
+          name: name.clone(),
+          ty,
+          constant: false,
+          value: match ty {
+            Type::I32 => Literal::Int(0),
+            Type::F32 => Literal::Float(0.0),
+          },
+          span,
+        });
+        exec.push(Statement::Assign {
+          target: Place::Variable(name.clone()),
+          value,
+          span,
+        });
+        updated = Some((name.text, constant));
+      },
+      Input::Statement(statement) => {
+        if let Statement::Assign {
+          target: Place::Variable(name),
+          ..
+        } = &statement
+        {
+          let constant = self.variables.iter().any(|variable| variable.name.text == name.text && variable.constant);
+
+          updated = Some((name.text.clone(), constant));
+        }
+        exec.push(statement);
+      },
+      Input::Condition(condition) => preq.push(condition),
+      Input::Expression(value) => {
+        let span = value.span();
+
+        exec.push(Statement::Return {
+          values: vec![value],
+          span,
+        });
+      },
+    }
+
+    let is_condition = !preq.is_empty();
+    let (status, ram) = self.run(line, task(data.clone(), preq, exec))?;
+
+    if is_condition {
+      return Ok(Outcome::Condition(status == Status::Success));
+    }
+
+    if let Some((name, constant)) = updated {
+      let index = data
+        .iter()
+        .position(|variable| variable.name.text == name)
+        .expect("assigned variables are declared");
+      let mut variable = data.remove(index);
+
+      variable.value = match variable.ty {
+        Type::I32 => Literal::Int(ram[index] as i32),
+        Type::F32 => Literal::Float(f32::from_bits(ram[index])),
+      };
+      variable.constant = constant;
+
+      match self.variables.iter_mut().find(|existing| existing.name.text == name) {
+        Some(existing) => *existing = variable.clone(),
+        None => self.variables.push(variable.clone()),
+      }
+      return Ok(Outcome::Variable(variable));
+    }
+
+    let values: Vec<OutputValue> = self.machine.output.iter().map(|output| output.value).collect();
+    if values.is_empty() {
+      Ok(Outcome::Done)
+    }
+    else {
+      Ok(Outcome::Values(values))
+    }
+  }
+
+  /// The type of an expression over the variables in `data`.
+  fn infer(&self, data: &[Variable], value: &Expr) -> Result<Type, String> {
+    let group = group(task(data.to_vec(), Vec::new(), Vec::new()));
+
+    codegen::infer(FILE, &group, &group.tasks[0], value).map_err(|error| error.to_string())
+  }
+
+  /// Compiles, assembles and runs a task, returning its status and its data.
+  fn run(&mut self, line: &str, task: Task) -> Result<(Status, Vec<u32>), String> {
+    let program = Program {
+      groups: vec![group(task)],
+    };
+    let unit = codegen::generate(FILE, line, &program)
+      .map_err(|errors| {
+        errors
+          .iter()
+          .map(|error| error.to_string())
+          .collect::<Vec<_>>()
+          .join("\n")
+      })?
+      .remove(0);
+    let assembled = asm::assemble_source(Path::new(FILE), &unit.assembly).map_err(|err| err.to_string())?;
+
+    self.assembly = Some(unit.assembly);
+    self.machine.output.clear();
+    self.machine.tasks = vec![TaskState {
+      header: assembled.header,
+      code: assembled.code,
+      ram: assembled.data,
+      status: Status::Ready,
+      sources: assembled.sources,
+      debug: None,
+    }];
+
+    let status = self.machine.run_task(0).map_err(|err| err.to_string())?;
+    Ok((status, self.machine.tasks[0].ram.clone()))
+  }
+}
+
+fn name(text: &str) -> Name {
+  Name {
+    text: text.to_string(),
+    span: Span::default(),
+  }
+}
+
+fn task(data: Vec<Variable>, preq: Vec<Condition>, exec: Vec<Statement>) -> Task {
+  Task {
+    name: name("repl"),
+    data,
+    freq: Frequency::Once,
+    preq,
+    exec,
+    span: Span::default(),
+  }
+}
+
+fn group(task: Task) -> Group {
+  Group {
+    name: name("repl"),
+    tasks: vec![task],
+    span: Span::default(),
+  }
+}
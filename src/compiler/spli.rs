@@ -0,0 +1,224 @@
+//! Instrument definition files (`.spli`), as provided by the operations team
+//! of a satellite:
+//!
+//! ```text
+//! // GPS receiver
+//! inst gps {
+//!   // Latitude, in degrees.
+//!   prop latt: f32;
+//!   queue: 4;
+//! }
+//! ```
+//!
+//! The `//` comments right before an instrument or a property document it.
+//! Entries other than `prop` and `var` (`queue`, `size`, `type`, `accs`,
+//! `shed`) are read as `KEY: VALUE;` and kept uninterpreted.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::compiler::ast::{Name, Position, Span, Type};
+use crate::compiler::CompileError;
+use crate::lexer::{LexemeKind, Lexer};
+
+const INST: &str = "inst";
+const PROP: &str = "prop";
+const VAR: &str = "var";
+
+/// The keywords of `.spli` files: `inst`, `prop`, `var` and the keys of the
+/// other entries.
+pub const INSTRUMENT_KEYWORDS: &[&str] = &[INST, PROP, VAR, "queue", "size", "type", "accs", "shed"];
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Definitions {
+  pub instruments: Vec<InstrumentDefinition>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct InstrumentDefinition {
+  /// The file it is defined in.
+  pub file: String,
+  pub name: Name,
+  pub doc: String,
+  pub properties: Vec<PropertyDefinition>,
+  /// The other entries, by key.
+  pub entries: Vec<(String, String)>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PropertyDefinition {
+  pub name: Name,
+  pub ty: Type,
+  pub doc: String,
+}
+
+impl Definitions {
+  pub fn parse(file: &str, source: &str) -> Result<Definitions, CompileError> {
+    let mut tokens = Vec::new();
+    let mut doc: Vec<&str> = Vec::new();
+    // the line of the last comment
+    let mut doc_line = 0;
+
+    // comments are attached to the token on the line that follows them
+    for lexeme in Lexer::new(source) {
+      let (line, column) = lexeme.location;
+
+      if !doc.is_empty() && line > doc_line + 1 {
+        doc.clear();
+      }
+
+      match lexeme.kind {
+        LexemeKind::Whitespace => {},
+        LexemeKind::Comment => {
+          doc.push(lexeme.code_span[2..].trim());
+          doc_line = line;
+        },
+        _ => {
+          let span = Span {
+            start: Position { line, column },
+            end: Position {
+              line,
+              column: column + lexeme.code_span.len(),
+            },
+          };
+
+          tokens.push((lexeme.kind, lexeme.code_span, span, doc.join(" ")));
+          doc.clear();
+        },
+      }
+    }
+
+    // the last token is `Eof`
+    let at = |index: usize| &tokens[index.min(tokens.len() - 1)];
+    let mut index = 0;
+    let mut definitions = Definitions::default();
+    let error = |index: usize, expected: &str| {
+      let (kind, text, span, _) = at(index);
+      let found = match kind {
+        LexemeKind::Eof => "the end of the file".to_string(),
+        _ => format!("`{}`", text),
+      };
+
+      Err(CompileError {
+        file: file.to_string(),
+        span: *span,
+        message: format!("expected {}, found {}", expected, found),
+      })
+    };
+    let name = |index: usize| Name {
+      text: at(index).1.to_string(),
+      span: at(index).2,
+    };
+
+    while at(index).0 != LexemeKind::Eof {
+      if at(index).1 != INST || at(index + 1).0 != LexemeKind::Identifier {
+        return error(index, "`inst` and an instrument name");
+      }
+      if at(index + 2).0 != LexemeKind::LeftBrace {
+        return error(index + 2, "`{`");
+      }
+
+      let mut instrument = InstrumentDefinition {
+        file: file.to_string(),
+        name: name(index + 1),
+        doc: at(index).3.clone(),
+        properties: Vec::new(),
+        entries: Vec::new(),
+      };
+      index += 3;
+
+      while at(index).0 != LexemeKind::RightBrace {
+        let (kind, text, _, doc) = at(index);
+
+        if *kind != LexemeKind::Identifier {
+          return error(index, "`prop`, `var` or an entry such as `queue: 4;`");
+        }
+
+        if *text == PROP || *text == VAR {
+          if at(index + 1).0 != LexemeKind::Identifier {
+            return error(index + 1, "a property name");
+          }
+          if at(index + 2).0 != LexemeKind::Colon {
+            return error(index + 2, "`:` and a type");
+          }
+          let ty = match at(index + 3).1 {
+            "i32" => Type::I32,
+            "f32" => Type::F32,
+            _ => return error(index + 3, "`i32` or `f32`"),
+          };
+          if at(index + 4).0 != LexemeKind::Semicolon {
+            return error(index + 4, "`;`");
+          }
+
+          instrument.properties.push(PropertyDefinition {
+            name: name(index + 1),
+            ty,
+            doc: doc.clone(),
+          });
+          index += 5;
+        }
+        else {
+          if at(index + 1).0 != LexemeKind::Colon {
+            return error(index + 1, "`:` and a value");
+          }
+
+          let start = index + 2;
+          index = start;
+          while at(index).0 != LexemeKind::Semicolon {
+            if at(index).0 == LexemeKind::Eof {
+              return error(index, "`;`");
+            }
+            index += 1;
+          }
+
+          let value: Vec<&str> = tokens[start..index].iter().map(|token| token.1).collect();
+          instrument.entries.push((text.to_string(), value.join(" ")));
+          index += 1;
+        }
+      }
+
+      definitions.instruments.push(instrument);
+      index += 1;
+    }
+
+    Ok(definitions)
+  }
+
+  pub fn instrument(&self, name: &str) -> Option<&InstrumentDefinition> {
+    self.instruments.iter().find(|instrument| instrument.name.text == name)
+  }
+
+  pub fn property(&self, instrument: &str, name: &str) -> Option<&PropertyDefinition> {
+    self
+      .instrument(instrument)?
+      .properties
+      .iter()
+      .find(|property| property.name.text == name)
+  }
+
+  /// Adds the definitions of another file; later files win.
+  pub fn extend(&mut self, other: Definitions) {
+    for instrument in other.instruments {
+      self.instruments.retain(|existing| existing.name.text != instrument.name.text);
+      self.instruments.push(instrument);
+    }
+  }
+}
+
+/// The `.spli` files in the directory of a task list file, sorted by name.
+pub fn files(file: &Path) -> Vec<PathBuf> {
+  let directory = match file.parent() {
+    Some(directory) if !directory.as_os_str().is_empty() => directory,
+    _ => Path::new("."),
+  };
+  let mut paths: Vec<PathBuf> = match fs::read_dir(directory) {
+    Ok(entries) => entries
+      .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+      .filter(|path| path.extension().is_some_and(|extension| extension == "spli"))
+      .collect(),
+    Err(_) => Vec::new(),
+  };
+
+  paths.sort();
+  paths
+}
@@ -0,0 +1,289 @@
+//! Debug information: where the words of a compiled task come from in its
+//! task list file, and what its data words hold. The compiler writes it as a
+//! `.spld` sidecar next to every `.splc` file, keyed by assembly line; the
+//! assembler and the linker write it next to `.splx` files, keyed by word:
+//!
+//! ```text
+//! task 1.1 imaging position src/tools/examples/orbit.spl
+//! line 4 11:7-11:22
+//! word 1 11:7-11:22
+//! data 1 lat f32
+//! data 3 earth f32 const
+//! ```
+//!
+//! Positions are 1-based `LINE:COLUMN`, the end of a span being exclusive;
+//! words are counted from the task header, word 0.
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::asm::preprocess::{Location, SourceLine};
+use crate::asm::Task;
+use crate::compiler::ast::{Position, Span, Type};
+
+pub const EXTENSION: &str = "spld";
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DebugInfo {
+  pub tasks: Vec<TaskDebug>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TaskDebug {
+  pub group_id: u8,
+  pub task_id: u8,
+  pub group: String,
+  pub task: String,
+  /// The task list file.
+  pub file: String,
+  /// The span of every instruction, by 1-based `.splc` line.
+  pub lines: Vec<(usize, Span)>,
+  /// The span of every instruction, by word.
+  pub words: Vec<(usize, Span)>,
+  pub variables: Vec<DebugVariable>,
+}
+
+/// A variable of the data section.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DebugVariable {
+  /// Data addresses start from 1.
+  pub address: u8,
+  pub name: String,
+  pub ty: Type,
+  pub constant: bool,
+}
+
+#[derive(Debug)]
+pub struct DebugError {
+  /// 1-based line of the sidecar.
+  pub line: usize,
+  pub message: String,
+}
+
+impl fmt::Display for DebugError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "line {}: {}", self.line, self.message)
+  }
+}
+
+impl std::error::Error for DebugError {}
+
+/// The sidecar of a `.splc` or `.splx` file.
+pub fn sidecar(path: &Path) -> PathBuf {
+  path.with_extension(EXTENSION)
+}
+
+/// Reads the sidecar of `path`, if there is one.
+pub fn read(path: &Path) -> Result<Option<DebugInfo>, String> {
+  let sidecar = sidecar(path);
+
+  if !sidecar.exists() {
+    return Ok(None);
+  }
+
+  let text = fs::read_to_string(&sidecar).map_err(|err| format!("{}: {}", sidecar.display(), err))?;
+  DebugInfo::parse(&text)
+    .map(Some)
+    .map_err(|err| format!("{}: {}", sidecar.display(), err))
+}
+
+impl DebugInfo {
+  pub fn parse(text: &str) -> Result<DebugInfo, DebugError> {
+    let mut info = DebugInfo::default();
+
+    for (index, line) in text.lines().enumerate() {
+      let error = |message: String| DebugError {
+        line: index + 1,
+        message,
+      };
+      let line = line.trim();
+      if line.is_empty() || line.starts_with('#') {
+        continue;
+      }
+
+      let (keyword, rest) = line.split_once(' ').unwrap_or((line, ""));
+      let fields: Vec<&str> = rest.split_whitespace().collect();
+
+      if keyword == "task" {
+        let ids = fields.first().and_then(|ids| ids.split_once('.'));
+        let (group_id, task_id) = match ids.map(|(group, task)| (group.parse(), task.parse())) {
+          Some((Ok(group_id), Ok(task_id))) if fields.len() >= 4 => (group_id, task_id),
+          _ => return Err(error("expected `task GROUP_ID.TASK_ID GROUP TASK FILE`".to_string())),
+        };
+        // the file name is the rest of the line, spaces included
+        let file = rest.splitn(4, ' ').nth(3).unwrap_or("").to_string();
+
+        info.tasks.push(TaskDebug {
+          group_id,
+          task_id,
+          group: fields[1].to_string(),
+          task: fields[2].to_string(),
+          file,
+          lines: Vec::new(),
+          words: Vec::new(),
+          variables: Vec::new(),
+        });
+        continue;
+      }
+
+      let task = info
+        .tasks
+        .last_mut()
+        .ok_or_else(|| error(format!("`{}` before the first `task`", keyword)))?;
+
+      match keyword {
+        "line" | "word" => {
+          let entry = match fields.as_slice() {
+            [number, span] => number.parse().ok().zip(parse_span(span)),
+            _ => None,
+          };
+          let entry = entry.ok_or_else(|| error(format!("expected `{} NUMBER LINE:COLUMN-LINE:COLUMN`", keyword)))?;
+
+          if keyword == "line" {
+            task.lines.push(entry);
+          }
+          else {
+            task.words.push(entry);
+          }
+        },
+        "data" => {
+          let ty = match fields.get(2) {
+            Some(&"i32") => Some(Type::I32),
+            Some(&"f32") => Some(Type::F32),
+            _ => None,
+          };
+          let variable = match (fields.first().and_then(|address| address.parse().ok()), ty) {
+            (Some(address), Some(ty)) if fields.len() == 3 || (fields.len() == 4 && fields[3] == "const") => {
+              DebugVariable {
+                address,
+                name: fields[1].to_string(),
+                ty,
+                constant: fields.len() == 4,
+              }
+            },
+            _ => return Err(error("expected `data ADDRESS NAME TYPE [const]`".to_string())),
+          };
+
+          task.variables.push(variable);
+        },
+        _ => return Err(error(format!("unknown entry `{}`", keyword))),
+      }
+    }
+
+    Ok(info)
+  }
+
+  pub fn task(&self, group_id: u8, task_id: u8) -> Option<&TaskDebug> {
+    self
+      .tasks
+      .iter()
+      .find(|task| task.group_id == group_id && task.task_id == task_id)
+  }
+
+  /// Gives every task its debug information, e.g. the tasks of a `.splx`
+  /// file the information of its sidecar.
+  pub fn attach(&self, tasks: &mut [Task]) {
+    for task in tasks {
+      task.debug = self.task(task.header.group_id, task.header.task_id).cloned();
+    }
+  }
+}
+
+impl fmt::Display for DebugInfo {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    for task in &self.tasks {
+      writeln!(f, "task {}.{} {} {} {}", task.group_id, task.task_id, task.group, task.task, task.file)?;
+
+      for (line, span) in &task.lines {
+        writeln!(f, "line {} {}", line, format_span(*span))?;
+      }
+      for (word, span) in &task.words {
+        writeln!(f, "word {} {}", word, format_span(*span))?;
+      }
+      for variable in &task.variables {
+        let constant = if variable.constant { " const" } else { "" };
+
+        writeln!(f, "data {} {} {}{}", variable.address, variable.name, variable.ty, constant)?;
+      }
+    }
+
+    Ok(())
+  }
+}
+
+impl TaskDebug {
+  /// The span of the source of a word.
+  pub fn span(&self, word: usize) -> Option<Span> {
+    self
+      .words
+      .iter()
+      .find(|(candidate, _)| *candidate == word)
+      .map(|(_, span)| *span)
+  }
+
+  /// `FILE:LINE:COLUMN` of the source of a word.
+  pub fn location(&self, word: usize) -> Option<String> {
+    self.span(word).map(|span| format!("{}:{}", self.file, span.start))
+  }
+
+  pub fn variable(&self, address: u8) -> Option<&DebugVariable> {
+    self.variables.iter().find(|variable| variable.address == address)
+  }
+
+  /// Keys the spans by word instead of by `.splc` line, given the source
+  /// line of every word of the assembled task. Lines produced by a macro or
+  /// an include take the span of the line that invoked them.
+  pub fn assembled(&self, sources: &[SourceLine]) -> TaskDebug {
+    let root = |location: &Location| {
+      let mut location = location;
+      while let Some(invocation) = &location.expanded_from {
+        location = invocation;
+      }
+      location.line
+    };
+    let words = sources
+      .iter()
+      .enumerate()
+      .filter_map(|(word, source)| {
+        let line = root(&source.location);
+
+        self
+          .lines
+          .iter()
+          .find(|(candidate, _)| *candidate == line)
+          .map(|(_, span)| (word, *span))
+      })
+      .collect();
+
+    TaskDebug {
+      lines: Vec::new(),
+      words,
+      ..self.clone()
+    }
+  }
+}
+
+fn format_span(span: Span) -> String {
+  format!("{}-{}", span.start, span.end)
+}
+
+/// `11:7-11:22`, 1-based.
+fn parse_span(text: &str) -> Option<Span> {
+  let position = |text: &str| {
+    let (line, column) = text.split_once(':')?;
+    let line: usize = line.parse().ok()?;
+    let column: usize = column.parse().ok()?;
+
+    Some(Position {
+      line: line.checked_sub(1)?,
+      column: column.checked_sub(1)?,
+    })
+  };
+  let (start, end) = text.split_once('-')?;
+
+  Some(Span {
+    start: position(start)?,
+    end: position(end)?,
+  })
+}
@@ -0,0 +1,358 @@
+//! A small JSON reader and writer for telemetry files, exports and the
+//! language server protocol.
+
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Json {
+  Null,
+  Bool(bool),
+  Number(f64),
+  String(String),
+  Array(Vec<Json>),
+  /// Members in document order.
+  Object(Vec<(String, Json)>),
+}
+
+#[derive(Debug)]
+pub struct JsonError {
+  /// Byte offset into the text.
+  pub offset: usize,
+  pub message: String,
+}
+
+impl fmt::Display for JsonError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "offset {}: {}", self.offset, self.message)
+  }
+}
+
+impl std::error::Error for JsonError {}
+
+impl Json {
+  pub fn parse(text: &str) -> Result<Json, JsonError> {
+    let mut parser = Parser { text, offset: 0 };
+    let value = parser.value()?;
+
+    parser.whitespace();
+    if parser.offset < text.len() {
+      return Err(parser.error("trailing characters after the JSON value"));
+    }
+
+    Ok(value)
+  }
+
+  /// The member `key` of an object.
+  pub fn get(&self, key: &str) -> Option<&Json> {
+    match self {
+      Json::Object(members) => members.iter().find(|(name, _)| name == key).map(|(_, value)| value),
+      _ => None,
+    }
+  }
+
+  pub fn as_f64(&self) -> Option<f64> {
+    match self {
+      Json::Number(value) => Some(*value),
+      _ => None,
+    }
+  }
+
+  pub fn as_str(&self) -> Option<&str> {
+    match self {
+      Json::String(value) => Some(value),
+      _ => None,
+    }
+  }
+
+  pub fn as_array(&self) -> Option<&[Json]> {
+    match self {
+      Json::Array(values) => Some(values),
+      _ => None,
+    }
+  }
+
+  /// Serializes with every array element and object member on a line of its
+  /// own, indented by two spaces per level, for files meant to be read.
+  pub fn pretty(&self) -> String {
+    let mut text = String::new();
+
+    self.write_pretty(&mut text, 0);
+    text
+  }
+
+  fn write_pretty(&self, text: &mut String, depth: usize) {
+    let indent = |text: &mut String, depth: usize| {
+      text.push('\n');
+      text.push_str(&"  ".repeat(depth));
+    };
+
+    match self {
+      Json::Array(values) if !values.is_empty() => {
+        text.push('[');
+        for (index, value) in values.iter().enumerate() {
+          if index > 0 {
+            text.push(',');
+          }
+          indent(text, depth + 1);
+          value.write_pretty(text, depth + 1);
+        }
+        indent(text, depth);
+        text.push(']');
+      },
+      Json::Object(members) if !members.is_empty() => {
+        text.push('{');
+        for (index, (key, value)) in members.iter().enumerate() {
+          if index > 0 {
+            text.push(',');
+          }
+          indent(text, depth + 1);
+          text.push_str(&format!("{}: ", Json::from(key.as_str())));
+          value.write_pretty(text, depth + 1);
+        }
+        indent(text, depth);
+        text.push('}');
+      },
+      _ => text.push_str(&self.to_string()),
+    }
+  }
+
+  /// Builds an object from `(key, value)` pairs.
+  pub fn object<'a>(members: impl IntoIterator<Item = (&'a str, Json)>) -> Json {
+    Json::Object(members.into_iter().map(|(key, value)| (key.to_string(), value)).collect())
+  }
+}
+
+impl From<&str> for Json {
+  fn from(value: &str) -> Json {
+    Json::String(value.to_string())
+  }
+}
+
+impl From<String> for Json {
+  fn from(value: String) -> Json {
+    Json::String(value)
+  }
+}
+
+impl From<f64> for Json {
+  fn from(value: f64) -> Json {
+    Json::Number(value)
+  }
+}
+
+impl From<bool> for Json {
+  fn from(value: bool) -> Json {
+    Json::Bool(value)
+  }
+}
+
+fn write_string(f: &mut fmt::Formatter, value: &str) -> fmt::Result {
+  write!(f, "\"")?;
+
+  for ch in value.chars() {
+    match ch {
+      '"' => write!(f, "\\\"")?,
+      '\\' => write!(f, "\\\\")?,
+      '\n' => write!(f, "\\n")?,
+      '\r' => write!(f, "\\r")?,
+      '\t' => write!(f, "\\t")?,
+      ch if (ch as u32) < 0x20 => write!(f, "\\u{:04x}", ch as u32)?,
+      ch => write!(f, "{}", ch)?,
+    }
+  }
+
+  write!(f, "\"")
+}
+
+/// Compact serialization, without whitespace.
+impl fmt::Display for Json {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Json::Null => write!(f, "null"),
+      Json::Bool(value) => write!(f, "{}", value),
+      Json::Number(value) if !value.is_finite() => write!(f, "null"),
+      Json::Number(value) => write!(f, "{}", value),
+      Json::String(value) => write_string(f, value),
+      Json::Array(values) => {
+        write!(f, "[")?;
+        for (index, value) in values.iter().enumerate() {
+          if index > 0 {
+            write!(f, ",")?;
+          }
+          write!(f, "{}", value)?;
+        }
+        write!(f, "]")
+      },
+      Json::Object(members) => {
+        write!(f, "{{")?;
+        for (index, (key, value)) in members.iter().enumerate() {
+          if index > 0 {
+            write!(f, ",")?;
+          }
+          write_string(f, key)?;
+          write!(f, ":{}", value)?;
+        }
+        write!(f, "}}")
+      },
+    }
+  }
+}
+
+struct Parser<'a> {
+  text: &'a str,
+  offset: usize,
+}
+
+impl<'a> Parser<'a> {
+  fn error(&self, message: &str) -> JsonError {
+    JsonError {
+      offset: self.offset,
+      message: message.to_string(),
+    }
+  }
+
+  fn peek(&self) -> Option<char> {
+    self.text[self.offset..].chars().next()
+  }
+
+  fn whitespace(&mut self) {
+    while let Some(ch) = self.peek() {
+      if !ch.is_ascii_whitespace() {
+        break;
+      }
+      self.offset += 1;
+    }
+  }
+
+  fn expect(&mut self, literal: &str) -> Result<(), JsonError> {
+    if self.text[self.offset..].starts_with(literal) {
+      self.offset += literal.len();
+      Ok(())
+    }
+    else {
+      Err(self.error(&format!("expected `{}`", literal)))
+    }
+  }
+
+  fn value(&mut self) -> Result<Json, JsonError> {
+    self.whitespace();
+
+    match self.peek() {
+      Some('n') => self.expect("null").map(|_| Json::Null),
+      Some('t') => self.expect("true").map(|_| Json::Bool(true)),
+      Some('f') => self.expect("false").map(|_| Json::Bool(false)),
+      Some('"') => self.string().map(Json::String),
+      Some('[') => {
+        self.offset += 1;
+        let mut values = Vec::new();
+
+        self.whitespace();
+        if self.peek() == Some(']') {
+          self.offset += 1;
+          return Ok(Json::Array(values));
+        }
+
+        loop {
+          values.push(self.value()?);
+          self.whitespace();
+
+          match self.peek() {
+            Some(',') => self.offset += 1,
+            Some(']') => {
+              self.offset += 1;
+              return Ok(Json::Array(values));
+            },
+            _ => return Err(self.error("expected `,` or `]`")),
+          }
+        }
+      },
+      Some('{') => {
+        self.offset += 1;
+        let mut members = Vec::new();
+
+        self.whitespace();
+        if self.peek() == Some('}') {
+          self.offset += 1;
+          return Ok(Json::Object(members));
+        }
+
+        loop {
+          self.whitespace();
+          let key = self.string()?;
+
+          self.whitespace();
+          self.expect(":")?;
+          members.push((key, self.value()?));
+          self.whitespace();
+
+          match self.peek() {
+            Some(',') => self.offset += 1,
+            Some('}') => {
+              self.offset += 1;
+              return Ok(Json::Object(members));
+            },
+            _ => return Err(self.error("expected `,` or `}`")),
+          }
+        }
+      },
+      Some(ch) if ch == '-' || ch.is_ascii_digit() => {
+        let start = self.offset;
+
+        while let Some(ch) = self.peek() {
+          if !(ch.is_ascii_digit() || "+-.eE".contains(ch)) {
+            break;
+          }
+          self.offset += 1;
+        }
+
+        self.text[start..self.offset]
+          .parse()
+          .map(Json::Number)
+          .map_err(|_| JsonError {
+            offset: start,
+            message: "invalid number".to_string(),
+          })
+      },
+      Some(_) => Err(self.error("expected a JSON value")),
+      None => Err(self.error("unexpected end of the text")),
+    }
+  }
+
+  fn string(&mut self) -> Result<String, JsonError> {
+    self.expect("\"")?;
+    let mut value = String::new();
+
+    loop {
+      let ch = self.peek().ok_or_else(|| self.error("unterminated string"))?;
+      self.offset += ch.len_utf8();
+
+      match ch {
+        '"' => return Ok(value),
+        '\\' => {
+          let escape = self.peek().ok_or_else(|| self.error("unterminated string"))?;
+          self.offset += 1;
+
+          match escape {
+            '"' | '\\' | '/' => value.push(escape),
+            'b' => value.push('\u{8}'),
+            'f' => value.push('\u{c}'),
+            'n' => value.push('\n'),
+            'r' => value.push('\r'),
+            't' => value.push('\t'),
+            'u' => {
+              let code = self
+                .text
+                .get(self.offset..self.offset + 4)
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                .ok_or_else(|| self.error("invalid \\u escape"))?;
+              self.offset += 4;
+              value.push(char::from_u32(code).unwrap_or('\u{fffd}'));
+            },
+            _ => return Err(self.error("invalid escape")),
+          }
+        },
+        ch => value.push(ch),
+      }
+    }
+  }
+}
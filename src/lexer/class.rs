@@ -0,0 +1,306 @@
+//! Classification of lexemes for highlighting: the same lexer as the compiler
+//! and the assembler, with a little context, e.g. `latt` in `gps.latt` is an
+//! instrument property.
+
+use crate::asm::isa::{lookup, ACTIONS, INSTRUMENTS, OPCODES, OPERATORS, PARAMETERS, PREFIXES, REGISTERS};
+use crate::compiler::builtins;
+use crate::compiler::parser::KEYWORDS;
+use crate::compiler::spli::INSTRUMENT_KEYWORDS;
+use crate::lexer::{Lexeme, LexemeKind, Lexer};
+
+/// The three languages of the toolchain.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Dialect {
+  /// Task list files, `.spl`.
+  Tasks,
+  /// Instrument definition files, `.spli`.
+  Instruments,
+  /// Assembly, `.splc`.
+  Assembly,
+}
+
+pub const DIALECTS: &[Dialect] = &[Dialect::Tasks, Dialect::Instruments, Dialect::Assembly];
+
+impl Dialect {
+  /// The dialect of a file, by its extension.
+  pub fn of(path: &str) -> Option<Dialect> {
+    DIALECTS
+      .iter()
+      .copied()
+      .find(|dialect| path.ends_with(&format!(".{}", dialect.extension())))
+  }
+
+  pub fn extension(self) -> &'static str {
+    match self {
+      Dialect::Tasks => "spl",
+      Dialect::Instruments => "spli",
+      Dialect::Assembly => "splc",
+    }
+  }
+
+  /// A human readable name, as editors list languages.
+  pub fn name(self) -> &'static str {
+    match self {
+      Dialect::Tasks => "Splice",
+      Dialect::Instruments => "Splice Instruments",
+      Dialect::Assembly => "Splice Assembly",
+    }
+  }
+
+  /// The words highlighted as keywords.
+  pub fn keywords(self) -> Vec<&'static str> {
+    match self {
+      Dialect::Tasks => KEYWORDS
+        .iter()
+        .copied()
+        .filter(|keyword| !TYPES.contains(keyword))
+        .chain(FREQUENCIES.iter().copied())
+        .collect(),
+      Dialect::Instruments => INSTRUMENT_KEYWORDS.to_vec(),
+      Dialect::Assembly => OPCODES.iter().map(|(mnemonic, _)| *mnemonic).collect(),
+    }
+  }
+}
+
+pub const TYPES: &[&str] = &["i32", "f32"];
+
+/// `freq: once;` and `freq: always;`.
+const FREQUENCIES: &[&str] = &["once", "always"];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TokenClass {
+  Keyword,
+  /// `i32` and `f32`.
+  Type,
+  /// Numbers, with their suffix: `2.5`, `10s`, `1.0f`.
+  Literal,
+  /// Arithmetic, comparisons and assignment, and in assembly the prefixes and
+  /// comparison operators of `OP_CMP`.
+  Operator,
+  /// `gps`, `INST_GPS`.
+  Instrument,
+  /// `latt` in `gps.latt`, `P_GPS_LATT`, `TASK.status`, and in assembly
+  /// the actions, which are parameters of `OP_ACT`.
+  Property,
+  /// Built-in functions and instrument actions: `sin`, `do_jpg` in
+  /// `img.do_jpg()`.
+  Function,
+  /// `IREG_A`, `FREG_B`.
+  Register,
+  /// Names of groups, tasks, variables and data labels.
+  Identifier,
+  Punctuation,
+  Comment,
+}
+
+pub const TOKEN_CLASSES: &[TokenClass] = &[
+  TokenClass::Keyword,
+  TokenClass::Type,
+  TokenClass::Literal,
+  TokenClass::Operator,
+  TokenClass::Instrument,
+  TokenClass::Property,
+  TokenClass::Function,
+  TokenClass::Register,
+  TokenClass::Identifier,
+  TokenClass::Punctuation,
+  TokenClass::Comment,
+];
+
+/// A lexeme and its class.
+#[derive(Clone, Copy, Debug)]
+pub struct Token<'a> {
+  pub lexeme: Lexeme<'a>,
+  pub class: TokenClass,
+}
+
+/// Classifies every lexeme of `source` but whitespace and unknown characters,
+/// in source order. In assembly, `;` starts a comment as well as `//`.
+pub fn classify(source: &str, dialect: Dialect) -> Vec<Token<'_>> {
+  let mut lexemes: Vec<Lexeme> = Vec::new();
+  let mut comment_line = None;
+
+  for lexeme in Lexer::new(source) {
+    let (line, column) = lexeme.location;
+
+    if comment_line == Some(line) {
+      continue;
+    }
+
+    match lexeme.kind {
+      LexemeKind::Whitespace | LexemeKind::Unknown | LexemeKind::Eof => {},
+      LexemeKind::Semicolon if dialect == Dialect::Assembly => {
+        let offset = line_offset(source, line) + column;
+        let length = source[offset..].find('\n').unwrap_or(source.len() - offset);
+
+        lexemes.push(Lexeme {
+          kind: LexemeKind::Comment,
+          code_span: source[offset..offset + length].trim_end_matches('\r'),
+          location: lexeme.location,
+        });
+        comment_line = Some(line);
+      },
+      _ => lexemes.push(lexeme),
+    }
+  }
+
+  let classes: Vec<TokenClass> = (0..lexemes.len())
+    .map(|index| match dialect {
+      Dialect::Tasks => task_class(&lexemes, index),
+      Dialect::Instruments => instrument_class(&lexemes, index),
+      Dialect::Assembly => assembly_class(&lexemes, index),
+    })
+    .collect();
+
+  lexemes
+    .into_iter()
+    .zip(classes)
+    .map(|(lexeme, class)| Token { lexeme, class })
+    .collect()
+}
+
+fn line_offset(source: &str, line: usize) -> usize {
+  source.split_inclusive('\n').take(line).map(str::len).sum()
+}
+
+/// Whether `lexeme` directly follows `previous`, as the `s` of `10s` does.
+fn adjacent(previous: &Lexeme, lexeme: &Lexeme) -> bool {
+  previous.location.0 == lexeme.location.0 && previous.location.1 + previous.code_span.len() == lexeme.location.1
+}
+
+fn kind_at(lexemes: &[Lexeme], index: Option<usize>) -> Option<LexemeKind> {
+  index.and_then(|index| lexemes.get(index)).map(|lexeme| lexeme.kind)
+}
+
+/// The classes that do not depend on the dialect.
+fn common_class(lexeme: &Lexeme) -> Option<TokenClass> {
+  use LexemeKind::*;
+
+  match lexeme.kind {
+    Comment => Some(TokenClass::Comment),
+    Integer | Float => Some(TokenClass::Literal),
+    Plus | Minus | Asterisk | Slash | Assign | Equal | NotEqual | Less | LessEqual | Greater | GreaterEqual => {
+      Some(TokenClass::Operator)
+    },
+    Identifier => None,
+    _ => Some(TokenClass::Punctuation),
+  }
+}
+
+fn task_class(lexemes: &[Lexeme], index: usize) -> TokenClass {
+  let lexeme = &lexemes[index];
+  if let Some(class) = common_class(lexeme) {
+    return class;
+  }
+
+  let text = lexeme.code_span;
+  let previous = index.checked_sub(1).map(|index| &lexemes[index]);
+  let next = kind_at(lexemes, Some(index + 1));
+
+  // `10s`, `1m`
+  if previous.is_some_and(|previous| previous.kind == LexemeKind::Integer && adjacent(previous, lexeme)) {
+    return TokenClass::Literal;
+  }
+
+  // `INSTRUMENT.MEMBER` and `TASK.MEMBER`
+  if previous.is_some_and(|previous| previous.kind == LexemeKind::Dot) {
+    let owner = index.checked_sub(2).map(|index| lexemes[index]);
+
+    if let Some(instrument) = owner.and_then(|owner| builtins::instrument(owner.code_span)) {
+      if builtins::action(instrument, text).is_some() {
+        return TokenClass::Function;
+      }
+      if builtins::property(instrument, text).is_some() {
+        return TokenClass::Property;
+      }
+    }
+    if text == "status" {
+      return TokenClass::Property;
+    }
+
+    return TokenClass::Identifier;
+  }
+
+  if TYPES.contains(&text) {
+    TokenClass::Type
+  }
+  else if Dialect::Tasks.keywords().contains(&text) {
+    TokenClass::Keyword
+  }
+  else if next == Some(LexemeKind::Dot) && builtins::instrument(text).is_some() {
+    TokenClass::Instrument
+  }
+  else if next == Some(LexemeKind::LeftParen) && builtins::function(text).is_some() {
+    TokenClass::Function
+  }
+  else {
+    TokenClass::Identifier
+  }
+}
+
+fn instrument_class(lexemes: &[Lexeme], index: usize) -> TokenClass {
+  let lexeme = &lexemes[index];
+  if let Some(class) = common_class(lexeme) {
+    return class;
+  }
+
+  let text = lexeme.code_span;
+  let previous = index.checked_sub(1).map(|index| lexemes[index].code_span);
+
+  match previous {
+    Some("inst") => TokenClass::Instrument,
+    Some("prop") | Some("var") => TokenClass::Property,
+    _ if TYPES.contains(&text) => TokenClass::Type,
+    _ if INSTRUMENT_KEYWORDS.contains(&text) => TokenClass::Keyword,
+    _ => TokenClass::Identifier,
+  }
+}
+
+fn assembly_class(lexemes: &[Lexeme], index: usize) -> TokenClass {
+  let lexeme = &lexemes[index];
+  let previous = index.checked_sub(1).map(|index| &lexemes[index]);
+  let starts_line = starts_line_at(lexemes, index);
+
+  // `.equ`, `.macro`, `.include`
+  if lexeme.kind == LexemeKind::Dot && starts_line && kind_at(lexemes, Some(index + 1)) == Some(LexemeKind::Identifier) {
+    return TokenClass::Keyword;
+  }
+  if let Some(class) = common_class(lexeme) {
+    return class;
+  }
+
+  let text = lexeme.code_span;
+
+  // `1.0f`, `5i`
+  if previous.is_some_and(|previous| {
+    matches!(previous.kind, LexemeKind::Integer | LexemeKind::Float) && adjacent(previous, lexeme)
+  }) {
+    return TokenClass::Literal;
+  }
+  if previous.is_some_and(|previous| previous.kind == LexemeKind::Dot && starts_line_at(lexemes, index - 1)) {
+    return TokenClass::Keyword;
+  }
+
+  if lookup(OPCODES, text).is_some() {
+    TokenClass::Keyword
+  }
+  else if lookup(PREFIXES, text).is_some() || lookup(OPERATORS, text).is_some() {
+    TokenClass::Operator
+  }
+  else if lookup(INSTRUMENTS, text).is_some() {
+    TokenClass::Instrument
+  }
+  else if lookup(PARAMETERS, text).is_some() || lookup(ACTIONS, text).is_some() {
+    TokenClass::Property
+  }
+  else if lookup(REGISTERS, text).is_some() {
+    TokenClass::Register
+  }
+  else {
+    TokenClass::Identifier
+  }
+}
+
+fn starts_line_at(lexemes: &[Lexeme], index: usize) -> bool {
+  index == 0 || lexemes[index - 1].location.0 != lexemes[index].location.0
+}
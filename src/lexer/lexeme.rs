@@ -17,6 +17,20 @@ pub enum LexemeKind {
   Asterisk,
   Slash,
 
+  Comma,
+  Colon,
+  Semicolon,
+  Dot,
+  Assign,
+  Equal,
+  NotEqual,
+  Less,
+  LessEqual,
+  Greater,
+  GreaterEqual,
+
+  Integer,
+  Float,
   Identifier,
 }
 
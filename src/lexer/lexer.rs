@@ -57,10 +57,43 @@ impl<'a> Lexer<'a> {
     }
   }
 
+  /// The length of the number at the current position: digits, an optional
+  /// fraction and an optional exponent, e.g. `40001`, `.5`, `9.0E4`, `1E-15`.
+  /// Signs are lexed as `Minus`, so that `a-1` is a subtraction.
+  fn number(&mut self) -> Option<Lexeme<'a>> {
+    let rest = &self.input.as_bytes()[self.position..];
+    let digits = |from: usize| rest[from..].iter().take_while(|byte| byte.is_ascii_digit()).count();
+
+    let mut length = digits(0);
+    let mut kind = LexemeKind::Integer;
+
+    if rest.get(length) == Some(&b'.') && rest.get(length + 1).is_some_and(u8::is_ascii_digit) {
+      length += 1 + digits(length + 1);
+      kind = LexemeKind::Float;
+    }
+
+    if let Some(b'e') | Some(b'E') = rest.get(length) {
+      let sign = match rest.get(length + 1) {
+        Some(b'+') | Some(b'-') => 1,
+        _ => 0,
+      };
+      let exponent = digits(length + 1 + sign);
+
+      if exponent > 0 {
+        length += 1 + sign + exponent;
+        kind = LexemeKind::Float;
+      }
+    }
+
+    self.lexeme(kind, length)
+  }
+
   pub fn next_lexeme(&mut self) -> Option<Lexeme<'a>> {
-    let mut chars = self.input.chars();
+    let mut chars = self.input[self.position..].chars();
+
+    if let Some(ch) = chars.next() {
+      let next_ch = chars.clone().next();
 
-    if let Some(ch) = chars.nth(self.position) {
       match ch {
         '(' => self.lexeme(LexemeKind::LeftParen, 1),
         ')' => self.lexeme(LexemeKind::RightParen, 1),
@@ -69,22 +102,23 @@ impl<'a> Lexer<'a> {
         '[' => self.lexeme(LexemeKind::LeftSquare, 1),
         ']' => self.lexeme(LexemeKind::RightSquare, 1),
 
+        ',' => self.lexeme(LexemeKind::Comma, 1),
+        ':' => self.lexeme(LexemeKind::Colon, 1),
+        ';' => self.lexeme(LexemeKind::Semicolon, 1),
+        '=' if next_ch == Some('=') => self.lexeme(LexemeKind::Equal, 2),
+        '=' => self.lexeme(LexemeKind::Assign, 1),
+        '<' if next_ch == Some('>') => self.lexeme(LexemeKind::NotEqual, 2),
+        '<' if next_ch == Some('=') => self.lexeme(LexemeKind::LessEqual, 2),
+        '<' => self.lexeme(LexemeKind::Less, 1),
+        '>' if next_ch == Some('=') => self.lexeme(LexemeKind::GreaterEqual, 2),
+        '>' => self.lexeme(LexemeKind::Greater, 1),
+
         '+' => self.lexeme(LexemeKind::Plus, 1),
         '-' => self.lexeme(LexemeKind::Minus, 1),
         '*' => self.lexeme(LexemeKind::Asterisk, 1),
         '/' => {
-          let next_ch = chars.next()?;
-
-          if next_ch == '/' {
-            let mut length = 2;
-
-            while let Some(comment_ch) = chars.next() {
-              if comment_ch == '\n' {
-                break;
-              }
-
-              length += 1;
-            }
+          if next_ch == Some('/') {
+            let length = self.input[self.position..].find('\n').unwrap_or(self.input.len() - self.position);
 
             self.lexeme(LexemeKind::Comment, length)
           }
@@ -93,25 +127,29 @@ impl<'a> Lexer<'a> {
           }
         },
 
+        '.' if next_ch.is_some_and(|next_ch| next_ch.is_ascii_digit()) => self.number(),
+        '.' => self.lexeme(LexemeKind::Dot, 1),
+        ch if ch.is_ascii_digit() => self.number(),
+
         '\n' => self.new_line(),
         '\t' => self.lexeme(LexemeKind::Whitespace, 1),
 
-        ch @ _ if ch.is_whitespace() => self.lexeme(LexemeKind::Whitespace, 1),
-        ch @ _ if ch.is_alphabetic() => {
-          let mut length = 1;
+        ch if ch.is_whitespace() => self.lexeme(LexemeKind::Whitespace, ch.len_utf8()),
+        ch if ch.is_alphabetic() => {
+          let mut length = ch.len_utf8();
 
-          while let Some(ident_ch) = chars.next() {
+          for ident_ch in chars {
             if !ident_ch.is_alphanumeric() && ident_ch != '_' {
               break;
             }
 
-            length += 1;
+            length += ident_ch.len_utf8();
           }
 
           self.lexeme(LexemeKind::Identifier, length)
         },
 
-        _ => self.lexeme(LexemeKind::Unknown, 1),
+        ch => self.lexeme(LexemeKind::Unknown, ch.len_utf8()),
       }
     } else {
       if self.has_hit_eof {
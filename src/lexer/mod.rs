@@ -1,5 +1,8 @@
+pub mod class;
 pub mod lexeme;
+#[allow(clippy::module_inception)]
 pub mod lexer;
+pub mod textmate;
 
 pub use lexeme::*;
 pub use lexer::*;
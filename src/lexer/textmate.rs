@@ -0,0 +1,190 @@
+//! TextMate grammars, as VS Code, Sublime Text and most other editors load
+//! them, generated from the tables `classify` uses, so that highlighting in
+//! an editor matches the lexer.
+
+use crate::asm::isa::{Table, ACTIONS, INSTRUMENTS, OPCODES, OPERATORS, PARAMETERS, PREFIXES, REGISTERS};
+use crate::compiler::builtins::{self, FUNCTIONS};
+use crate::json::Json;
+use crate::lexer::class::{Dialect, TokenClass, TYPES};
+
+/// Numbers as the lexer reads them: `40001`, `2.5`, `.5`, `9.0E4`, `1E-15`.
+const NUMBER: &str = r"(?:[0-9]+(?:\.[0-9]+)?|\.[0-9]+)(?:[eE][+-]?[0-9]+)?";
+/// Identifiers as the lexer reads them.
+const IDENTIFIER: &str = r"[[:alpha:]][[:alnum:]_]*";
+const OPERATOR: &str = r"==|<>|<=|>=|[-+*/=<>]";
+const PUNCTUATION: &str = r"[{}()\[\];,:.]";
+
+/// The TextMate scope of a class, without the language suffix.
+pub fn scope(class: TokenClass) -> &'static str {
+  match class {
+    TokenClass::Keyword => "keyword.control",
+    TokenClass::Type => "storage.type",
+    TokenClass::Literal => "constant.numeric",
+    TokenClass::Operator => "keyword.operator",
+    TokenClass::Instrument => "entity.name.namespace",
+    TokenClass::Property => "variable.other.property",
+    TokenClass::Function => "support.function",
+    TokenClass::Register => "variable.language.register",
+    TokenClass::Identifier => "variable.other",
+    TokenClass::Punctuation => "punctuation",
+    TokenClass::Comment => "comment.line",
+  }
+}
+
+/// `source.spl`, `source.spli` and `source.splc`.
+pub fn scope_name(dialect: Dialect) -> String {
+  format!("source.{}", dialect.extension())
+}
+
+/// The `.tmLanguage.json` grammar of a dialect.
+pub fn grammar(dialect: Dialect) -> Json {
+  let mut rules = Rules {
+    suffix: dialect.extension(),
+    patterns: Vec::new(),
+  };
+
+  match dialect {
+    Dialect::Tasks => tasks(&mut rules),
+    Dialect::Instruments => instruments(&mut rules),
+    Dialect::Assembly => assembly(&mut rules),
+  }
+
+  Json::object(vec![
+    ("name", Json::from(dialect.name())),
+    ("scopeName", Json::from(scope_name(dialect))),
+    ("fileTypes", Json::Array(vec![Json::from(dialect.extension())])),
+    (
+      "comment",
+      Json::from("Generated by `splice grammar` from the Splice lexer, do not edit."),
+    ),
+    ("patterns", Json::Array(rules.patterns)),
+  ])
+}
+
+struct Rules {
+  suffix: &'static str,
+  patterns: Vec<Json>,
+}
+
+impl Rules {
+  fn name(&self, class: TokenClass) -> Json {
+    Json::from(format!("{}.{}", scope(class), self.suffix))
+  }
+
+  fn add(&mut self, pattern: String, class: TokenClass) {
+    let rule = Json::object(vec![("match", Json::from(pattern)), ("name", self.name(class))]);
+
+    self.patterns.push(rule);
+  }
+
+  /// A pattern whose groups are highlighted as `classes`, in order.
+  fn add_captures(&mut self, pattern: String, classes: &[TokenClass]) {
+    let captures = classes
+      .iter()
+      .enumerate()
+      .map(|(index, class)| {
+        let name = (index + 1).to_string();
+
+        (name, Json::object(vec![("name", self.name(*class))]))
+      })
+      .collect();
+    let rule = Json::object(vec![("match", Json::from(pattern)), ("captures", Json::Object(captures))]);
+
+    self.patterns.push(rule);
+  }
+
+  fn words(&mut self, words: &[&str], class: TokenClass) {
+    if !words.is_empty() {
+      self.add(format!(r"\b(?:{})\b", words.join("|")), class);
+    }
+  }
+
+  fn comment(&mut self, start: &str) {
+    self.add(format!("(?:{}).*$", start), TokenClass::Comment);
+  }
+
+  fn numbers(&mut self) {
+    self.add(format!(r"(?<![[:alnum:]_]){}", NUMBER), TokenClass::Literal);
+  }
+
+  fn operators_and_punctuation(&mut self) {
+    self.add(OPERATOR.to_string(), TokenClass::Operator);
+    self.add(PUNCTUATION.to_string(), TokenClass::Punctuation);
+  }
+}
+
+fn mnemonics(table: Table) -> Vec<&'static str> {
+  table.iter().map(|(mnemonic, _)| *mnemonic).collect()
+}
+
+fn tasks(rules: &mut Rules) {
+  use TokenClass::*;
+
+  rules.comment("//");
+
+  // `gps.latt` and `img.do_jpg`, per instrument
+  for (mnemonic, id) in INSTRUMENTS {
+    let name = mnemonic["INST_".len()..].to_ascii_lowercase();
+    let properties = builtins::properties(*id);
+    let actions = builtins::actions(*id);
+
+    if !actions.is_empty() {
+      rules.add_captures(
+        format!(r"\b({})\s*(\.)\s*({})\b", name, actions.join("|")),
+        &[Instrument, Punctuation, Function],
+      );
+    }
+    if !properties.is_empty() {
+      rules.add_captures(
+        format!(r"\b({})\s*(\.)\s*({})\b", name, properties.join("|")),
+        &[Instrument, Punctuation, Property],
+      );
+    }
+    rules.add(format!(r"\b{}\b(?=\s*\.)", name), Instrument);
+  }
+  rules.add_captures(r"(\.)\s*(status)\b".to_string(), &[Punctuation, Property]);
+
+  // `10s`, `1m`
+  rules.add(format!(r"(?<![[:alnum:]_.])[0-9]+{}", IDENTIFIER), Literal);
+  rules.numbers();
+  rules.words(TYPES, Type);
+  rules.words(&Dialect::Tasks.keywords(), Keyword);
+
+  let functions: Vec<&str> = FUNCTIONS.iter().map(|function| function.name).collect();
+  rules.add(format!(r"\b(?:{})\b(?=\s*\()", functions.join("|")), Function);
+  rules.operators_and_punctuation();
+}
+
+fn instruments(rules: &mut Rules) {
+  use TokenClass::*;
+
+  rules.comment("//");
+  rules.add_captures(format!(r"\b(inst)\s+({})", IDENTIFIER), &[Keyword, Instrument]);
+  rules.add_captures(format!(r"\b(prop|var)\s+({})", IDENTIFIER), &[Keyword, Property]);
+  rules.numbers();
+  rules.words(TYPES, Type);
+  rules.words(&Dialect::Instruments.keywords(), Keyword);
+  rules.operators_and_punctuation();
+}
+
+fn assembly(rules: &mut Rules) {
+  use TokenClass::*;
+
+  rules.comment(";|//");
+  // `.equ`, `.macro`, `.include`
+  rules.add(format!(r"^\s*\.{}", IDENTIFIER), Keyword);
+  // `1.0f`, `5i`
+  rules.add(format!(r"(?<![[:alnum:]_]){}(?:[[:alpha:]][[:alnum:]_]*)?", NUMBER), Literal);
+
+  // mnemonics are not case sensitive
+  let mut words = |words: Vec<&str>, class: TokenClass| {
+    rules.add(format!(r"(?i)\b(?:{})\b", words.join("|")), class);
+  };
+  words(mnemonics(OPCODES), Keyword);
+  words([mnemonics(PREFIXES), mnemonics(OPERATORS)].concat(), Operator);
+  words(mnemonics(INSTRUMENTS), Instrument);
+  words([mnemonics(PARAMETERS), mnemonics(ACTIONS)].concat(), Property);
+  words(mnemonics(REGISTERS), Register);
+
+  rules.operators_and_punctuation();
+}
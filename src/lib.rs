@@ -0,0 +1,12 @@
+pub mod asm;
+pub mod compiler;
+pub mod debug;
+pub mod json;
+pub mod lexer;
+pub mod link;
+pub mod lsp;
+pub mod manifest;
+pub mod splx;
+pub mod target;
+pub mod toml;
+pub mod vm;
@@ -0,0 +1,623 @@
+//! The dependencies between the tasks of a group: which tasks load which
+//! other tasks' data (`OP_LEA` of a foreign `TASK_ID`) and which tasks only
+//! run depending on the status of others (`TSX_EQ`/`TSX_NE`), with the
+//! cycles among them and the loads of data no task stores to. Tasks can only
+//! store to their own data (`OP_MOV, PRE_MOV_RAM`), so a load of data its
+//! task never stores to always reads its initial value. Tasks depended on
+//! but not given are reported as missing.
+
+use std::fmt;
+use std::path::Path;
+
+use crate::asm::isa::unpack32to4x8;
+use crate::asm::Task;
+use crate::json::Json;
+use crate::link::referenced_task;
+use crate::target::{Operand, Target};
+
+/// A task, named after its debug information or its source file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Node {
+  pub group_id: u8,
+  pub task_id: u8,
+  pub group: Option<String>,
+  pub name: Option<String>,
+}
+
+/// A data word of a task, named after its variable or data label.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DataWord {
+  pub address: u8,
+  pub name: Option<String>,
+}
+
+impl fmt::Display for DataWord {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match &self.name {
+      Some(name) => write!(f, "{}", name),
+      None => write!(f, "{}", self.address),
+    }
+  }
+}
+
+/// `to` depends on `from`, a task of the same group.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Dependency {
+  pub group_id: u8,
+  pub from: u8,
+  pub to: u8,
+  /// The data words of `from` that `to` loads.
+  pub reads: Vec<DataWord>,
+  /// Whether `to` compares the status of `from` in a `TSX_EQ`/`TSX_NE`.
+  pub status: bool,
+}
+
+/// Tasks of a group that all depend on one another, directly or through
+/// other tasks of the cycle.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cycle {
+  pub group_id: u8,
+  /// Ordered by id.
+  pub tasks: Vec<u8>,
+}
+
+impl fmt::Display for Cycle {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let tasks: Vec<String> = self
+      .tasks
+      .iter()
+      .map(|task_id| format!("{}.{}", self.group_id, task_id))
+      .collect();
+
+    write!(f, "tasks {} depend on each other", tasks.join(", "))
+  }
+}
+
+/// A load of a data word that its task never stores to.
+#[derive(Clone, Debug, PartialEq)]
+pub struct UnwrittenRead {
+  pub group_id: u8,
+  /// The task loading it, and the word of the load, the header being word 0.
+  pub task_id: u8,
+  pub word: usize,
+  pub location: Option<String>,
+  /// The task of the data word.
+  pub owner: u8,
+  pub data: DataWord,
+}
+
+impl fmt::Display for UnwrittenRead {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    if let Some(location) = &self.location {
+      write!(f, "{}: ", location)?;
+    }
+
+    write!(
+      f,
+      "word {} of task {}.{} reads `{}` of task {}.{}, which no task writes",
+      self.word, self.group_id, self.task_id, self.data, self.group_id, self.owner
+    )
+  }
+}
+
+/// A task that others depend on but that is not among the tasks given, so
+/// whether its data is written is unknown.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MissingTask {
+  pub group_id: u8,
+  pub task_id: u8,
+  /// The tasks depending on it, ordered by id.
+  pub dependents: Vec<u8>,
+}
+
+impl fmt::Display for MissingTask {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let dependents: Vec<String> = self
+      .dependents
+      .iter()
+      .map(|task_id| format!("{}.{}", self.group_id, task_id))
+      .collect();
+
+    let dependents = match dependents.as_slice() {
+      [task] => format!("task {} depends", task),
+      _ => format!("tasks {} depend", dependents.join(", ")),
+    };
+
+    write!(f, "task {}.{} is not in the inputs, but {} on it", self.group_id, self.task_id, dependents)
+  }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Graph {
+  /// Ordered by group and task id.
+  pub nodes: Vec<Node>,
+  pub dependencies: Vec<Dependency>,
+  pub cycles: Vec<Cycle>,
+  pub unwritten: Vec<UnwrittenRead>,
+  pub missing: Vec<MissingTask>,
+}
+
+impl Graph {
+  pub fn new(tasks: &[Task], target: &Target) -> Graph {
+    let mut tasks: Vec<&Task> = tasks.iter().collect();
+    tasks.sort_by_key(|task| (task.header.group_id, task.header.task_id));
+
+    let find = |group_id: u8, task_id: u8| {
+      tasks
+        .iter()
+        .find(|task| task.header.group_id == group_id && task.header.task_id == task_id)
+    };
+    let data_word = |group_id: u8, task_id: u8, address: u8| DataWord {
+      address,
+      name: find(group_id, task_id).and_then(|task| variable(task, address)),
+    };
+
+    let mut dependencies: Vec<Dependency> = Vec::new();
+    let mut unwritten = Vec::new();
+
+    for task in &tasks {
+      let header = task.header;
+
+      for (index, word) in task.code.iter().enumerate() {
+        let (owner, address) = match referenced_task(target, *word) {
+          Some((owner, address)) if owner != header.task_id => (owner, address),
+          _ => continue,
+        };
+
+        let position = dependencies
+          .iter()
+          .position(|dependency| {
+            (dependency.group_id, dependency.from, dependency.to) == (header.group_id, owner, header.task_id)
+          })
+          .unwrap_or_else(|| {
+            dependencies.push(Dependency {
+              group_id: header.group_id,
+              from: owner,
+              to: header.task_id,
+              reads: Vec::new(),
+              status: false,
+            });
+            dependencies.len() - 1
+          });
+        let dependency = &mut dependencies[position];
+
+        let address = match address {
+          Some(address) => address,
+          None => {
+            dependency.status = true;
+            continue;
+          },
+        };
+
+        if !dependency.reads.iter().any(|read| read.address == address) {
+          dependency.reads.push(data_word(header.group_id, owner, address));
+        }
+
+        // reported as missing instead
+        let owner = match find(header.group_id, owner) {
+          Some(owner) => owner,
+          None => continue,
+        };
+        let written = stores(owner, target).contains(&address);
+        let constant = owner
+          .debug
+          .as_ref()
+          .and_then(|debug| debug.variable(address))
+          .is_some_and(|variable| variable.constant);
+
+        if !written && !constant {
+          let word = index + 1;
+
+          unwritten.push(UnwrittenRead {
+            group_id: header.group_id,
+            task_id: header.task_id,
+            word,
+            location: task
+              .debug
+              .as_ref()
+              .and_then(|debug| debug.location(word))
+              .or_else(|| task.sources.get(word).map(|source| source.location.to_string())),
+            owner: owner.header.task_id,
+            data: data_word(header.group_id, owner.header.task_id, address),
+          });
+        }
+      }
+    }
+
+    dependencies.sort_by_key(|dependency| (dependency.group_id, dependency.from, dependency.to));
+    for dependency in &mut dependencies {
+      dependency.reads.sort_by_key(|read| read.address);
+    }
+
+    let mut missing: Vec<MissingTask> = Vec::new();
+    for dependency in dependencies.iter().filter(|dependency| find(dependency.group_id, dependency.from).is_none()) {
+      match missing.last_mut() {
+        Some(task) if (task.group_id, task.task_id) == (dependency.group_id, dependency.from) => {
+          task.dependents.push(dependency.to);
+        },
+        _ => missing.push(MissingTask {
+          group_id: dependency.group_id,
+          task_id: dependency.from,
+          dependents: vec![dependency.to],
+        }),
+      }
+    }
+
+    let nodes: Vec<Node> = tasks.iter().map(|task| node(task)).collect();
+    let cycles = cycles(&nodes, &dependencies);
+
+    Graph {
+      nodes,
+      dependencies,
+      cycles,
+      unwritten,
+      missing,
+    }
+  }
+
+  fn node(&self, group_id: u8, task_id: u8) -> Option<&Node> {
+    self
+      .nodes
+      .iter()
+      .find(|node| node.group_id == group_id && node.task_id == task_id)
+  }
+
+  /// `1.2 shoot`, or `1.2` for a task without a name or outside the graph.
+  fn label(&self, group_id: u8, task_id: u8) -> String {
+    match self.node(group_id, task_id).and_then(|node| node.name.as_ref()) {
+      Some(name) => format!("{}.{} {}", group_id, task_id, name),
+      None => format!("{}.{}", group_id, task_id),
+    }
+  }
+
+  fn in_cycle(&self, dependency: &Dependency) -> bool {
+    self.cycles.iter().any(|cycle| {
+      cycle.group_id == dependency.group_id && cycle.tasks.contains(&dependency.from) && cycle.tasks.contains(&dependency.to)
+    })
+  }
+
+  fn reads_unwritten(&self, dependency: &Dependency) -> bool {
+    self.unwritten.iter().any(|read| {
+      (read.group_id, read.owner, read.task_id) == (dependency.group_id, dependency.from, dependency.to)
+    })
+  }
+
+  /// A Graphviz digraph with a cluster per group and an edge from every task
+  /// to the tasks depending on it, labelled with the data they load; edges
+  /// of status comparisons only are dashed, edges of a cycle red, and edges
+  /// loading data no task writes orange.
+  pub fn to_dot(&self) -> String {
+    let mut text = String::from("digraph tasks {\n  node [shape=box];\n");
+    let mut groups: Vec<u8> = self.nodes.iter().map(|node| node.group_id).collect();
+    groups.dedup();
+
+    for group_id in groups {
+      let label = match self.nodes.iter().find(|node| node.group_id == group_id).and_then(|node| node.group.as_ref()) {
+        Some(group) => format!("group {} {}", group_id, group),
+        None => format!("group {}", group_id),
+      };
+
+      text.push_str(&format!("  subgraph cluster_{} {{\n    label={};\n", group_id, quote(&label)));
+      for node in self.nodes.iter().filter(|node| node.group_id == group_id) {
+        text.push_str(&format!(
+          "    {} [label={}];\n",
+          quote(&format!("{}.{}", node.group_id, node.task_id)),
+          quote(&self.label(node.group_id, node.task_id))
+        ));
+      }
+      text.push_str("  }\n");
+    }
+
+    for dependency in &self.dependencies {
+      let mut label: Vec<String> = dependency.reads.iter().map(DataWord::to_string).collect();
+      let mut attributes = Vec::new();
+
+      if dependency.status {
+        label.push("status".to_string());
+      }
+      attributes.push(format!("label={}", quote(&label.join(", "))));
+      if dependency.reads.is_empty() {
+        attributes.push("style=dashed".to_string());
+      }
+      if self.in_cycle(dependency) {
+        attributes.push("color=red".to_string());
+      }
+      else if self.reads_unwritten(dependency) {
+        attributes.push("color=orange".to_string());
+      }
+
+      text.push_str(&format!(
+        "  {} -> {} [{}];\n",
+        quote(&format!("{}.{}", dependency.group_id, dependency.from)),
+        quote(&format!("{}.{}", dependency.group_id, dependency.to)),
+        attributes.join(", ")
+      ));
+    }
+
+    text.push_str("}\n");
+    text
+  }
+
+  pub fn to_json(&self) -> Json {
+    let optional = |text: &Option<String>| text.as_deref().map_or(Json::Null, Json::from);
+    let data_word = |data: &DataWord| {
+      Json::object(vec![
+        ("address", f64::from(data.address).into()),
+        ("name", optional(&data.name)),
+      ])
+    };
+
+    Json::object(vec![
+      (
+        "tasks",
+        Json::Array(
+          self
+            .nodes
+            .iter()
+            .map(|node| {
+              Json::object(vec![
+                ("group", f64::from(node.group_id).into()),
+                ("task", f64::from(node.task_id).into()),
+                ("group_name", optional(&node.group)),
+                ("name", optional(&node.name)),
+              ])
+            })
+            .collect(),
+        ),
+      ),
+      (
+        "dependencies",
+        Json::Array(
+          self
+            .dependencies
+            .iter()
+            .map(|dependency| {
+              Json::object(vec![
+                ("group", f64::from(dependency.group_id).into()),
+                ("from", f64::from(dependency.from).into()),
+                ("to", f64::from(dependency.to).into()),
+                ("reads", Json::Array(dependency.reads.iter().map(data_word).collect())),
+                ("status", dependency.status.into()),
+              ])
+            })
+            .collect(),
+        ),
+      ),
+      (
+        "cycles",
+        Json::Array(
+          self
+            .cycles
+            .iter()
+            .map(|cycle| {
+              Json::object(vec![
+                ("group", f64::from(cycle.group_id).into()),
+                ("tasks", Json::Array(cycle.tasks.iter().map(|task_id| f64::from(*task_id).into()).collect())),
+              ])
+            })
+            .collect(),
+        ),
+      ),
+      (
+        "unwritten",
+        Json::Array(
+          self
+            .unwritten
+            .iter()
+            .map(|read| {
+              Json::object(vec![
+                ("group", f64::from(read.group_id).into()),
+                ("task", f64::from(read.task_id).into()),
+                ("word", (read.word as f64).into()),
+                ("location", optional(&read.location)),
+                ("owner", f64::from(read.owner).into()),
+                ("data", data_word(&read.data)),
+              ])
+            })
+            .collect(),
+        ),
+      ),
+      (
+        "missing",
+        Json::Array(
+          self
+            .missing
+            .iter()
+            .map(|task| {
+              Json::object(vec![
+                ("group", f64::from(task.group_id).into()),
+                ("task", f64::from(task.task_id).into()),
+                ("dependents", Json::Array(task.dependents.iter().map(|task_id| f64::from(*task_id).into()).collect())),
+              ])
+            })
+            .collect(),
+        ),
+      ),
+    ])
+  }
+}
+
+/// A dependency per line, then the cycles, unwritten loads and missing tasks
+/// as warnings.
+impl fmt::Display for Graph {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    for dependency in &self.dependencies {
+      let mut what = Vec::new();
+
+      if !dependency.reads.is_empty() {
+        let reads: Vec<String> = dependency.reads.iter().map(DataWord::to_string).collect();
+
+        what.push(format!("reads {}", reads.join(", ")));
+      }
+      if dependency.status {
+        what.push("compares its status".to_string());
+      }
+
+      writeln!(
+        f,
+        "{} -> {}: {}",
+        self.label(dependency.group_id, dependency.from),
+        self.label(dependency.group_id, dependency.to),
+        what.join(", ")
+      )?;
+    }
+
+    for cycle in &self.cycles {
+      writeln!(f, "warning: {}", cycle)?;
+    }
+    for read in &self.unwritten {
+      writeln!(f, "warning: {}", read)?;
+    }
+    for task in &self.missing {
+      writeln!(f, "warning: {}", task)?;
+    }
+
+    Ok(())
+  }
+}
+
+fn node(task: &Task) -> Node {
+  let file = task
+    .sources
+    .first()
+    .and_then(|source| Path::new(&*source.location.file).file_stem())
+    .map(|stem| stem.to_string_lossy().into_owned());
+
+  Node {
+    group_id: task.header.group_id,
+    task_id: task.header.task_id,
+    group: task.debug.as_ref().map(|debug| debug.group.clone()),
+    name: task.debug.as_ref().map(|debug| debug.task.clone()).or(file),
+  }
+}
+
+/// The name of a data word: its variable, or its data label.
+fn variable(task: &Task, address: u8) -> Option<String> {
+  task
+    .debug
+    .as_ref()
+    .and_then(|debug| debug.variable(address))
+    .map(|variable| variable.name.clone())
+    .or_else(|| {
+      task
+        .symbols
+        .iter()
+        .find(|symbol| symbol.address == address)
+        .map(|symbol| symbol.name.clone())
+    })
+}
+
+/// The addresses of its own data a task stores to.
+fn stores(task: &Task, target: &Target) -> Vec<u8> {
+  task
+    .code
+    .iter()
+    .filter_map(|word| {
+      let bytes = unpack32to4x8(*word);
+
+      target
+        .operands(*word)?
+        .into_iter()
+        .find(|(operand, _)| *operand == Operand::Address)
+        .map(|(_, byte)| bytes[byte])
+    })
+    .collect()
+}
+
+/// The strongly connected components of more than one task, with Tarjan's
+/// algorithm over the dependencies of every group.
+fn cycles(nodes: &[Node], dependencies: &[Dependency]) -> Vec<Cycle> {
+  struct State<'a> {
+    dependencies: &'a [Dependency],
+    group_id: u8,
+    index: Vec<(u8, usize, usize)>,
+    stack: Vec<u8>,
+    cycles: Vec<Cycle>,
+  }
+
+  impl State<'_> {
+    fn visit(&mut self, task_id: u8) {
+      let index = self.index.len();
+
+      self.index.push((task_id, index, index));
+      self.stack.push(task_id);
+
+      let successors: Vec<u8> = self
+        .dependencies
+        .iter()
+        .filter(|dependency| dependency.group_id == self.group_id && dependency.from == task_id)
+        .map(|dependency| dependency.to)
+        .collect();
+
+      for successor in successors {
+        let low = match self.index.iter().find(|(candidate, _, _)| *candidate == successor) {
+          None => {
+            self.visit(successor);
+            self.low(successor)
+          },
+          Some((_, index, _)) if self.stack.contains(&successor) => *index,
+          Some(_) => continue,
+        };
+        let entry = self.entry(task_id);
+
+        entry.2 = entry.2.min(low);
+      }
+
+      let (_, index, low) = *self.entry(task_id);
+      if index == low {
+        let start = self.stack.iter().position(|candidate| *candidate == task_id).expect("visited tasks are stacked");
+        let mut tasks = self.stack.split_off(start);
+
+        if tasks.len() > 1 {
+          tasks.sort_unstable();
+          self.cycles.push(Cycle {
+            group_id: self.group_id,
+            tasks,
+          });
+        }
+      }
+    }
+
+    fn entry(&mut self, task_id: u8) -> &mut (u8, usize, usize) {
+      self
+        .index
+        .iter_mut()
+        .find(|(candidate, _, _)| *candidate == task_id)
+        .expect("visited tasks are indexed")
+    }
+
+    fn low(&mut self, task_id: u8) -> usize {
+      self.entry(task_id).2
+    }
+  }
+
+  let mut cycles = Vec::new();
+  let mut groups: Vec<u8> = nodes.iter().map(|node| node.group_id).collect();
+  groups.dedup();
+
+  for group_id in groups {
+    let mut state = State {
+      dependencies,
+      group_id,
+      index: Vec::new(),
+      stack: Vec::new(),
+      cycles: Vec::new(),
+    };
+
+    for node in nodes.iter().filter(|node| node.group_id == group_id) {
+      if !state.index.iter().any(|(candidate, _, _)| *candidate == node.task_id) {
+        state.visit(node.task_id);
+      }
+    }
+
+    cycles.extend(state.cycles);
+  }
+
+  cycles.sort_by_key(|cycle| (cycle.group_id, cycle.tasks[0]));
+  cycles
+}
+
+/// A Graphviz string.
+fn quote(text: &str) -> String {
+  format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
@@ -0,0 +1,312 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::asm::isa::*;
+use crate::asm::preprocess::Location;
+use crate::asm::{Task, Word};
+use crate::debug::{DebugInfo, TaskDebug};
+use crate::splx;
+use crate::target::{Operand, Target};
+
+/// A task together with the name of the source file it was assembled from.
+#[derive(Clone, Debug)]
+pub struct Unit {
+  pub source: String,
+  pub task: Task,
+}
+
+/// Task table entry of a linked image.
+#[derive(Clone, Debug)]
+pub struct TaskEntry {
+  pub group_id: u8,
+  pub task_id: u8,
+  /// Offset of the task header, in words from the start of the image.
+  pub offset: usize,
+  /// Size of the task in words, header included.
+  pub size: usize,
+  pub source: String,
+}
+
+/// The result of linking: all tasks ordered by group and task id.
+#[derive(Debug)]
+pub struct Image {
+  pub entries: Vec<TaskEntry>,
+  pub units: Vec<Unit>,
+  /// The target the tasks were assembled for.
+  pub target: Target,
+}
+
+#[derive(Debug)]
+pub enum LinkError {
+  DuplicateTask {
+    group_id: u8,
+    task_id: u8,
+    first: String,
+    second: String,
+  },
+  /// An `OP_LEA` or `TSX_*` comparison names a task missing from the link set.
+  UnresolvedTask {
+    source: String,
+    group_id: u8,
+    task_id: u8,
+    /// 1-based index of the instruction within the code segment.
+    instruction: usize,
+  },
+  /// An `OP_LEA` reads past the data section of another task.
+  AddressOutOfRange {
+    source: String,
+    instruction: usize,
+    group_id: u8,
+    task_id: u8,
+    address: u8,
+    data_len: usize,
+  },
+  /// A `t<TASK_ID>.label` reference to a label the task does not declare.
+  UndefinedLabel {
+    location: Location,
+    group_id: u8,
+    task_id: u8,
+    label: String,
+  },
+}
+
+impl fmt::Display for LinkError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      LinkError::DuplicateTask { group_id, task_id, first, second } => write!(
+        f,
+        "task {}.{} is defined in both {} and {}",
+        group_id, task_id, first, second
+      ),
+      LinkError::UnresolvedTask { source, group_id, task_id, instruction } => write!(
+        f,
+        "{}: instruction {} references task {}.{}, which is not part of the link",
+        source, instruction, group_id, task_id
+      ),
+      LinkError::AddressOutOfRange { source, instruction, group_id, task_id, address, data_len } => write!(
+        f,
+        "{}: instruction {} reads address {} of task {}.{}, which has {} data words",
+        source, instruction, address, group_id, task_id, data_len
+      ),
+      LinkError::UndefinedLabel { location, group_id, task_id, label } => write!(
+        f,
+        "{}: task {}.{} has no data label `{}`",
+        location, group_id, task_id, label
+      ),
+    }
+  }
+}
+
+impl std::error::Error for LinkError {}
+
+pub struct Linker {
+  units: Vec<Unit>,
+  target: Target,
+}
+
+impl Default for Linker {
+  fn default() -> Linker {
+    Linker::new()
+  }
+}
+
+impl Linker {
+  pub fn new() -> Linker {
+    Linker::for_target(Target::opssat())
+  }
+
+  /// A linker of tasks assembled for `target`.
+  pub fn for_target(target: &Target) -> Linker {
+    Linker {
+      units: Vec::new(),
+      target: target.clone(),
+    }
+  }
+
+  pub fn add(&mut self, source: &str, task: Task) {
+    self.units.push(Unit {
+      source: source.to_string(),
+      task,
+    });
+  }
+
+  /// Orders the tasks into a task table, checks that every cross-task
+  /// reference can be resolved and patches in the addresses of other tasks'
+  /// data labels. All problems found are reported at once.
+  pub fn link(self) -> Result<Image, Vec<LinkError>> {
+    let mut errors = Vec::new();
+    let mut table: BTreeMap<(u8, u8), Unit> = BTreeMap::new();
+
+    for unit in self.units {
+      let key = (unit.task.header.group_id, unit.task.header.task_id);
+
+      if let Some(existing) = table.get(&key) {
+        errors.push(LinkError::DuplicateTask {
+          group_id: key.0,
+          task_id: key.1,
+          first: existing.source.clone(),
+          second: unit.source,
+        });
+      }
+      else {
+        table.insert(key, unit);
+      }
+    }
+
+    for unit in table.values() {
+      let header = unit.task.header;
+
+      for (index, word) in unit.task.code.iter().enumerate() {
+        let (task_id, address) = match referenced_task(&self.target, *word) {
+          Some(reference) if reference.0 != header.task_id => reference,
+          _ => continue,
+        };
+
+        match table.get(&(header.group_id, task_id)) {
+          None => errors.push(LinkError::UnresolvedTask {
+            source: unit.source.clone(),
+            group_id: header.group_id,
+            task_id,
+            instruction: index + 1,
+          }),
+          Some(target) => {
+            let data_len = target.task.data.len();
+            let relocated = unit.task.relocations.iter().any(|relocation| relocation.instruction == index);
+
+            match address {
+              Some(address) if !relocated && (address == 0 || usize::from(address) > data_len) => {
+                errors.push(LinkError::AddressOutOfRange {
+                  source: unit.source.clone(),
+                  instruction: index + 1,
+                  group_id: header.group_id,
+                  task_id,
+                  address,
+                  data_len,
+                })
+              },
+              _ => {},
+            }
+          },
+        }
+      }
+    }
+
+    let mut patches = Vec::new();
+
+    for (key, unit) in &table {
+      for relocation in &unit.task.relocations {
+        let target = match table.get(&(key.0, relocation.task_id)) {
+          Some(target) => target,
+          // already reported as an unresolved task
+          None => continue,
+        };
+
+        match target.task.symbol(&relocation.label) {
+          Some(symbol) => patches.push((*key, relocation.instruction, symbol.address)),
+          None => errors.push(LinkError::UndefinedLabel {
+            location: relocation.location.clone(),
+            group_id: key.0,
+            task_id: relocation.task_id,
+            label: relocation.label.clone(),
+          }),
+        }
+      }
+    }
+
+    if !errors.is_empty() {
+      return Err(errors);
+    }
+
+    for (key, instruction, address) in patches {
+      if let Some(unit) = table.get_mut(&key) {
+        let word = &mut unit.task.code[instruction];
+        *word = (*word & !0xFF) | Word::from(address);
+      }
+    }
+
+    for unit in table.values_mut() {
+      unit.task.relocations.clear();
+    }
+
+    let mut entries = Vec::with_capacity(table.len());
+    let mut offset = 0;
+
+    for ((group_id, task_id), unit) in &table {
+      let size = 1 + unit.task.code.len() + unit.task.data.len();
+
+      entries.push(TaskEntry {
+        group_id: *group_id,
+        task_id: *task_id,
+        offset,
+        size,
+        source: unit.source.clone(),
+      });
+      offset += size;
+    }
+
+    Ok(Image {
+      entries,
+      units: table.into_values().collect(),
+      target: self.target,
+    })
+  }
+}
+
+/// Returns the id of the task (within the same group) whose data or
+/// execution status the instruction reads, if any, together with the data
+/// address for `OP_LEA`: the operands of `data` and `task` of the ISA.
+pub fn referenced_task(target: &Target, word: Word) -> Option<(u8, Option<u8>)> {
+  let bytes = unpack32to4x8(word);
+
+  target
+    .operands(word)?
+    .into_iter()
+    .find_map(|(operand, byte)| match operand {
+      Operand::Data => Some((bytes[byte], Some(bytes[byte + 1]))),
+      Operand::Task => Some((bytes[byte], None)),
+      _ => None,
+    })
+}
+
+impl Image {
+  /// The linked executable: one `.splx` line per task, in task table order.
+  pub fn to_splx(&self) -> String {
+    let mut output = String::new();
+
+    for unit in &self.units {
+      output.push_str(&splx::format_task_for(&self.target, &unit.task.words()));
+      output.push('\n');
+    }
+
+    output
+  }
+
+  /// The debug information of the tasks that have some, `None` if none has.
+  pub fn debug(&self) -> Option<DebugInfo> {
+    let tasks: Vec<TaskDebug> = self.units.iter().filter_map(|unit| unit.task.debug.clone()).collect();
+
+    if tasks.is_empty() {
+      None
+    }
+    else {
+      Some(DebugInfo { tasks })
+    }
+  }
+
+  /// A human-readable link map listing the offset and size of every task.
+  pub fn map(&self) -> String {
+    let mut output = String::from("GROUP  TASK  OFFSET  SIZE  SOURCE\n");
+
+    for entry in &self.entries {
+      output.push_str(&format!(
+        "{:>5}  {:>4}  {:>6}  {:>4}  {}\n",
+        entry.group_id, entry.task_id, entry.offset, entry.size, entry.source
+      ));
+    }
+
+    let total: usize = self.entries.iter().map(|entry| entry.size).sum();
+    output.push_str(&format!("{} tasks, {} words\n", self.entries.len(), total));
+
+    output
+  }
+}
@@ -0,0 +1,4 @@
+pub mod graph;
+pub mod linker;
+
+pub use linker::*;
@@ -0,0 +1,517 @@
+//! What the language server knows about a task list file: its diagnostics and
+//! the symbol every name in it refers to.
+
+use crate::asm::isa::{name_of, INSTRUMENTS};
+use crate::compiler::ast::*;
+use crate::compiler::builtins::{self, Function, FUNCTIONS};
+use crate::compiler::parser::{self, KEYWORDS};
+use crate::compiler::spli::Definitions;
+use crate::compiler::{self, CompileError};
+use crate::lexer::class::Dialect;
+
+/// What a name refers to.
+#[derive(Clone, Copy)]
+pub enum Symbol<'a> {
+  Group(&'a Group),
+  Task(&'a Group, &'a Task),
+  Variable(&'a Task, &'a Variable),
+  /// `TASK.status`.
+  Status(&'a Task),
+  Instrument(u8),
+  Property(u8, &'static str),
+  Action(u8, &'static str),
+  Function(&'static Function),
+}
+
+/// A name in the source and the symbol it refers to.
+#[derive(Clone, Copy)]
+pub struct Reference<'a> {
+  pub span: Span,
+  pub symbol: Symbol<'a>,
+}
+
+/// Where a symbol is defined: a span of the document itself, or of an
+/// instrument definition file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Definition {
+  /// `None` for the document itself.
+  pub file: Option<String>,
+  pub span: Span,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CompletionKind {
+  Keyword,
+  Variable,
+  Constant,
+  Task,
+  Instrument,
+  Property,
+  Action,
+  Function,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Completion {
+  pub label: String,
+  pub kind: CompletionKind,
+  pub detail: String,
+  pub doc: String,
+}
+
+pub struct Document {
+  pub file: String,
+  pub source: String,
+  /// The last version of the file that parsed, kept while the file is being
+  /// edited into shape.
+  pub program: Option<Program>,
+  pub errors: Vec<CompileError>,
+}
+
+impl Document {
+  pub fn new(file: &str, source: &str) -> Document {
+    let mut document = Document {
+      file: file.to_string(),
+      source: String::new(),
+      program: None,
+      errors: Vec::new(),
+    };
+
+    document.update(source);
+    document
+  }
+
+  /// Replaces the text and checks it again; instrument definition files are
+  /// only parsed, and assembly files only highlighted.
+  pub fn update(&mut self, source: &str) {
+    self.source = source.to_string();
+
+    self.errors = match Dialect::of(&self.file) {
+      Some(Dialect::Instruments) => Definitions::parse(&self.file, source).err().into_iter().collect(),
+      Some(Dialect::Assembly) => Vec::new(),
+      _ => {
+        if let Ok(program) = parser::parse(&self.file, source) {
+          self.program = Some(program);
+        }
+
+        match compiler::compile(&self.file, source) {
+          Ok(_) => Vec::new(),
+          Err(errors) => errors,
+        }
+      },
+    };
+  }
+
+  /// Every name of the last program that parsed, in source order.
+  pub fn references(&self) -> Vec<Reference<'_>> {
+    let mut references = Vec::new();
+
+    if let Some(program) = &self.program {
+      for group in &program.groups {
+        references.push(Reference {
+          span: group.name.span,
+          symbol: Symbol::Group(group),
+        });
+
+        for task in &group.tasks {
+          let mut walker = Walker {
+            group,
+            task,
+            references: &mut references,
+          };
+
+          walker.push(task.name.span, Symbol::Task(group, task));
+          for variable in &task.data {
+            walker.push(variable.name.span, Symbol::Variable(task, variable));
+          }
+          for condition in &task.preq {
+            walker.expr(&condition.left);
+            walker.expr(&condition.right);
+          }
+          for statement in &task.exec {
+            walker.statement(statement);
+          }
+        }
+      }
+    }
+
+    references.sort_by_key(|reference| reference.span.start);
+    references
+  }
+
+  pub fn reference_at(&self, position: Position) -> Option<Reference<'_>> {
+    self
+      .references()
+      .into_iter()
+      .find(|reference| reference.span.contains(position))
+  }
+
+  pub fn definition(&self, position: Position, definitions: &Definitions) -> Option<Definition> {
+    let local = |span: Span| Some(Definition { file: None, span });
+
+    match self.reference_at(position)?.symbol {
+      Symbol::Group(group) => local(group.name.span),
+      Symbol::Task(_, task) | Symbol::Status(task) => local(task.name.span),
+      Symbol::Variable(_, variable) => local(variable.name.span),
+      Symbol::Instrument(instrument) => {
+        let definition = definitions.instrument(&builtins::instrument_name(instrument)?)?;
+
+        Some(Definition {
+          file: Some(definition.file.clone()),
+          span: definition.name.span,
+        })
+      },
+      Symbol::Property(instrument, mnemonic) => {
+        let name = builtins::instrument_name(instrument)?;
+        let definition = definitions.instrument(&name)?;
+        let property = definitions.property(&name, &property_name(mnemonic))?;
+
+        Some(Definition {
+          file: Some(definition.file.clone()),
+          span: property.name.span,
+        })
+      },
+      Symbol::Action(..) | Symbol::Function(_) => None,
+    }
+  }
+
+  /// Markdown describing the symbol at `position`.
+  pub fn hover(&self, position: Position, definitions: &Definitions) -> Option<(Span, String)> {
+    let reference = self.reference_at(position)?;
+    let text = match reference.symbol {
+      Symbol::Group(group) => {
+        let id = self.group_id(group);
+
+        code(&format!("group {}", group.name.text), &format!("Group {}, {} task(s).", id, group.tasks.len()))
+      },
+      Symbol::Task(group, task) => {
+        let variables: Vec<String> = task
+          .data
+          .iter()
+          .map(|variable| format!("`{}: {}`", variable.name.text, variable.ty))
+          .collect();
+        let data = if variables.is_empty() {
+          "No data.".to_string()
+        }
+        else {
+          format!("Data: {}.", variables.join(", "))
+        };
+        let id = group.tasks.iter().position(|other| std::ptr::eq(other, task)).unwrap_or(0) + 1;
+
+        code(
+          &format!("task {}", task.name.text),
+          &format!("Task {}.{} of group `{}`, runs {}. {}", self.group_id(group), id, group.name.text, task.freq, data),
+        )
+      },
+      Symbol::Variable(task, variable) => {
+        let keyword = if variable.constant { "const" } else { "var" };
+        let value = match variable.value {
+          Literal::Int(value) => value.to_string(),
+          Literal::Float(value) => format!("{:?}", value),
+        };
+
+        code(
+          &format!("{} {}: {} = {}", keyword, variable.name.text, variable.ty, value),
+          &format!("Data of task `{}`.", task.name.text),
+        )
+      },
+      Symbol::Status(task) => code(
+        &format!("{}.status: i32", task.name.text),
+        "The status of the task: 0 before its first run, 255 after a run that succeeded, 1 when a prerequisite failed and 2 after a fault. Only `==` and `<>` compare it.",
+      ),
+      Symbol::Instrument(instrument) => {
+        let name = builtins::instrument_name(instrument)?;
+        let doc = definitions.instrument(&name).map_or("", |definition| definition.doc.as_str());
+
+        code(&format!("inst {}", name), &join(&format!("`{}`", name_of(INSTRUMENTS, "INST_", instrument)?), doc))
+      },
+      Symbol::Property(instrument, mnemonic) => {
+        let name = builtins::instrument_name(instrument)?;
+        let property = property_name(mnemonic);
+        let doc = definitions
+          .property(&name, &property)
+          .map_or("", |definition| definition.doc.as_str());
+
+        code(
+          &format!("{}.{}: {}", name, property, builtins::property_type(mnemonic)),
+          &join(&format!("`{}`", mnemonic), doc),
+        )
+      },
+      Symbol::Action(instrument, mnemonic) => {
+        let name = builtins::instrument_name(instrument)?;
+
+        code(&format!("{}.{}()", name, property_name(mnemonic)), &format!("`{}`", mnemonic))
+      },
+      Symbol::Function(function) => code(&signature(function), function.doc),
+    };
+
+    Some((reference.span, text))
+  }
+
+  /// What can be written at `position`: the members of an instrument or task
+  /// after `NAME.`, and otherwise the names in scope, functions and keywords.
+  pub fn completions(&self, position: Position, definitions: &Definitions) -> Vec<Completion> {
+    let line = self.source.lines().nth(position.line).unwrap_or("");
+    let before = &line[..position.column.min(line.len())];
+    let partial = before.trim_end_matches(|ch: char| ch.is_alphanumeric() || ch == '_');
+    let scope = self.scope(position);
+
+    if let Some(prefix) = partial.strip_suffix('.') {
+      let start = prefix
+        .rfind(|ch: char| !ch.is_alphanumeric() && ch != '_')
+        .map_or(0, |index| index + 1);
+
+      return self.members(&prefix[start..], scope, definitions);
+    }
+
+    let mut completions = Vec::new();
+
+    if let Some((group, task)) = scope {
+      for variable in &task.data {
+        completions.push(variable_completion(variable));
+      }
+      for sibling in group.tasks.iter().filter(|sibling| !std::ptr::eq(*sibling, task)) {
+        completions.push(Completion {
+          label: sibling.name.text.clone(),
+          kind: CompletionKind::Task,
+          detail: format!("task of group {}", group.name.text),
+          doc: String::new(),
+        });
+      }
+    }
+
+    for (mnemonic, id) in INSTRUMENTS {
+      let name = mnemonic["INST_".len()..].to_ascii_lowercase();
+      let doc = definitions.instrument(&name).map_or(String::new(), |definition| definition.doc.clone());
+
+      completions.push(Completion {
+        label: name,
+        kind: CompletionKind::Instrument,
+        detail: name_of(INSTRUMENTS, "INST_", *id).unwrap_or_default().to_string(),
+        doc,
+      });
+    }
+
+    for function in FUNCTIONS {
+      completions.push(Completion {
+        label: function.name.to_string(),
+        kind: CompletionKind::Function,
+        detail: signature(function),
+        doc: function.doc.to_string(),
+      });
+    }
+
+    for keyword in KEYWORDS.iter().chain(&["once", "always"]) {
+      completions.push(Completion {
+        label: keyword.to_string(),
+        kind: CompletionKind::Keyword,
+        detail: String::new(),
+        doc: String::new(),
+      });
+    }
+
+    completions
+  }
+
+  fn members(&self, name: &str, scope: Option<(&Group, &Task)>, definitions: &Definitions) -> Vec<Completion> {
+    let mut completions = Vec::new();
+
+    if let Some(sibling) = scope.and_then(|(group, _)| group.tasks.iter().find(|task| task.name.text == name)) {
+      completions.extend(sibling.data.iter().map(variable_completion));
+      completions.push(Completion {
+        label: "status".to_string(),
+        kind: CompletionKind::Property,
+        detail: "i32".to_string(),
+        doc: "The status of the task, 255 after a run that succeeded.".to_string(),
+      });
+
+      return completions;
+    }
+
+    if let Some(instrument) = builtins::instrument(name) {
+      for property in builtins::properties(instrument) {
+        let (_, mnemonic) = builtins::property(instrument, &property).expect("listed properties exist");
+        let doc = definitions
+          .property(name, &property)
+          .map_or(String::new(), |definition| definition.doc.clone());
+
+        completions.push(Completion {
+          label: property,
+          kind: CompletionKind::Property,
+          detail: format!("{}: {}", mnemonic, builtins::property_type(mnemonic)),
+          doc,
+        });
+      }
+
+      for action in builtins::actions(instrument) {
+        let (_, mnemonic) = builtins::action(instrument, &action).expect("listed actions exist");
+
+        completions.push(Completion {
+          label: action,
+          kind: CompletionKind::Action,
+          detail: mnemonic.to_string(),
+          doc: String::new(),
+        });
+      }
+    }
+
+    completions
+  }
+
+  /// The group and task the position is in.
+  pub fn scope(&self, position: Position) -> Option<(&Group, &Task)> {
+    let program = self.program.as_ref()?;
+
+    program.groups.iter().find_map(|group| {
+      group
+        .tasks
+        .iter()
+        .find(|task| task.span.contains(position))
+        .map(|task| (group, task))
+    })
+  }
+
+  fn group_id(&self, group: &Group) -> usize {
+    self
+      .program
+      .as_ref()
+      .and_then(|program| program.groups.iter().position(|other| std::ptr::eq(other, group)))
+      .unwrap_or(0)
+      + 1
+  }
+}
+
+struct Walker<'a, 'r> {
+  group: &'a Group,
+  task: &'a Task,
+  references: &'r mut Vec<Reference<'a>>,
+}
+
+impl<'a, 'r> Walker<'a, 'r> {
+  fn push(&mut self, span: Span, symbol: Symbol<'a>) {
+    self.references.push(Reference { span, symbol });
+  }
+
+  fn variable(&mut self, name: &Name) {
+    let task = self.task;
+
+    if let Some(variable) = task.data.iter().find(|variable| variable.name.text == name.text) {
+      self.push(name.span, Symbol::Variable(task, variable));
+    }
+  }
+
+  fn member(&mut self, left: &Name, right: &Name, action: bool) {
+    let group = self.group;
+
+    if let Some(sibling) = group.tasks.iter().find(|task| task.name.text == left.text) {
+      self.push(left.span, Symbol::Task(group, sibling));
+
+      match sibling.data.iter().find(|variable| variable.name.text == right.text) {
+        Some(variable) => self.push(right.span, Symbol::Variable(sibling, variable)),
+        None if right.text == "status" => self.push(right.span, Symbol::Status(sibling)),
+        None => {},
+      }
+    }
+    else if let Some(instrument) = builtins::instrument(&left.text) {
+      self.push(left.span, Symbol::Instrument(instrument));
+
+      let member = if action {
+        builtins::action(instrument, &right.text).map(|(_, mnemonic)| Symbol::Action(instrument, mnemonic))
+      }
+      else {
+        builtins::property(instrument, &right.text).map(|(_, mnemonic)| Symbol::Property(instrument, mnemonic))
+      };
+      if let Some(member) = member {
+        self.push(right.span, member);
+      }
+    }
+  }
+
+  fn expr(&mut self, expr: &Expr) {
+    match expr {
+      Expr::Literal(..) => {},
+      Expr::Name(name) => self.variable(name),
+      Expr::Member(left, right) => self.member(left, right, false),
+      Expr::Negate(operand, _) => self.expr(operand),
+      Expr::Binary(_, left, right) => {
+        self.expr(left);
+        self.expr(right);
+      },
+      Expr::Call(name, arguments, _) => {
+        if let Some(function) = builtins::function(&name.text) {
+          self.push(name.span, Symbol::Function(function));
+        }
+        for argument in arguments {
+          self.expr(argument);
+        }
+      },
+    }
+  }
+
+  fn statement(&mut self, statement: &Statement) {
+    match statement {
+      Statement::Assign { target, value, .. } => {
+        match target {
+          Place::Variable(name) => self.variable(name),
+          Place::Property(left, right) => self.member(left, right, false),
+        }
+        self.expr(value);
+      },
+      Statement::Action {
+        instrument,
+        action,
+        argument,
+        ..
+      } => {
+        self.member(instrument, action, true);
+        if let Some(argument) = argument {
+          self.expr(argument);
+        }
+      },
+      Statement::Return { values, .. } => {
+        for value in values {
+          self.expr(value);
+        }
+      },
+    }
+  }
+}
+
+/// `latt` for `P_GPS_LATT`, `do_jpg` for `A_IMG_DO_JPG`.
+fn property_name(mnemonic: &str) -> String {
+  mnemonic.splitn(3, '_').nth(2).unwrap_or(mnemonic).to_ascii_lowercase()
+}
+
+fn signature(function: &Function) -> String {
+  let parameters = ["x", "y"][..function.arity].join(", ");
+  let result = if function.name == "i32" { "i32" } else { "f32" };
+
+  format!("{}({}) -> {}", function.name, parameters, result)
+}
+
+fn variable_completion(variable: &Variable) -> Completion {
+  Completion {
+    label: variable.name.text.clone(),
+    kind: if variable.constant {
+      CompletionKind::Constant
+    }
+    else {
+      CompletionKind::Variable
+    },
+    detail: variable.ty.to_string(),
+    doc: String::new(),
+  }
+}
+
+fn code(signature: &str, text: &str) -> String {
+  format!("```splice\n{}\n```\n{}", signature, text)
+}
+
+fn join(first: &str, second: &str) -> String {
+  if second.is_empty() {
+    first.to_string()
+  }
+  else {
+    format!("{}\n\n{}", first, second)
+  }
+}
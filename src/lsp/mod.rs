@@ -0,0 +1,8 @@
+//! A language server for task list files: diagnostics, go-to-definition,
+//! hover, completion and document symbols over stdin and stdout.
+
+pub mod analysis;
+pub mod rpc;
+pub mod server;
+
+pub use server::Server;
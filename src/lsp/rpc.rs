@@ -0,0 +1,67 @@
+//! JSON-RPC messages framed by a `Content-Length` header, as the language
+//! server protocol sends them over stdin and stdout.
+
+use std::io::{self, BufRead, Write};
+
+use crate::json::Json;
+
+/// Reads the next message; `None` at the end of the input.
+pub fn read(input: &mut impl BufRead) -> io::Result<Option<Json>> {
+  let mut length = None;
+
+  loop {
+    let mut line = String::new();
+    if input.read_line(&mut line)? == 0 {
+      return Ok(None);
+    }
+
+    let line = line.trim_end();
+    if line.is_empty() {
+      break;
+    }
+
+    if let Some((name, value)) = line.split_once(':') {
+      if name.eq_ignore_ascii_case("Content-Length") {
+        length = value.trim().parse::<usize>().ok();
+      }
+    }
+  }
+
+  let length = length.ok_or_else(|| invalid("a message without a Content-Length header"))?;
+  let mut body = vec![0; length];
+  input.read_exact(&mut body)?;
+
+  let text = String::from_utf8(body).map_err(|_| invalid("a message that is not UTF-8"))?;
+  Json::parse(&text)
+    .map(Some)
+    .map_err(|err| invalid(&format!("a message that is not JSON: {}", err)))
+}
+
+pub fn write(output: &mut impl Write, message: &Json) -> io::Result<()> {
+  let body = message.to_string();
+
+  write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+  output.flush()
+}
+
+pub fn response(id: Json, result: Json) -> Json {
+  Json::object(vec![("jsonrpc", Json::from("2.0")), ("id", id), ("result", result)])
+}
+
+pub fn error(id: Json, code: i32, message: &str) -> Json {
+  let error = Json::object(vec![("code", Json::from(code as f64)), ("message", Json::from(message))]);
+
+  Json::object(vec![("jsonrpc", Json::from("2.0")), ("id", id), ("error", error)])
+}
+
+pub fn notification(method: &str, params: Json) -> Json {
+  Json::object(vec![
+    ("jsonrpc", Json::from("2.0")),
+    ("method", Json::from(method)),
+    ("params", params),
+  ])
+}
+
+fn invalid(message: &str) -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidData, format!("received {}", message))
+}
@@ -0,0 +1,512 @@
+//! The requests and notifications of the language server protocol that the
+//! server answers, over documents kept in memory as the editor sends them.
+
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+
+use crate::compiler::ast::{Position, Span};
+use crate::compiler::spli::{self, Definitions};
+use crate::json::Json;
+use crate::lexer::class::{classify, Dialect, TokenClass};
+use crate::lsp::analysis::{CompletionKind, Document};
+use crate::lsp::rpc;
+
+const METHOD_NOT_FOUND: i32 = -32601;
+const INVALID_REQUEST: i32 = -32600;
+const INVALID_PARAMS: i32 = -32602;
+
+pub struct Server {
+  /// Open documents, by URI.
+  documents: Vec<(String, Document)>,
+  shut_down: bool,
+}
+
+impl Default for Server {
+  fn default() -> Server {
+    Server::new()
+  }
+}
+
+impl Server {
+  pub fn new() -> Server {
+    Server {
+      documents: Vec::new(),
+      shut_down: false,
+    }
+  }
+
+  /// Serves messages until `exit` or the end of the input, returning the exit
+  /// code: 0 when the client asked for a shutdown first, 1 otherwise.
+  pub fn run(&mut self, input: &mut impl BufRead, output: &mut impl Write) -> io::Result<i32> {
+    while let Some(message) = rpc::read(input)? {
+      let method = message.get("method").and_then(Json::as_str).unwrap_or("");
+      let params = message.get("params").cloned().unwrap_or(Json::Null);
+
+      if method == "exit" {
+        break;
+      }
+
+      match message.get("id") {
+        Some(id) => {
+          let reply = match self.request(method, &params) {
+            Ok(result) => rpc::response(id.clone(), result),
+            Err((code, text)) => rpc::error(id.clone(), code, &text),
+          };
+
+          rpc::write(output, &reply)?;
+        },
+        None => {
+          for notification in self.notify(method, &params) {
+            rpc::write(output, &notification)?;
+          }
+        },
+      }
+    }
+
+    Ok(if self.shut_down { 0 } else { 1 })
+  }
+
+  fn request(&mut self, method: &str, params: &Json) -> Result<Json, (i32, String)> {
+    if self.shut_down {
+      return Err((INVALID_REQUEST, "the server is shut down".to_string()));
+    }
+
+    match method {
+      "initialize" => Ok(capabilities()),
+      "shutdown" => {
+        self.shut_down = true;
+        Ok(Json::Null)
+      },
+      "textDocument/definition" => self.at(params, |document, position, definitions| {
+        document
+          .definition(position, definitions)
+          .and_then(|definition| match definition.file {
+            None => Some(location(&uri(params), &document.source, definition.span)),
+            Some(file) => {
+              let source = fs::read_to_string(&file).ok()?;
+
+              Some(location(&path_to_uri(&file), &source, definition.span))
+            },
+          })
+          .unwrap_or(Json::Null)
+      }),
+      "textDocument/hover" => self.at(params, |document, position, definitions| {
+        match document.hover(position, definitions) {
+          Some((span, text)) => Json::object(vec![
+            ("contents", markdown(text)),
+            ("range", range(&document.source, span)),
+          ]),
+          None => Json::Null,
+        }
+      }),
+      "textDocument/completion" => self.at(params, |document, position, definitions| {
+        let items = document
+          .completions(position, definitions)
+          .into_iter()
+          .map(|completion| {
+            let mut item = vec![
+              ("label", Json::from(completion.label)),
+              ("kind", Json::from(completion_kind(completion.kind))),
+              ("detail", Json::from(completion.detail)),
+            ];
+            if !completion.doc.is_empty() {
+              item.push(("documentation", markdown(completion.doc)));
+            }
+
+            Json::object(item)
+          })
+          .collect();
+
+        Json::Array(items)
+      }),
+      "textDocument/documentSymbol" => {
+        let document = self.document(params)?;
+
+        Ok(document_symbols(document))
+      },
+      "textDocument/semanticTokens/full" => {
+        let document = self.document(params)?;
+
+        Ok(semantic_tokens(document))
+      },
+      _ => Err((METHOD_NOT_FOUND, format!("unknown method `{}`", method))),
+    }
+  }
+
+  /// Handles a notification, returning the notifications to send back.
+  fn notify(&mut self, method: &str, params: &Json) -> Vec<Json> {
+    let uri = uri(params);
+
+    match method {
+      "textDocument/didOpen" => {
+        let text = params
+          .get("textDocument")
+          .and_then(|document| document.get("text"))
+          .and_then(Json::as_str)
+          .unwrap_or("");
+
+        self.documents.retain(|(other, _)| *other != uri);
+        self.documents.push((uri.clone(), Document::new(&uri_to_path(&uri), text)));
+      },
+      "textDocument/didChange" => {
+        // the server asks for full text synchronization, the last change
+        // holds the whole document
+        let text = params
+          .get("contentChanges")
+          .and_then(Json::as_array)
+          .and_then(|changes| changes.last())
+          .and_then(|change| change.get("text"))
+          .and_then(Json::as_str);
+
+        match (text, self.documents.iter_mut().find(|(other, _)| *other == uri)) {
+          (Some(text), Some((_, document))) => document.update(text),
+          _ => return Vec::new(),
+        }
+      },
+      "textDocument/didClose" => {
+        self.documents.retain(|(other, _)| *other != uri);
+
+        // clears the diagnostics of the closed file
+        return vec![diagnostics(&uri, None)];
+      },
+      _ => return Vec::new(),
+    }
+
+    let document = self.documents.iter().find(|(other, _)| *other == uri).map(|(_, document)| document);
+    vec![diagnostics(&uri, document)]
+  }
+
+  fn document(&self, params: &Json) -> Result<&Document, (i32, String)> {
+    let uri = uri(params);
+
+    self
+      .documents
+      .iter()
+      .find(|(other, _)| *other == uri)
+      .map(|(_, document)| document)
+      .ok_or_else(|| (INVALID_PARAMS, format!("`{}` is not open", uri)))
+  }
+
+  /// Answers a request about a position of a document, with the instrument
+  /// definitions of its directory.
+  fn at(
+    &self,
+    params: &Json,
+    answer: impl FnOnce(&Document, Position, &Definitions) -> Json,
+  ) -> Result<Json, (i32, String)> {
+    let document = self.document(params)?;
+    let position = params
+      .get("position")
+      .and_then(|position| {
+        let line = position.get("line")?.as_f64()? as usize;
+        let character = position.get("character")?.as_f64()? as usize;
+
+        Some(from_utf16(&document.source, line, character))
+      })
+      .ok_or_else(|| (INVALID_PARAMS, "missing position".to_string()))?;
+
+    Ok(answer(document, position, &definitions(&document.file)))
+  }
+}
+
+/// The instrument definitions of every `.spli` file next to `file`; files
+/// that do not parse are skipped.
+pub fn definitions(file: &str) -> Definitions {
+  let mut definitions = Definitions::default();
+
+  for path in spli::files(Path::new(file)) {
+    let name = path.display().to_string();
+
+    if let Ok(Ok(parsed)) = fs::read_to_string(&path).map(|source| Definitions::parse(&name, &source)) {
+      definitions.extend(parsed);
+    }
+  }
+
+  definitions
+}
+
+fn capabilities() -> Json {
+  Json::object(vec![
+    (
+      "capabilities",
+      Json::object(vec![
+        ("textDocumentSync", Json::from(1.0)),
+        ("definitionProvider", Json::from(true)),
+        ("hoverProvider", Json::from(true)),
+        (
+          "completionProvider",
+          Json::object(vec![("triggerCharacters", Json::Array(vec![Json::from(".")]))]),
+        ),
+        ("documentSymbolProvider", Json::from(true)),
+        (
+          "semanticTokensProvider",
+          Json::object(vec![
+            (
+              "legend",
+              Json::object(vec![
+                (
+                  "tokenTypes",
+                  Json::Array(SEMANTIC_TOKEN_TYPES.iter().map(|name| Json::from(*name)).collect()),
+                ),
+                ("tokenModifiers", Json::Array(Vec::new())),
+              ]),
+            ),
+            ("full", Json::from(true)),
+          ]),
+        ),
+      ]),
+    ),
+    (
+      "serverInfo",
+      Json::object(vec![
+        ("name", Json::from("splice")),
+        ("version", Json::from(env!("CARGO_PKG_VERSION"))),
+      ]),
+    ),
+  ])
+}
+
+fn diagnostics(uri: &str, document: Option<&Document>) -> Json {
+  let items = document
+    .map(|document| {
+      document
+        .errors
+        .iter()
+        .map(|error| {
+          Json::object(vec![
+            ("range", range(&document.source, error.span)),
+            ("severity", Json::from(1.0)),
+            ("source", Json::from("splice")),
+            ("message", Json::from(error.message.as_str())),
+          ])
+        })
+        .collect()
+    })
+    .unwrap_or_default();
+
+  rpc::notification(
+    "textDocument/publishDiagnostics",
+    Json::object(vec![("uri", Json::from(uri)), ("diagnostics", Json::Array(items))]),
+  )
+}
+
+fn document_symbols(document: &Document) -> Json {
+  // symbol kinds of the protocol
+  const NAMESPACE: f64 = 3.0;
+  const FUNCTION: f64 = 12.0;
+  const VARIABLE: f64 = 13.0;
+  const CONSTANT: f64 = 14.0;
+
+  let source = &document.source;
+  let symbol = |name: &str, kind: f64, span: Span, selection: Span, children: Vec<Json>| {
+    Json::object(vec![
+      ("name", Json::from(name)),
+      ("kind", Json::from(kind)),
+      ("range", range(source, span)),
+      ("selectionRange", range(source, selection)),
+      ("children", Json::Array(children)),
+    ])
+  };
+  let groups = document.program.iter().flat_map(|program| &program.groups).map(|group| {
+    let tasks = group
+      .tasks
+      .iter()
+      .map(|task| {
+        let variables = task
+          .data
+          .iter()
+          .map(|variable| {
+            let kind = if variable.constant { CONSTANT } else { VARIABLE };
+
+            symbol(&variable.name.text, kind, variable.span, variable.name.span, Vec::new())
+          })
+          .collect();
+
+        symbol(&task.name.text, FUNCTION, task.span, task.name.span, variables)
+      })
+      .collect();
+
+    symbol(&group.name.text, NAMESPACE, group.span, group.name.span, tasks)
+  });
+
+  Json::Array(groups.collect())
+}
+
+/// The token types of the protocol the classes of the lexer map to, in the
+/// order of `semantic_type`.
+const SEMANTIC_TOKEN_TYPES: &[&str] = &[
+  "keyword",
+  "type",
+  "number",
+  "operator",
+  "namespace",
+  "property",
+  "function",
+  "enumMember",
+  "variable",
+  "comment",
+];
+
+/// The index of a class in `SEMANTIC_TOKEN_TYPES`; punctuation is left to
+/// the grammar of the editor.
+fn semantic_type(class: TokenClass) -> Option<usize> {
+  match class {
+    TokenClass::Keyword => Some(0),
+    TokenClass::Type => Some(1),
+    TokenClass::Literal => Some(2),
+    TokenClass::Operator => Some(3),
+    TokenClass::Instrument => Some(4),
+    TokenClass::Property => Some(5),
+    TokenClass::Function => Some(6),
+    TokenClass::Register => Some(7),
+    TokenClass::Identifier => Some(8),
+    TokenClass::Comment => Some(9),
+    TokenClass::Punctuation => None,
+  }
+}
+
+/// The classified lexemes, each as its line and start relative to the
+/// previous one, its length, its type and no modifiers.
+fn semantic_tokens(document: &Document) -> Json {
+  let dialect = Dialect::of(&document.file).unwrap_or(Dialect::Tasks);
+  let lines: Vec<&str> = document.source.lines().collect();
+  let mut data = Vec::new();
+  let (mut previous_line, mut previous_start) = (0, 0);
+
+  for token in classify(&document.source, dialect) {
+    let kind = match semantic_type(token.class) {
+      Some(kind) => kind,
+      None => continue,
+    };
+    let (line, column) = token.lexeme.location;
+    let text = lines.get(line).copied().unwrap_or("");
+    let start = utf16_length(text, column);
+    let length = token.lexeme.code_span.chars().map(char::len_utf16).sum::<usize>();
+
+    if line != previous_line {
+      previous_start = 0;
+    }
+    for value in [line - previous_line, start - previous_start, length, kind, 0] {
+      data.push(Json::from(value as f64));
+    }
+    previous_line = line;
+    previous_start = start;
+  }
+
+  Json::object(vec![("data", Json::Array(data))])
+}
+
+fn completion_kind(kind: CompletionKind) -> f64 {
+  match kind {
+    CompletionKind::Action => 2.0,
+    CompletionKind::Function => 3.0,
+    CompletionKind::Variable => 6.0,
+    CompletionKind::Task => 7.0,
+    CompletionKind::Instrument => 9.0,
+    CompletionKind::Property => 10.0,
+    CompletionKind::Keyword => 14.0,
+    CompletionKind::Constant => 21.0,
+  }
+}
+
+fn markdown(text: String) -> Json {
+  Json::object(vec![("kind", Json::from("markdown")), ("value", Json::from(text))])
+}
+
+fn uri(params: &Json) -> String {
+  params
+    .get("textDocument")
+    .and_then(|document| document.get("uri"))
+    .and_then(Json::as_str)
+    .unwrap_or("")
+    .to_string()
+}
+
+fn location(uri: &str, source: &str, span: Span) -> Json {
+  Json::object(vec![("uri", Json::from(uri)), ("range", range(source, span))])
+}
+
+fn range(source: &str, span: Span) -> Json {
+  Json::object(vec![
+    ("start", to_utf16(source, span.start)),
+    ("end", to_utf16(source, span.end)),
+  ])
+}
+
+/// The protocol counts columns in UTF-16 code units, spans in bytes.
+fn to_utf16(source: &str, position: Position) -> Json {
+  let line = source.lines().nth(position.line).unwrap_or("");
+
+  Json::object(vec![
+    ("line", Json::from(position.line as f64)),
+    ("character", Json::from(utf16_length(line, position.column) as f64)),
+  ])
+}
+
+/// The UTF-16 length of the first `bytes` bytes of `text`.
+fn utf16_length(text: &str, bytes: usize) -> usize {
+  text
+    .char_indices()
+    .take_while(|(index, _)| *index < bytes)
+    .map(|(_, ch)| ch.len_utf16())
+    .sum()
+}
+
+fn from_utf16(source: &str, line: usize, character: usize) -> Position {
+  let text = source.lines().nth(line).unwrap_or("");
+  let mut units = 0;
+  let mut column = text.len();
+
+  for (index, ch) in text.char_indices() {
+    if units >= character {
+      column = index;
+      break;
+    }
+    units += ch.len_utf16();
+  }
+
+  Position { line, column }
+}
+
+/// `/home/me/orbit.spl` for `file:///home/me/orbit.spl`.
+pub fn uri_to_path(uri: &str) -> String {
+  let path = uri.strip_prefix("file://").unwrap_or(uri);
+  let bytes = path.as_bytes();
+  let mut decoded = Vec::with_capacity(bytes.len());
+  let mut index = 0;
+
+  while index < bytes.len() {
+    let escaped = path
+      .get(index + 1..index + 3)
+      .filter(|_| bytes[index] == b'%')
+      .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+
+    match escaped {
+      Some(byte) => {
+        decoded.push(byte);
+        index += 3;
+      },
+      None => {
+        decoded.push(bytes[index]);
+        index += 1;
+      },
+    }
+  }
+
+  String::from_utf8_lossy(&decoded).into_owned()
+}
+
+pub fn path_to_uri(path: &str) -> String {
+  let absolute = fs::canonicalize(path).map_or(path.to_string(), |path| path.display().to_string());
+  let mut uri = String::from("file://");
+
+  for byte in absolute.bytes() {
+    match byte {
+      b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b'-' | b'_' | b'.' | b'~' => uri.push(byte as char),
+      _ => uri.push_str(&format!("%{:02X}", byte)),
+    }
+  }
+
+  uri
+}
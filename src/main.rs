@@ -1,29 +1,974 @@
-mod lexer;
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::process;
 
-use lexer::{LexemeKind, Lexer};
+use splice::asm::disasm::disassemble_for;
+use splice::asm::listing::listing;
+use splice::asm;
+use splice::compiler;
+use splice::compiler::cache::{self, Cache, Compiled};
+use splice::compiler::ast::{Literal, Variable};
+use splice::compiler::repl::{Outcome, Session};
+use splice::compiler::spli::{self, Definitions};
+use splice::debug::{self, DebugInfo};
+use splice::lexer::class::{Dialect, DIALECTS};
+use splice::lexer::textmate;
+use splice::lexer::{LexemeKind, Lexer};
+use splice::link::graph::Graph;
+use splice::link::Linker;
+use splice::manifest::{self, Manifest, OutputFormat};
+use splice::lsp::Server;
+use splice::splx;
+use splice::target::Target;
+use splice::vm::cost::{CostTable, Report};
+use splice::vm::machine::OutputValue;
+use splice::vm::{loader, Machine, Scheduler, Status};
+
+const USAGE: &str = "Usage: splice <command> [options] <file>...
+
+Commands:
+  check <file.spl|file.splc>...          compiles or assembles the files, reporting every error
+  compile <file.spl> [-o <dir>] [--explain]
+                                         writes the assembly of every task, GROUP_TASK.splc
+  asm <file.splc> [-o <file.splx>] [--listing [<file.lst>]]
+                                         assembles a single task
+  link -o <file.splx> [-m <file.map>] [--explain] <file.spl|file.splc>...
+                                         links tasks into a single executable
+  build [--explain] [Splice.toml]        builds the mission described by a manifest
+  disasm <file.splx> [-o <dir>]          turns the tasks of an executable back into assembly
+  graph [--dot | --json] [-o <file>] <file.spl|file.splc|file.splx>... | <Splice.toml>
+                                         shows which tasks depend on which others
+  run [-t <seconds>] <file.spl|file.splc|file.splx>...
+                                         runs the tasks on the reference VM
+  fmt [--check] <file.spl|file.splc>...  formats source files in place
+  lex <file> [-o <file>]                 prints the lexemes of a source file
+  repl                                   evaluates expressions and statements line by line
+  lsp                                    runs the language server over stdin and stdout
+  grammar [-o <dir>] [spl|spli|splc]...  writes TextMate grammars for editors
+
+check, compile, asm, link and disasm take `--target <name|file.toml>`, the
+target profile describing the ISA and executable format of the satellite,
+the built-in `opssat` by default.
+
+`splice <command> --help` describes a command. Every command exits with 0 on
+success, 1 when a file has errors and 2 on invalid usage.";
+
+const CHECK_USAGE: &str = "Usage: splice check [--target <name|file.toml>] <file.spl|file.splc>...
+
+Compiles every .spl file and assembles every .splc file for the target
+without writing anything, reporting every error. Exits with 1 if any file has
+errors.";
+
+const COMPILE_USAGE: &str = "Usage: splice compile [--target <name|file.toml>] <file.spl> [-o <dir>] [--explain]
+
+Compiles a task list file into the assembly of every task for the target, the
+registers, compare operators and instruments of its profile, written as
+GROUP_TASK.splc into the given directory, next to the source by default.
+Tasks are numbered from 1 in declaration order within their group, and
+groups in declaration order within the file. Every task also gets a
+GROUP_TASK.spld debug sidecar mapping its instructions to their .spl source
+and its data addresses to its variables.
+
+Compiled tasks are cached in a .splice-cache directory next to the output, and
+only the tasks whose source, position, target profile, instrument definitions
+(the .spli files next to the source) or the data declarations of the tasks
+they read changed are compiled again. --explain says why every task was compiled or that it was
+taken from the cache.";
+
+const ASM_USAGE: &str = "Usage: splice asm [--target <name|file.toml>] <file.splc> [-o <file.splx>] [--listing [<file.lst>]]
+
+Assembles a single task into the executable format of the target, written
+next to the source with the extension of the target, .splx for OPS-SAT,
+unless -o is given (`-o -` writes to stdout).
+With --listing, also writes a listing of every word with its encoding and
+source line, by default next to the source with a .lst extension. Tasks that
+refer to other tasks' data have to be linked instead. The .spld debug sidecar
+of a compiled task is carried over next to the output, keyed by word.";
+
+const LINK_USAGE: &str = "Usage: splice link [--target <name|file.toml>] -o <file.splx> [-m <file.map>] [--explain] <file.spl|file.splc>...
+
+Compiles and assembles every task for the target, checks that no (group, task) pair is
+defined twice and that all cross-task references resolve, then writes the
+tasks ordered by group and task id into a single .splx file. The link map is
+written next to the output file with a .map extension unless -m is given.
+The debug sidecars of compiled tasks are merged into a .spld file next to the
+output. The tasks of .spl files are compiled and assembled through the cache
+of `splice compile`, in a .splice-cache directory next to the output, and
+--explain says why each of them was compiled.";
+
+const BUILD_USAGE: &str = "Usage: splice build [--explain] [Splice.toml]
+
+Builds the mission described by a manifest, Splice.toml in the current
+directory by default: compiles the groups it selects from its .spl sources
+against its .spli files, assembles its .splc sources, links them and checks
+the cost of every group against its budgets with the cost table of its
+target profile, then writes the requested formats into the output directory. Paths are
+relative to the manifest, and the same manifest and sources always give the
+same files. Nothing is written when a budget is exceeded. Tasks are compiled
+through the cache of `splice compile`, in the output directory, and
+--explain says why each of them was compiled.";
+
+const DISASM_USAGE: &str = "Usage: splice disasm [--target <name|file.toml>] <file.splx> [-o <dir>]
+
+Turns every task of an executable of the target back into assembly, written as
+GROUP_TASK.splc into the given directory, or printed to stdout without -o.
+Data addresses are numeric; a data word is a float when the code loads it
+into or stores it from a float register, an integer otherwise. With a .spld
+debug sidecar next to the executable, files are named after the group and the
+task like those of compile, data words are named and typed after their
+variables and instructions are preceded by their .spl source line; without
+one, GROUP and TASK are the ids.";
+
+const GRAPH_USAGE: &str = "Usage: splice graph [--target <name|file.toml>] [--dot | --json] [-o <file>]
+                    <file.spl|file.splc|file.splx>... | <Splice.toml>
+
+Loads the tasks like `splice run`, or the sources of a manifest for its
+target, and lists, for every group, which tasks load the data of which others
+(OP_LEA of another TASK_ID) and which compare the status of others
+(TSX_EQ/TSX_NE). Warns about cycles among dependent
+tasks and about loads of data that no task stores to, which always read the
+initial value, unless it is declared const. With --dot, writes a Graphviz
+digraph instead, and with --json a JSON object; both to stdout unless -o is
+given, the warnings going to stderr.";
+
+const RUN_USAGE: &str = "Usage: splice run [-t <seconds>] <file.spl|file.splc|file.splx>...
+
+Loads the tasks of every .splx file, plus the .spl and .splc files compiled,
+assembled and linked together, and runs them on the scheduler for the given
+number of one second ticks (1 by default, which runs every task once). Every
+run is logged with its status and OP_STR output. Exits with 1 if a task
+faults. splvm runs tasks with simulated orbits, telemetry and traces.";
+
+const FMT_USAGE: &str = "Usage: splice fmt [--check] <file.spl|file.splc>...
+
+Formats source files in place. Task list files get their blocks indented by
+two spaces and one statement per line, assembly files fields separated by
+`, `; comments and single blank lines are kept. Files that do not parse are
+reported and left alone. With --check, nothing is written: the files that
+are not formatted are listed and the exit code is 1 if there are any.";
+
+const LEX_USAGE: &str = "Usage: splice lex <file> [-o <file>]
+
+Prints every lexeme of a source file with its line, column and kind, to
+stdout unless -o is given.";
+
+const REPL_USAGE: &str = "Usage: splice repl
+
+Evaluates declarations, statements, comparisons and expressions of the task
+language read from stdin, until the end of input or :quit. Every line is
+compiled into a task and run on the VM with the simulated instruments, so
+values have the precision a task would compute them with.";
+
+const REPL_HELP: &str = "  1.5 * sin(0.5)        evaluates an expression
+  var x = gps.altt      declares a variable, optionally with a type: `var n: i32 = 3`
+  x = x / 2             assigns a variable or an instrument property
+  img.do_jpg()          runs an action
+  x > 100.0             tells whether a comparison holds
+  :vars                 lists the variables
+  :asm                  shows the assembly of the last line
+  :tokens [line]        shows the lexemes of a line, the last one by default
+  :quit                 leaves the REPL";
+
+const LSP_USAGE: &str = "Usage: splice lsp
+
+Runs a language server for task list files, speaking the language server
+protocol over stdin and stdout: diagnostics as files change, go-to-definition,
+hover, completion and document symbols. Hover and completion describe the
+instrument properties with the documentation of the .spli files found next to
+the file being edited.";
+
+const GRAMMAR_USAGE: &str = "Usage: splice grammar [-o <dir>] [spl|spli|splc]...
+
+Generates the TextMate grammars of task list (spl), instrument definition
+(spli) and assembly (splc) files from the tables of the lexer, for editors
+to highlight them exactly as the toolchain reads them. With -o, writes
+EXT.tmLanguage.json into the directory for every language given, all of them
+by default; without, prints the grammar of a single language.";
+
+/// Exit code of a command that found errors in its input.
+const FAILURE: i32 = 1;
+/// Exit code of an invalid command line.
+const MISUSE: i32 = 2;
+
+fn fail(message: String) -> ! {
+  eprintln!("{}", message);
+  process::exit(FAILURE);
+}
+
+fn usage(text: &str) -> ! {
+  eprintln!("{}", text);
+  process::exit(MISUSE);
+}
+
+/// The target profile of `--target`, OPS-SAT by default.
+fn target(args: &Arguments) -> Target {
+  Target::load(args.value("--target").unwrap_or("opssat")).unwrap_or_else(|err| fail(err))
+}
+
+fn read(path: &str) -> String {
+  fs::read_to_string(path).unwrap_or_else(|err| fail(format!("{}: {}", path, err)))
+}
+
+fn write(path: &Path, text: &str) {
+  fs::write(path, text).unwrap_or_else(|err| fail(format!("{}: {}", path.display(), err)));
+}
+
+/// The arguments of a command: its options with their values, and its input
+/// files.
+struct Arguments {
+  options: Vec<(String, String)>,
+  inputs: Vec<String>,
+}
+
+impl Arguments {
+  /// Parses `args`, `with_value` being the options followed by a value;
+  /// `--help` prints `text` and exits.
+  fn parse(args: &[String], with_value: &[&str], text: &str) -> Arguments {
+    let mut parsed = Arguments {
+      options: Vec::new(),
+      inputs: Vec::new(),
+    };
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+      if arg == "-h" || arg == "--help" {
+        println!("{}", text);
+        process::exit(0);
+      }
+      else if with_value.contains(&arg.as_str()) {
+        let value = args.next().cloned().unwrap_or_else(|| usage(text));
+
+        parsed.options.push((arg.clone(), value));
+      }
+      else if arg.starts_with('-') && arg != "-" {
+        usage(text);
+      }
+      else {
+        parsed.inputs.push(arg.clone());
+      }
+    }
+
+    parsed
+  }
+
+  fn value(&self, option: &str) -> Option<&str> {
+    self
+      .options
+      .iter()
+      .rev()
+      .find(|(name, _)| name == option)
+      .map(|(_, value)| value.as_str())
+  }
+}
 
 fn main() {
-  println!("Hello, space!");
+  let args: Vec<String> = env::args().skip(1).collect();
+  let (command, args) = match args.split_first() {
+    Some((command, args)) => (command.as_str(), args),
+    None => ("repl", &args[..]),
+  };
 
-  let mut stdout = std::io::stdout();
-  let stdin = std::io::stdin();
+  match command {
+    "check" => check(args),
+    "compile" => compile(args),
+    "asm" => assemble(args),
+    "link" => link(args),
+    "build" => build(args),
+    "disasm" => disasm(args),
+    "graph" => graph(args),
+    "run" => run(args),
+    "fmt" => fmt(args),
+    "lex" => lex(args),
+    "repl" => repl(args),
+    "lsp" => lsp(args),
+    "grammar" => grammar(args),
+    "-h" | "--help" | "help" => println!("{}", USAGE),
+    _ => usage(USAGE),
+  }
+}
 
-  loop {
-    use std::io::Write;
+fn check(args: &[String]) {
+  let args = Arguments::parse(args, &["--target"], CHECK_USAGE);
+  let target = target(&args);
+  let mut errors = 0;
+
+  if args.inputs.is_empty() {
+    usage(CHECK_USAGE);
+  }
+
+  for input in &args.inputs {
+    let source = read(input);
+
+    if input.ends_with(".splc") {
+      if let Err(err) = asm::assemble_for(&target, Path::new(input), &source) {
+        eprintln!("error: {}", err);
+        errors += 1;
+      }
+    }
+    else if let Err(file_errors) = compiler::compile_for(&target, input, &source) {
+      for error in &file_errors {
+        eprintln!("error: {}", error);
+      }
+      errors += file_errors.len();
+    }
+  }
+
+  if errors > 0 {
+    fail(format!("found {} error(s)", errors));
+  }
+}
+
+/// `--explain` output: why a task was compiled, or that it came from the cache.
+fn explanation(compiled: &Compiled) -> String {
+  match &compiled.reason {
+    Some(reason) => format!("compiled: {}", reason),
+    None => "cached".to_string(),
+  }
+}
+
+fn compile(args: &[String]) {
+  let explain = args.iter().any(|arg| arg == "--explain");
+  let rest: Vec<String> = args.iter().filter(|arg| *arg != "--explain").cloned().collect();
+  let args = Arguments::parse(&rest, &["-o", "--target"], COMPILE_USAGE);
+  let target = target(&args);
+  let input = match args.inputs.as_slice() {
+    [input] => input,
+    _ => usage(COMPILE_USAGE),
+  };
+  let directory = match args.value("-o") {
+    Some(directory) => PathBuf::from(directory),
+    None => Path::new(input).parent().unwrap_or_else(|| Path::new("")).to_path_buf(),
+  };
+
+  let mut cache = Cache::open(&directory.join(cache::DIRECTORY));
+  let compiled = cache::compile(&target, input, &read(input), &spli::files(Path::new(input)), &directory, &mut cache)
+    .unwrap_or_else(|err| fail(err));
+
+  if !directory.as_os_str().is_empty() {
+    fs::create_dir_all(&directory).unwrap_or_else(|err| fail(format!("{}: {}", directory.display(), err)));
+  }
+
+  for entry in &compiled {
+    let unit = &entry.unit;
+    let path = directory.join(unit.file_name());
+
+    write(&path, &unit.assembly);
+    write(&directory.join(unit.debug_file_name()), &DebugInfo { tasks: vec![unit.debug.clone()] }.to_string());
+
+    if explain {
+      println!("{}.{} {} ({})", unit.group_id, unit.task_id, path.display(), explanation(entry));
+    }
+    else {
+      println!("{}.{} {}", unit.group_id, unit.task_id, path.display());
+    }
+  }
+
+  cache
+    .save()
+    .unwrap_or_else(|err| fail(format!("{}: {}", directory.join(cache::DIRECTORY).display(), err)));
+}
+
+fn assemble(args: &[String]) {
+  let mut with_listing = false;
+  let mut listing_path = None;
+  let mut rest = Vec::new();
+
+  // `--listing` takes an optional value
+  let mut iter = args.iter().peekable();
+  while let Some(arg) = iter.next() {
+    if arg == "--listing" {
+      with_listing = true;
+      if iter.peek().is_some_and(|next| next.ends_with(".lst")) {
+        listing_path = iter.next().cloned();
+      }
+    }
+    else {
+      rest.push(arg.clone());
+    }
+  }
+
+  let args = Arguments::parse(&rest, &["-o", "--target"], ASM_USAGE);
+  let target = target(&args);
+  let input = match args.inputs.as_slice() {
+    [input] => input,
+    _ => usage(ASM_USAGE),
+  };
+  let task = asm::assemble_file_for(&target, Path::new(input)).unwrap_or_else(|err| fail(format!("error: {}", err)));
+
+  if let Some(relocation) = task.relocations.first() {
+    fail(format!(
+      "error: {}: `t{}.{}` refers to another task's data, use `splice link` to resolve it",
+      relocation.location, relocation.task_id, relocation.label
+    ));
+  }
+
+  if with_listing {
+    let path = listing_path.unwrap_or_else(|| Path::new(input).with_extension("lst").display().to_string());
+
+    write(Path::new(&path), &listing(&task, input));
+  }
+
+  let text = format!("{}\n", splx::format_task_for(&target, &task.words()));
+  let output = match args.value("-o") {
+    Some("-") => {
+      print!("{}", text);
+      return;
+    },
+    Some(output) => PathBuf::from(output),
+    None => Path::new(input).with_extension(&target.container.extension),
+  };
+
+  write(&output, &text);
+  if let Some(debug) = task.debug {
+    write(&debug::sidecar(&output), &DebugInfo { tasks: vec![debug] }.to_string());
+  }
+}
+
+fn link(args: &[String]) {
+  let explain = args.iter().any(|arg| arg == "--explain");
+  let rest: Vec<String> = args.iter().filter(|arg| *arg != "--explain").cloned().collect();
+  let args = Arguments::parse(&rest, &["-o", "-m", "--target"], LINK_USAGE);
+  let target = target(&args);
+  let output = match args.value("-o") {
+    Some(output) if !args.inputs.is_empty() => output,
+    _ => usage(LINK_USAGE),
+  };
+  let map = args
+    .value("-m")
+    .map(PathBuf::from)
+    .unwrap_or_else(|| Path::new(output).with_extension("map"));
+
+  let cache_directory = Path::new(output)
+    .parent()
+    .unwrap_or_else(|| Path::new(""))
+    .join(cache::DIRECTORY);
+  let mut cache = Cache::open(&cache_directory);
+  let mut linker = Linker::for_target(&target);
+
+  for input in &args.inputs {
+    if input.ends_with(".spl") {
+      let directory = Path::new(input).parent().unwrap_or_else(|| Path::new(""));
+      let compiled = cache::compile(&target, input, &read(input), &spli::files(Path::new(input)), directory, &mut cache)
+        .unwrap_or_else(|err| fail(err));
+
+      for entry in compiled {
+        let path = directory.join(entry.unit.file_name()).display().to_string();
+
+        if explain {
+          let unit = &entry.unit;
+
+          println!("{}.{} {}.{} ({})", unit.group_id, unit.task_id, unit.group, unit.task, explanation(&entry));
+        }
+        linker.add(&path, entry.task);
+      }
+    }
+    else {
+      let task = asm::assemble_file_for(&target, Path::new(input)).unwrap_or_else(|err| fail(format!("error: {}", err)));
+
+      linker.add(input, task);
+    }
+  }
+
+  let image = linker.link().unwrap_or_else(|errors| {
+    for error in &errors {
+      eprintln!("error: {}", error);
+    }
+    fail(format!("linking failed with {} error(s)", errors.len()));
+  });
+
+  write(Path::new(output), &image.to_splx());
+  write(&map, &image.map());
+  if let Some(debug) = image.debug() {
+    write(&debug::sidecar(Path::new(output)), &debug.to_string());
+  }
+  cache
+    .save()
+    .unwrap_or_else(|err| fail(format!("{}: {}", cache_directory.display(), err)));
+}
+
+fn build(args: &[String]) {
+  let explain = args.iter().any(|arg| arg == "--explain");
+  let rest: Vec<String> = args.iter().filter(|arg| *arg != "--explain").cloned().collect();
+  let args = Arguments::parse(&rest, &[], BUILD_USAGE);
+  let path = match args.inputs.as_slice() {
+    [] => manifest::FILE_NAME,
+    [path] => path.as_str(),
+    _ => usage(BUILD_USAGE),
+  };
+  let manifest = Manifest::parse(&read(path)).unwrap_or_else(|err| fail(format!("{}: {}", path, err)));
+
+  // every path of the manifest is relative to it
+  match Path::new(path).parent() {
+    Some(directory) if !directory.as_os_str().is_empty() => env::set_current_dir(directory)
+      .unwrap_or_else(|err| fail(format!("{}: {}", directory.display(), err))),
+    _ => {},
+  }
+
+  let instruments: Vec<PathBuf> = manifest.instruments.iter().map(PathBuf::from).collect();
+  for path in &instruments {
+    let file = path.display().to_string();
+
+    if let Err(err) = Definitions::parse(&file, &read(&file)) {
+      fail(format!("error: {}", err));
+    }
+  }
+
+  let target = Target::load(&manifest.target).unwrap_or_else(|err| fail(format!("{}: {}", path, err)));
+  let output = Path::new(&manifest.output);
+
+  let mut cache = Cache::open(&output.join(cache::DIRECTORY));
+  let mut linker = Linker::for_target(&target);
+  let mut groups: Vec<(String, u8)> = Vec::new();
+  // written once everything is built and within budget
+  let mut outputs: Vec<(PathBuf, String)> = Vec::new();
 
-    let mut input = String::new();
+  for input in &manifest.sources {
+    if input.ends_with(".splc") {
+      let task = asm::assemble_file_for(&target, Path::new(input)).unwrap_or_else(|err| fail(format!("error: {}", err)));
 
-    stdout.write(b"> ").expect("Couldn't write to stdout");
+      if manifest.has(OutputFormat::Listing) {
+        let path = output.join(Path::new(input).with_extension("lst").file_name().unwrap_or_default());
+
+        outputs.push((path, listing(&task, input)));
+      }
+      linker.add(input, task);
+      continue;
+    }
+
+    let instruments = if instruments.is_empty() { spli::files(Path::new(input)) } else { instruments.clone() };
+    let compiled =
+      cache::compile(&target, input, &read(input), &instruments, output, &mut cache).unwrap_or_else(|err| fail(err));
+
+    for entry in compiled {
+      let unit = &entry.unit;
+
+      if !manifest.groups.is_empty() && !manifest.groups.contains(&unit.group) {
+        continue;
+      }
+      if !groups.iter().any(|(group, _)| *group == unit.group) {
+        groups.push((unit.group.clone(), unit.group_id));
+      }
+
+      let path = output.join(unit.file_name());
+      if explain {
+        // the .splc path only if it is written
+        let written = if manifest.has(OutputFormat::Assembly) { format!(" {}", path.display()) } else { String::new() };
+
+        println!(
+          "{}.{} {}.{}{} ({})",
+          unit.group_id,
+          unit.task_id,
+          unit.group,
+          unit.task,
+          written,
+          explanation(&entry)
+        );
+      }
+
+      if manifest.has(OutputFormat::Assembly) {
+        outputs.push((path.clone(), unit.assembly.clone()));
+        outputs.push((output.join(unit.debug_file_name()), DebugInfo { tasks: vec![unit.debug.clone()] }.to_string()));
+      }
+      if manifest.has(OutputFormat::Listing) {
+        let task = asm::assemble_for(&target, &path, &unit.assembly).unwrap_or_else(|err| fail(format!("error: {}", err)));
+
+        outputs.push((path.with_extension("lst"), listing(&task, &path.display().to_string())));
+      }
+
+      linker.add(&path.display().to_string(), entry.task);
+    }
+  }
+
+  if let Some(group) = manifest.groups.iter().find(|group| !groups.iter().any(|(name, _)| name == *group)) {
+    fail(format!("{}: no source declares the group `{}`", path, group));
+  }
+
+  let image = linker.link().unwrap_or_else(|errors| {
+    for error in &errors {
+      eprintln!("error: {}", error);
+    }
+    fail(format!("linking failed with {} error(s)", errors.len()));
+  });
+
+  let table = CostTable::builtin(&target.costs).expect("targets name built-in cost tables");
+  let budgets = manifest.budgets(&groups).unwrap_or_else(|err| fail(format!("{}: {}", path, err)));
+  let tasks: Vec<_> = image.units.iter().map(|unit| unit.task.clone()).collect();
+  let report = Report::new(&tasks, &table, &budgets);
+
+  if !report.violations.is_empty() {
+    for violation in &report.violations {
+      eprintln!("error: {}", violation);
+    }
+    fail(format!("{} budget(s) exceeded on target {}", report.violations.len(), target.name));
+  }
+
+  let executable = output.join(format!("{}.{}", manifest.name, target.container.extension));
+
+  if manifest.has(OutputFormat::Executable) {
+    outputs.push((executable.clone(), image.to_splx()));
+  }
+  if manifest.has(OutputFormat::Map) {
+    outputs.push((executable.with_extension("map"), image.map()));
+  }
+  if let (true, Some(debug)) = (manifest.has(OutputFormat::Debug), image.debug()) {
+    outputs.push((debug::sidecar(&executable), debug.to_string()));
+  }
+  if manifest.has(OutputFormat::Cost) {
+    outputs.push((executable.with_extension("cost"), report.to_string()));
+  }
+
+  fs::create_dir_all(output).unwrap_or_else(|err| fail(format!("{}: {}", output.display(), err)));
+  for (path, text) in &outputs {
+    write(path, text);
+    println!("{}", path.display());
+  }
+
+  cache
+    .save()
+    .unwrap_or_else(|err| fail(format!("{}: {}", output.join(cache::DIRECTORY).display(), err)));
+}
+
+fn disasm(args: &[String]) {
+  let args = Arguments::parse(args, &["-o", "--target"], DISASM_USAGE);
+  let target = target(&args);
+  let input = match args.inputs.as_slice() {
+    [input] => input,
+    _ => usage(DISASM_USAGE),
+  };
+  let mut tasks = splx::parse_for(&target, &read(input)).unwrap_or_else(|err| {
+    let hint = "pass --target <profile> if it was linked for another target";
+
+    fail(format!("{}: {}; {}", input, err.not_executable_of(&target), hint))
+  });
+
+  if let Some(info) = debug::read(Path::new(input)).unwrap_or_else(|err| fail(err)) {
+    info.attach(&mut tasks);
+  }
+
+  if let Some(directory) = args.value("-o") {
+    fs::create_dir_all(directory).unwrap_or_else(|err| fail(format!("{}: {}", directory, err)));
+  }
+
+  for task in &tasks {
+    let header = task.header;
+    let text = disassemble_for(&target, task).unwrap_or_else(|err| {
+      fail(format!("{}: task {}.{}: {}", input, header.group_id, header.task_id, err))
+    });
+
+    match args.value("-o") {
+      Some(directory) => {
+        // the `GROUP_TASK.splc` of `splice compile`, by id without debug information
+        let name = match &task.debug {
+          Some(debug) => format!("{}_{}.splc", debug.group, debug.task),
+          None => format!("{}_{}.splc", header.group_id, header.task_id),
+        };
+        let path = Path::new(directory).join(name);
+
+        write(&path, &text);
+        println!("{}.{} {}", header.group_id, header.task_id, path.display());
+      },
+      None => println!("; task {}.{}\n{}", header.group_id, header.task_id, text),
+    }
+  }
+}
+
+fn graph(args: &[String]) {
+  let dot = args.iter().any(|arg| arg == "--dot");
+  let json = args.iter().any(|arg| arg == "--json");
+  let rest: Vec<String> = args.iter().filter(|arg| *arg != "--dot" && *arg != "--json").cloned().collect();
+  let args = Arguments::parse(&rest, &["-o", "--target"], GRAPH_USAGE);
+
+  if args.inputs.is_empty() || (dot && json) {
+    usage(GRAPH_USAGE);
+  }
+
+  let (target, inputs) = match args.inputs.as_slice() {
+    [path] if path.ends_with(manifest::FILE_NAME) => {
+      let manifest = Manifest::parse(&read(path)).unwrap_or_else(|err| fail(format!("{}: {}", path, err)));
+
+      // every path of the manifest is relative to it
+      match Path::new(path).parent() {
+        Some(directory) if !directory.as_os_str().is_empty() => env::set_current_dir(directory)
+          .unwrap_or_else(|err| fail(format!("{}: {}", directory.display(), err))),
+        _ => {},
+      }
+
+      let target = match args.value("--target") {
+        Some(_) => target(&args),
+        None => Target::load(&manifest.target).unwrap_or_else(|err| fail(format!("{}: {}", path, err))),
+      };
+
+      (target, manifest.sources)
+    },
+    _ => (target(&args), args.inputs.clone()),
+  };
+  let tasks = loader::load_for(&target, &inputs).unwrap_or_else(|err| fail(err));
+  let graph = Graph::new(&tasks, &target);
+  let text = if dot {
+    graph.to_dot()
+  }
+  else if json {
+    format!("{}\n", graph.to_json().pretty())
+  }
+  else {
+    graph.to_string()
+  };
+
+  if dot || json {
+    for cycle in &graph.cycles {
+      eprintln!("warning: {}", cycle);
+    }
+    for read in &graph.unwritten {
+      eprintln!("warning: {}", read);
+    }
+    for task in &graph.missing {
+      eprintln!("warning: {}", task);
+    }
+  }
+
+  match args.value("-o") {
+    Some(output) => write(Path::new(output), &text),
+    None => print!("{}", text),
+  }
+}
+
+fn run(args: &[String]) {
+  let args = Arguments::parse(args, &["-t"], RUN_USAGE);
+  let duration = match args.value("-t").map(str::parse) {
+    None => 1,
+    Some(Ok(duration)) => duration,
+    Some(Err(_)) => usage(RUN_USAGE),
+  };
+
+  if args.inputs.is_empty() {
+    usage(RUN_USAGE);
+  }
+
+  let tasks = loader::load(&args.inputs).unwrap_or_else(|err| fail(err));
+  let machine = Machine::new(tasks).unwrap_or_else(|err| fail(err));
+  let mut scheduler = Scheduler::new(machine);
+
+  scheduler.run_for(duration);
+
+  for entry in &scheduler.log {
+    println!("{}", entry);
+
+    for output in &scheduler.machine.output[entry.output.clone()] {
+      println!("{:>6}s  {}.{}: {}", output.time, output.group_id, output.task_id, output.value);
+    }
+  }
+
+  if duration > 1 {
+    print!("\n{}", scheduler.summary());
+  }
+
+  if scheduler.log.iter().any(|entry| entry.status == Status::Fault) {
+    process::exit(FAILURE);
+  }
+}
+
+fn fmt(args: &[String]) {
+  let check = args.iter().any(|arg| arg == "--check");
+  let rest: Vec<String> = args.iter().filter(|arg| *arg != "--check").cloned().collect();
+  let args = Arguments::parse(&rest, &[], FMT_USAGE);
+  let mut errors = 0;
+  let mut unformatted = 0;
+
+  if args.inputs.is_empty() {
+    usage(FMT_USAGE);
+  }
+
+  for input in &args.inputs {
+    let source = read(input);
+    let formatted = if input.ends_with(".splc") {
+      asm::format::format(&source)
+    }
+    else {
+      match compiler::format::format(input, &source) {
+        Ok(formatted) => formatted,
+        Err(err) => {
+          eprintln!("error: {}", err);
+          errors += 1;
+          continue;
+        },
+      }
+    };
+
+    if formatted == source {
+      continue;
+    }
+
+    if check {
+      println!("{}", input);
+      unformatted += 1;
+    }
+    else {
+      write(Path::new(input), &formatted);
+    }
+  }
+
+  let mut problems = Vec::new();
+
+  if unformatted > 0 {
+    problems.push(format!("{} file(s) not formatted", unformatted));
+  }
+  if errors > 0 {
+    problems.push(format!("{} file(s) with errors", errors));
+  }
+
+  if !problems.is_empty() {
+    fail(problems.join(", "));
+  }
+}
+
+fn lex(args: &[String]) {
+  let args = Arguments::parse(args, &["-o"], LEX_USAGE);
+  let input = match args.inputs.as_slice() {
+    [input] => input,
+    _ => usage(LEX_USAGE),
+  };
+  let source = read(input);
+  let mut text = String::new();
+
+  for lexeme in Lexer::new(&source) {
+    let (line, column) = lexeme.location;
+
+    text.push_str(&format!("{}:{}  {:?}  {:?}\n", line + 1, column + 1, lexeme.kind, lexeme.code_span));
+    if lexeme.kind == LexemeKind::Eof {
+      break;
+    }
+  }
+
+  match args.value("-o") {
+    Some(output) => write(Path::new(output), &text),
+    None => print!("{}", text),
+  }
+}
+
+fn repl(args: &[String]) {
+  Arguments::parse(args, &[], REPL_USAGE);
+
+  let mut session = Session::new();
+  let mut last = String::new();
+  let mut stdout = io::stdout();
+  let mut lines = io::stdin().lock().lines();
+
+  println!("Hello, space! :help lists the commands.");
+
+  loop {
+    stdout.write_all(b"> ").expect("Couldn't write to stdout");
     stdout.flush().expect("Couldn't flush stdout");
 
-    stdin
-      .read_line(&mut input)
-      .expect("Couldn't read line from stdin");
+    let input = match lines.next() {
+      Some(Ok(input)) => input,
+      _ => break,
+    };
+    let input = input.trim();
+    let (command, rest) = input.split_once(' ').unwrap_or((input, ""));
+
+    match command {
+      "" => {},
+      ":quit" | ":q" => return,
+      ":help" => println!("{}", REPL_HELP),
+      ":asm" => match &session.assembly {
+        Some(assembly) => print!("{}", assembly),
+        None => println!("nothing compiled yet"),
+      },
+      ":tokens" => {
+        let text = if rest.is_empty() { last.as_str() } else { rest };
+
+        for lexeme in Lexer::new(text) {
+          println!("{:?}", lexeme);
+        }
+      },
+      ":vars" => {
+        for variable in &session.variables {
+          println!("{}", describe(variable));
+        }
+      },
+      _ if command.starts_with(':') => println!("unknown command `{}`, :help lists the commands", command),
+      _ => {
+        last = input.to_string();
 
-    let lexer = Lexer::new(&input);
-    
-    for lexeme in lexer {
-      println!("{:?}", lexeme);
+        match session.eval(input) {
+          Ok(Outcome::Values(values)) => {
+            for value in values {
+              match value {
+                OutputValue::Int(_) => println!("i32 {}", value),
+                OutputValue::Float(_) => println!("f32 {}", value),
+                OutputValue::Bin(_) => println!("{}", value),
+              }
+            }
+          },
+          Ok(Outcome::Variable(variable)) => println!("{}", describe(&variable)),
+          Ok(Outcome::Condition(holds)) => println!("{}", holds),
+          Ok(Outcome::Done) => {},
+          Err(err) => eprintln!("{}", err),
+        }
+      },
     }
   }
+
+  println!();
+}
+
+fn lsp(args: &[String]) {
+  Arguments::parse(args, &[], LSP_USAGE);
+
+  let stdin = io::stdin();
+  let mut input = stdin.lock();
+  let mut output = io::stdout();
+
+  match Server::new().run(&mut input, &mut output) {
+    Ok(code) => process::exit(code),
+    Err(err) => fail(format!("lsp: {}", err)),
+  }
+}
+
+fn grammar(args: &[String]) {
+  let args = Arguments::parse(args, &["-o"], GRAMMAR_USAGE);
+  let mut dialects: Vec<Dialect> = args
+    .inputs
+    .iter()
+    .map(|name| {
+      DIALECTS
+        .iter()
+        .copied()
+        .find(|dialect| dialect.extension() == name.trim_start_matches('.'))
+        .unwrap_or_else(|| usage(GRAMMAR_USAGE))
+    })
+    .collect();
+
+  match args.value("-o") {
+    Some(directory) => {
+      if dialects.is_empty() {
+        dialects = DIALECTS.to_vec();
+      }
+      fs::create_dir_all(directory).unwrap_or_else(|err| fail(format!("{}: {}", directory, err)));
+
+      for dialect in dialects {
+        let path = Path::new(directory).join(format!("{}.tmLanguage.json", dialect.extension()));
+
+        write(&path, &format!("{}\n", textmate::grammar(dialect).pretty()));
+        println!("{}", path.display());
+      }
+    },
+    None => match dialects.as_slice() {
+      [dialect] => println!("{}", textmate::grammar(*dialect).pretty()),
+      _ => usage(GRAMMAR_USAGE),
+    },
+  }
+}
+
+/// `var x: f32 = 0.5`.
+fn describe(variable: &Variable) -> String {
+  let value = match variable.value {
+    Literal::Int(value) => value.to_string(),
+    Literal::Float(value) => format!("{:?}", value),
+  };
+  let keyword = if variable.constant { "const" } else { "var" };
+
+  format!("{} {}: {} = {}", keyword, variable.name.text, variable.ty, value)
 }
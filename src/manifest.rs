@@ -0,0 +1,231 @@
+//! Mission manifests, `Splice.toml`: what `splice build` needs to turn the
+//! sources of a mission into its executable in one command.
+//!
+//! ```toml
+//! [mission]
+//! name = "orbit"                   # the outputs are named after it
+//! target = "opssat"                # a built-in target profile or a .toml file
+//! instruments = ["opssat.spli"]
+//! sources = ["orbit.spl", "housekeeping.splc"]
+//! groups = ["imaging"]             # every group by default
+//! output = "build"                 # the default
+//! formats = ["splx", "map", "spld", "cost"]
+//!
+//! [budget.imaging]                 # a group name or id, or "*"
+//! wcet = 20000
+//! INST_IMG = 1
+//! ```
+//!
+//! Paths are relative to the manifest.
+
+use crate::json::Json;
+use crate::target::Target;
+use crate::toml;
+use crate::vm::cost::{Budget, Limit};
+
+pub const FILE_NAME: &str = "Splice.toml";
+
+/// What `splice build` writes into the output directory.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+  /// `GROUP_TASK.splc` and its `.spld` sidecar for every compiled task.
+  Assembly,
+  /// `NAME.splx`, the linked executable.
+  Executable,
+  /// `NAME.map`, the link map.
+  Map,
+  /// `NAME.spld`, the debug sidecar of the executable.
+  Debug,
+  /// `GROUP_TASK.lst` for every compiled task, `FILE.lst` for `.splc` sources.
+  Listing,
+  /// `NAME.cost`, the cost report.
+  Cost,
+}
+
+pub const OUTPUT_FORMATS: &[OutputFormat] = &[
+  OutputFormat::Assembly,
+  OutputFormat::Executable,
+  OutputFormat::Map,
+  OutputFormat::Debug,
+  OutputFormat::Listing,
+  OutputFormat::Cost,
+];
+
+impl OutputFormat {
+  /// The extension of its files, which also names the format in manifests.
+  pub fn extension(self) -> &'static str {
+    match self {
+      OutputFormat::Assembly => "splc",
+      OutputFormat::Executable => "splx",
+      OutputFormat::Map => "map",
+      OutputFormat::Debug => "spld",
+      OutputFormat::Listing => "lst",
+      OutputFormat::Cost => "cost",
+    }
+  }
+}
+
+/// A budget of a group given by name, id or `*`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GroupBudget {
+  pub group: String,
+  pub limit: Limit,
+  pub value: u64,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Manifest {
+  pub name: String,
+  /// The name of a built-in target, or the path of a target profile.
+  pub target: String,
+  pub instruments: Vec<String>,
+  /// `.spl` and `.splc` files, in link order.
+  pub sources: Vec<String>,
+  /// The groups of the `.spl` sources to build, all of them when empty.
+  pub groups: Vec<String>,
+  pub output: String,
+  pub formats: Vec<OutputFormat>,
+  pub budgets: Vec<GroupBudget>,
+}
+
+const MISSION_KEYS: &[&str] = &["name", "target", "instruments", "sources", "groups", "output", "formats"];
+
+impl Manifest {
+  pub fn parse(text: &str) -> Result<Manifest, String> {
+    let root = toml::parse(text).map_err(|err| err.to_string())?;
+
+    for (key, _) in members(&root) {
+      if key != "mission" && key != "budget" {
+        return Err(format!("unknown table `{}`", key));
+      }
+    }
+
+    let mission = root.get("mission").ok_or("missing the [mission] table")?;
+    for (key, _) in members(mission) {
+      if !MISSION_KEYS.contains(&key.as_str()) {
+        return Err(format!("unknown key `mission.{}`", key));
+      }
+    }
+
+    let string = |key: &str, default: Option<&str>| match mission.get(key) {
+      Some(value) => value
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| format!("`mission.{}` has to be a string", key)),
+      None => default
+        .map(str::to_string)
+        .ok_or_else(|| format!("missing `mission.{}`", key)),
+    };
+    let strings = |key: &str| match mission.get(key) {
+      None => Ok(Vec::new()),
+      Some(Json::String(value)) => Ok(vec![value.clone()]),
+      Some(value) => value
+        .as_array()
+        .and_then(|values| values.iter().map(|value| value.as_str().map(str::to_string)).collect())
+        .ok_or_else(|| format!("`mission.{}` has to be an array of strings", key)),
+    };
+
+    let name = string("name", None)?;
+    if name.is_empty() || name.contains(['/', '\\']) {
+      return Err(format!("`mission.name` has to be a file name, not `{}`", name));
+    }
+
+    let target = string("target", Some("opssat"))?;
+    if !target.ends_with(".toml") {
+      Target::builtin(&target).ok_or_else(|| format!("unknown target `{}`", target))?;
+    }
+
+    let sources = strings("sources")?;
+    if sources.is_empty() {
+      return Err("`mission.sources` lists no source".to_string());
+    }
+    if let Some(source) = sources.iter().find(|source| !source.ends_with(".spl") && !source.ends_with(".splc")) {
+      return Err(format!("`{}` is neither a .spl nor a .splc file", source));
+    }
+
+    let formats = match mission.get("formats") {
+      None => vec![OutputFormat::Executable, OutputFormat::Map, OutputFormat::Debug],
+      Some(_) => strings("formats")?
+        .iter()
+        .map(|name| {
+          OUTPUT_FORMATS
+            .iter()
+            .copied()
+            .find(|format| format.extension() == name)
+            .ok_or_else(|| format!("unknown output format `{}`", name))
+        })
+        .collect::<Result<_, _>>()?,
+    };
+
+    let mut budgets = Vec::new();
+    if let Some(budget) = root.get("budget") {
+      for (group, limits) in members(budget) {
+        if !matches!(limits, Json::Object(_)) {
+          return Err(format!("`budget.{}` has to be a table of limits", group));
+        }
+
+        for (limit, value) in members(limits) {
+          let value = value
+            .as_f64()
+            .filter(|value| *value >= 0.0 && value.fract() == 0.0)
+            .ok_or_else(|| format!("`budget.{}.{}` has to be a non-negative integer", group, limit))?;
+
+          budgets.push(GroupBudget {
+            group: group.clone(),
+            limit: Limit::parse(limit).ok_or_else(|| format!("unknown limit `budget.{}.{}`", group, limit))?,
+            value: value as u64,
+          });
+        }
+      }
+    }
+
+    Ok(Manifest {
+      name,
+      target,
+      instruments: strings("instruments")?,
+      sources,
+      groups: strings("groups")?,
+      output: string("output", Some("build"))?,
+      formats,
+      budgets,
+    })
+  }
+
+  pub fn has(&self, format: OutputFormat) -> bool {
+    self.formats.contains(&format)
+  }
+
+  /// The budgets by group id, given the id of every group name.
+  pub fn budgets(&self, groups: &[(String, u8)]) -> Result<Vec<Budget>, String> {
+    self
+      .budgets
+      .iter()
+      .map(|budget| {
+        let group_id = match budget.group.as_str() {
+          "*" => None,
+          group => match groups.iter().find(|(name, _)| name == group) {
+            Some((_, id)) => Some(*id),
+            None => Some(
+              group
+                .parse()
+                .map_err(|_| format!("the budget of `{}` names no group of the mission", group))?,
+            ),
+          },
+        };
+
+        Ok(Budget {
+          group_id,
+          limit: budget.limit,
+          value: budget.value,
+        })
+      })
+      .collect()
+  }
+}
+
+fn members(value: &Json) -> &[(String, Json)] {
+  match value {
+    Json::Object(members) => members,
+    _ => &[],
+  }
+}
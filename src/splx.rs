@@ -0,0 +1,219 @@
+//! The `.splx` executable format: every task is written as a comma-separated
+//! list of hexadecimal machine words on its own line. Other targets can
+//! write words in another radix, separate them otherwise and order the
+//! fields of the header differently, as their container describes.
+
+use std::fmt;
+
+use crate::asm::isa::unpack32to4x8;
+use crate::asm::{Header, Task, Word};
+use crate::target::Target;
+
+/// Formats the words of a single task as one `.splx` line (without newline).
+pub fn format_task(words: &[Word]) -> String {
+  format_task_for(Target::opssat(), words)
+}
+
+/// Like `format_task`, in the container of `target`.
+pub fn format_task_for(target: &Target, words: &[Word]) -> String {
+  let container = &target.container;
+
+  words
+    .iter()
+    .enumerate()
+    .map(|(index, word)| match index {
+      0 => format_word(container.encode_header(*word), container.radix),
+      _ => format_word(*word, container.radix),
+    })
+    .collect::<Vec<_>>()
+    .join(&container.separator)
+}
+
+fn format_word(mut word: Word, radix: u32) -> String {
+  let mut digits = Vec::new();
+
+  loop {
+    digits.push(std::char::from_digit(word % radix, radix).expect("digits are below the radix"));
+    word /= radix;
+
+    if word == 0 {
+      return digits.iter().rev().collect();
+    }
+  }
+}
+
+#[derive(Debug)]
+pub struct SplxError {
+  /// 1-based line of the `.splx` file.
+  pub line: usize,
+  pub message: String,
+}
+
+impl fmt::Display for SplxError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "line {}: {}", self.line, self.message)
+  }
+}
+
+impl std::error::Error for SplxError {}
+
+impl SplxError {
+  /// The error of a file that is not an executable of `target`, as a file
+  /// linked for another target profile does not parse in its container.
+  pub fn not_executable_of(&self, target: &Target) -> String {
+    let what = match Target::builtin(&target.name) {
+      Some(_) => "an OPS-SAT".to_string(),
+      None => format!("a `{}`", target.name),
+    };
+
+    format!("not {} executable ({})", what, self)
+  }
+}
+
+/// Parses a `.splx` file, as written by `splasm` (one task) or `splink`
+/// (one task per line).
+pub fn parse(text: &str) -> Result<Vec<Task>, SplxError> {
+  parse_for(Target::opssat(), text)
+}
+
+/// Like `parse`, in the container and with the ISA of `target`.
+pub fn parse_for(target: &Target, text: &str) -> Result<Vec<Task>, SplxError> {
+  let container = &target.container;
+  let mut tasks = Vec::new();
+
+  for (index, line) in text.lines().enumerate() {
+    let line = line.trim();
+
+    if line.is_empty() {
+      continue;
+    }
+
+    let error = |message: String| SplxError {
+      line: index + 1,
+      message,
+    };
+
+    let mut words = line
+      .split(container.separator.as_str())
+      .map(|word| {
+        Word::from_str_radix(word.trim(), container.radix).map_err(|_| error(format!("invalid word `{}`", excerpt(word))))
+      })
+      .collect::<Result<Vec<_>, _>>()?;
+
+    if let Some(header) = words.first_mut() {
+      *header = container.decode_header(*header);
+    }
+    tasks.push(parse_task_for(target, &words).map_err(error)?);
+  }
+
+  Ok(tasks)
+}
+
+/// The start of a word that does not parse, which is the whole line when the
+/// file separates its words otherwise.
+fn excerpt(word: &str) -> String {
+  match word.char_indices().nth(32) {
+    Some((end, _)) => format!("{}...", &word[..end]),
+    None => word.to_string(),
+  }
+}
+
+/// Splits the words of a task into header, code and data. Older files have
+/// a zero header length; their code is taken to end at the first `OP_HLT`.
+pub fn parse_task(words: &[Word]) -> Result<Task, String> {
+  parse_task_for(Target::opssat(), words)
+}
+
+/// Like `parse_task`, with the `OP_HLT` of `target`; the header word is the
+/// one of `Header::word`.
+pub fn parse_task_for(target: &Target, words: &[Word]) -> Result<Task, String> {
+  let (&header_word, body) = words.split_first().ok_or_else(|| "empty task".to_string())?;
+  let [group_id, task_id, freq, length] = unpack32to4x8(header_word);
+
+  let is_halt = |word: &Word| unpack32to4x8(*word)[0] == target.halt();
+  let halt = body.iter().position(is_halt);
+  let code_len = match (usize::from(length), halt) {
+    (0, Some(halt)) => halt + 1,
+    (length, _) if length > 0 && body.get(length - 1).is_some_and(is_halt) => length,
+    (0, None) => return Err(format!("task {}.{}: no OP_HLT ends the code section", group_id, task_id)),
+    (length, Some(halt)) => {
+      return Err(format!(
+        "task {}.{}: the header says {} code words, but OP_HLT is word {}",
+        group_id,
+        task_id,
+        length,
+        halt + 1
+      ))
+    },
+    (length, None) => {
+      return Err(format!(
+        "task {}.{}: no OP_HLT ends the code section of {} words",
+        group_id, task_id, length
+      ))
+    },
+  };
+
+  Ok(Task {
+    header: Header {
+      group_id,
+      task_id,
+      freq,
+      length: code_len as u8,
+    },
+    code: body[..code_len].to_vec(),
+    data: body[code_len..].to_vec(),
+    symbols: Vec::new(),
+    relocations: Vec::new(),
+    sources: Vec::new(),
+    debug: None,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // the code of task 2.1 below: `OP_GET, INST_NMF, P_NMF_TIME, IREG_A` and `OP_HLT`
+  const GET: Word = 0x0506_0100;
+  const HLT: Word = 0x0700_0000;
+
+  #[test]
+  fn splits_code_and_data_at_the_header_length() {
+    let task = parse_task(&[0x0201_0502, GET, HLT, 42]).unwrap();
+
+    assert_eq!(task.code, vec![GET, HLT]);
+    assert_eq!(task.data, vec![42]);
+  }
+
+  #[test]
+  fn finds_the_code_of_older_files_at_the_first_halt() {
+    let task = parse_task(&[0x0201_0500, GET, HLT, 42]).unwrap();
+
+    assert_eq!(task.header.length, 2);
+    assert_eq!(task.data, vec![42]);
+  }
+
+  #[test]
+  fn reports_a_header_length_that_misses_the_halt() {
+    let error = parse_task(&[0x0201_0503, GET, HLT, 42]).unwrap_err();
+
+    assert_eq!(error, "task 2.1: the header says 3 code words, but OP_HLT is word 2");
+  }
+
+  #[test]
+  fn reports_a_missing_halt() {
+    assert_eq!(
+      parse_task(&[0x0201_0502, GET, GET]).unwrap_err(),
+      "task 2.1: no OP_HLT ends the code section of 2 words"
+    );
+    assert_eq!(parse_task(&[0x0201_0500, GET]).unwrap_err(), "task 2.1: no OP_HLT ends the code section");
+  }
+
+  #[test]
+  fn reads_the_words_of_a_line() {
+    let tasks = parse("2010502,5060100,7000000,2a\n").unwrap();
+
+    assert_eq!(tasks.len(), 1);
+    assert_eq!(tasks[0].data, vec![42]);
+  }
+}
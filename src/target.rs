@@ -0,0 +1,855 @@
+//! Target profiles: the ISA of the VM of a satellite and the container of its
+//! executables, read from a TOML data file so that the same compiler emits
+//! code for every satellite. `targets/opssat.toml` describes the OPS-SAT VM,
+//! which the reference VM runs; it is built in as `opssat`, and as
+//! `reference` with the unit costs of the `reference` cost table:
+//!
+//! ```toml
+//! name = "opssat"
+//! costs = "opssat"                 # the built-in cost table of the target
+//!
+//! [container]
+//! extension = "splx"
+//! radix = 16                       # of the words, separated by `separator`
+//! separator = ","
+//! header = ["group", "task", "freq", "length"]
+//!
+//! [registers]
+//! integer = ["IREG_A", "IREG_B"]   # from 0
+//! float = ["FREG_A", "FREG_B"]     # from float_base
+//! float_base = 16
+//!
+//! [opcodes.OP_MOV]
+//! value = 1
+//! operands = ["prefix", "register", "selected"]
+//! prefixes = { PRE_MOV_REG = 1, PRE_MOV_RAM = { value = 2, selects = "address" } }
+//!
+//! [operators]
+//! ALU_GT = { value = 3, comparison = ">" }
+//! FPU_GT = { value = 9, comparison = ">", type = "f32" }
+//! TSX_EQ = { value = 13, comparison = "==", selects = "task" }
+//!
+//! [instruments.INST_GPS]
+//! value = 2
+//! parameters = { P_GPS_LATT = 1, P_GPS_TIME = { value = 4, type = "i32" } }
+//! ```
+//!
+//! The operands of an opcode lay out the three bytes after it. A `data`
+//! operand takes the last two, the `TASK_ID, ADDRESS` pair of the data word
+//! the instruction loads, which the linker patches; a `selected` operand is a
+//! register, unless the prefix or operator before it selects the `address` of
+//! the task's own data the instruction stores to or a `task` id.
+
+use std::convert::TryFrom;
+use std::fs;
+use std::sync::OnceLock;
+
+use crate::asm::isa::{pack4x8to32, unpack32to4x8, Word};
+use crate::compiler::ast::{Comparison, Type};
+use crate::json::Json;
+use crate::toml;
+use crate::vm::cost::CostTable;
+
+/// The built-in targets, by name.
+pub const BUILTIN: &[(&str, &str)] = &[("opssat", include_str!("../targets/opssat.toml"))];
+
+/// What a byte of an instruction holds, or two bytes for `Data`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Operand {
+  Register,
+  Prefix,
+  Operator,
+  Instrument,
+  /// A parameter of the instrument before it.
+  Parameter,
+  /// An action of the instrument before it.
+  Action,
+  /// `TASK_ID, ADDRESS` of a data word to load.
+  Data,
+  /// An address of the task's own data to store to.
+  Address,
+  Task,
+  /// What the prefix or operator before it selects.
+  Selected,
+  /// Zero, and left out of the assembly.
+  Unused,
+}
+
+const OPERANDS: &[(&str, Operand)] = &[
+  ("register", Operand::Register),
+  ("prefix", Operand::Prefix),
+  ("operator", Operand::Operator),
+  ("instrument", Operand::Instrument),
+  ("parameter", Operand::Parameter),
+  ("action", Operand::Action),
+  ("data", Operand::Data),
+  ("address", Operand::Address),
+  ("task", Operand::Task),
+  ("selected", Operand::Selected),
+  ("unused", Operand::Unused),
+];
+
+impl Operand {
+  pub fn name(self) -> &'static str {
+    OPERANDS
+      .iter()
+      .find(|(_, operand)| *operand == self)
+      .map(|(name, _)| *name)
+      .expect("every operand has a name")
+  }
+
+  /// The bytes it takes in an instruction.
+  pub fn width(self) -> usize {
+    match self {
+      Operand::Data => 2,
+      _ => 1,
+    }
+  }
+}
+
+/// A prefix of an opcode.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Prefix {
+  pub mnemonic: String,
+  pub value: u8,
+  /// What the `selected` operand of the instruction is.
+  pub selects: Operand,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Opcode {
+  pub mnemonic: String,
+  pub value: u8,
+  /// Empty for instructions without operands.
+  pub operands: Vec<Operand>,
+  pub prefixes: Vec<Prefix>,
+}
+
+impl Opcode {
+  /// The numbers of operands its assembly can have: `Data` is written as
+  /// `TASK_ID, ADDRESS` or as a single `task.label`.
+  pub fn operand_counts(&self) -> Vec<usize> {
+    let written = self
+      .operands
+      .iter()
+      .filter(|operand| **operand != Operand::Unused)
+      .count();
+
+    if self.operands.contains(&Operand::Data) {
+      vec![written, written + 1]
+    }
+    else {
+      vec![written]
+    }
+  }
+
+  pub fn prefix(&self, mnemonic: &str) -> Option<&Prefix> {
+    self
+      .prefixes
+      .iter()
+      .find(|prefix| prefix.mnemonic.eq_ignore_ascii_case(mnemonic))
+  }
+
+  pub fn prefix_of(&self, value: u8) -> Option<&Prefix> {
+    self.prefixes.iter().find(|prefix| prefix.value == value)
+  }
+}
+
+/// A compare operator of `OP_CMP`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Operator {
+  pub mnemonic: String,
+  pub value: u8,
+  pub comparison: Comparison,
+  /// The type of the registers it compares.
+  pub ty: Type,
+  /// `Register`, or `Task` for the operators comparing the status of a task.
+  pub selects: Operand,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Parameter {
+  pub mnemonic: String,
+  pub value: u8,
+  pub ty: Type,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Instrument {
+  pub mnemonic: String,
+  pub value: u8,
+  pub parameters: Vec<Parameter>,
+  pub actions: Vec<(String, u8)>,
+}
+
+impl Instrument {
+  /// `gps` for `INST_GPS`, as task list files name it.
+  pub fn name(&self) -> String {
+    self
+      .mnemonic
+      .trim_start_matches("INST_")
+      .to_ascii_lowercase()
+  }
+
+  pub fn parameter(&self, mnemonic: &str) -> Option<&Parameter> {
+    self
+      .parameters
+      .iter()
+      .find(|parameter| parameter.mnemonic.eq_ignore_ascii_case(mnemonic))
+  }
+
+  pub fn parameter_of(&self, value: u8) -> Option<&Parameter> {
+    self.parameters.iter().find(|parameter| parameter.value == value)
+  }
+
+  pub fn action(&self, mnemonic: &str) -> Option<&(String, u8)> {
+    self
+      .actions
+      .iter()
+      .find(|(candidate, _)| candidate.eq_ignore_ascii_case(mnemonic))
+  }
+
+  pub fn action_of(&self, value: u8) -> Option<&(String, u8)> {
+    self.actions.iter().find(|(_, candidate)| *candidate == value)
+  }
+
+  /// `P_GPS_` for the parameters of `INST_GPS`, `A_GPS_` for its actions.
+  fn prefix(&self, kind: &str) -> String {
+    format!("{}_{}_", kind, self.mnemonic.trim_start_matches("INST_"))
+  }
+
+  /// The parameter of a property, `latt` for `P_GPS_LATT`.
+  pub fn property(&self, name: &str) -> Option<&Parameter> {
+    let mnemonic = format!("{}{}", self.prefix("P"), name.to_ascii_uppercase());
+
+    self
+      .parameters
+      .iter()
+      .find(|parameter| parameter.mnemonic == mnemonic)
+      .filter(|_| !name.chars().any(|ch| ch.is_ascii_uppercase()))
+  }
+
+  /// The names of its properties.
+  pub fn properties(&self) -> Vec<String> {
+    let prefix = self.prefix("P");
+
+    self
+      .parameters
+      .iter()
+      .filter_map(|parameter| parameter.mnemonic.strip_prefix(&prefix))
+      .map(str::to_ascii_lowercase)
+      .collect()
+  }
+
+  /// The mnemonic of an action, `A_IMG_DO_JPG` for `do_jpg`.
+  pub fn action_named(&self, name: &str) -> Option<&str> {
+    let mnemonic = format!("{}{}", self.prefix("A"), name.to_ascii_uppercase());
+
+    self
+      .actions
+      .iter()
+      .find(|(candidate, _)| *candidate == mnemonic)
+      .filter(|_| !name.chars().any(|ch| ch.is_ascii_uppercase()))
+      .map(|(mnemonic, _)| mnemonic.as_str())
+  }
+
+  /// The names of its actions.
+  pub fn action_names(&self) -> Vec<String> {
+    let prefix = self.prefix("A");
+
+    self
+      .actions
+      .iter()
+      .filter_map(|(mnemonic, _)| mnemonic.strip_prefix(&prefix))
+      .map(str::to_ascii_lowercase)
+      .collect()
+  }
+}
+
+/// The integer registers are numbered from 0, the float registers from
+/// `float_base`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RegisterFile {
+  pub integer: Vec<String>,
+  pub float: Vec<String>,
+  pub float_base: u8,
+}
+
+impl RegisterFile {
+  pub fn count(&self, ty: Type) -> usize {
+    match ty {
+      Type::I32 => self.integer.len(),
+      Type::F32 => self.float.len(),
+    }
+  }
+
+  /// The name of the register `index` of a type, counting from the first.
+  pub fn name(&self, ty: Type, index: usize) -> Option<&str> {
+    match ty {
+      Type::I32 => self.integer.get(index),
+      Type::F32 => self.float.get(index),
+    }
+    .map(String::as_str)
+  }
+
+  pub fn value(&self, name: &str) -> Option<u8> {
+    let position = |names: &[String]| names.iter().position(|candidate| candidate.eq_ignore_ascii_case(name));
+
+    match position(&self.integer) {
+      Some(index) => Some(index as u8),
+      None => position(&self.float).map(|index| self.float_base + index as u8),
+    }
+  }
+
+  pub fn name_of(&self, value: u8) -> Option<&str> {
+    let name = match value.checked_sub(self.float_base) {
+      Some(index) => self.float.get(usize::from(index)),
+      None => self.integer.get(usize::from(value)),
+    };
+
+    name.map(String::as_str)
+  }
+
+  pub fn is_float(&self, value: u8) -> bool {
+    value >= self.float_base
+  }
+}
+
+/// A field of the task header.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HeaderField {
+  Group,
+  Task,
+  Freq,
+  Length,
+}
+
+const HEADER_FIELDS: &[(&str, HeaderField)] = &[
+  ("group", HeaderField::Group),
+  ("task", HeaderField::Task),
+  ("freq", HeaderField::Freq),
+  ("length", HeaderField::Length),
+];
+
+/// How executables are written: every task on its own line, as its words in
+/// `radix` separated by `separator`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Container {
+  pub extension: String,
+  pub radix: u32,
+  pub separator: String,
+  /// The fields of the header word, from its most significant byte.
+  pub header: [HeaderField; 4],
+}
+
+impl Container {
+  /// The header word in the container, given `Header::word`.
+  pub fn encode_header(&self, word: Word) -> Word {
+    let bytes = unpack32to4x8(word);
+    let [a, b, c, d] = self.header.map(|field| bytes[field as usize]);
+
+    pack4x8to32(a, b, c, d)
+  }
+
+  /// `Header::word`, given the header word in the container.
+  pub fn decode_header(&self, word: Word) -> Word {
+    let mut bytes = [0; 4];
+
+    for (field, byte) in self.header.iter().zip(unpack32to4x8(word)) {
+      bytes[*field as usize] = byte;
+    }
+
+    pack4x8to32(bytes[0], bytes[1], bytes[2], bytes[3])
+  }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Target {
+  pub name: String,
+  /// The built-in cost table of `splice::vm::cost` it is estimated with.
+  pub costs: String,
+  pub container: Container,
+  pub registers: RegisterFile,
+  pub opcodes: Vec<Opcode>,
+  pub operators: Vec<Operator>,
+  pub instruments: Vec<Instrument>,
+}
+
+/// The mnemonic every target ends the code of a task with.
+pub const HALT: &str = "OP_HLT";
+
+impl Target {
+  pub fn builtin(name: &str) -> Option<Target> {
+    if name == "reference" {
+      return Target::builtin("opssat").map(|target| Target {
+        name: name.to_string(),
+        costs: name.to_string(),
+        ..target
+      });
+    }
+
+    BUILTIN
+      .iter()
+      .find(|(candidate, _)| *candidate == name)
+      .map(|(_, text)| Target::parse(text).expect("built-in targets are valid"))
+  }
+
+  /// The OPS-SAT target, the default of every tool.
+  pub fn opssat() -> &'static Target {
+    static OPSSAT: OnceLock<Target> = OnceLock::new();
+
+    OPSSAT.get_or_init(|| Target::builtin("opssat").expect("opssat is built in"))
+  }
+
+  /// A built-in target, or the target profile of a `.toml` file.
+  pub fn load(name: &str) -> Result<Target, String> {
+    if !name.ends_with(".toml") {
+      return Target::builtin(name).ok_or_else(|| format!("unknown target `{}`", name));
+    }
+
+    let text = fs::read_to_string(name).map_err(|err| format!("{}: {}", name, err))?;
+    Target::parse(&text).map_err(|err| format!("{}: {}", name, err))
+  }
+
+  pub fn parse(text: &str) -> Result<Target, String> {
+    let root = toml::parse(text).map_err(|err| err.to_string())?;
+    keys(&root, "", &["name", "costs", "container", "registers", "opcodes", "operators", "instruments"])?;
+
+    let name = string(&root, "name")?.ok_or("missing `name`")?;
+    let costs = string(&root, "costs")?.unwrap_or_else(|| "reference".to_string());
+    if CostTable::builtin(&costs).is_none() {
+      return Err(format!("unknown cost table `{}`", costs));
+    }
+
+    let target = Target {
+      name,
+      costs,
+      container: container(root.get("container"))?,
+      registers: registers(root.get("registers").ok_or("missing the [registers] table")?)?,
+      opcodes: opcodes(root.get("opcodes").ok_or("missing the [opcodes] table")?)?,
+      operators: operators(root.get("operators"))?,
+      instruments: instruments(root.get("instruments"))?,
+    };
+
+    match target.opcode(HALT) {
+      Some(halt) if halt.operands.is_empty() => Ok(target),
+      Some(_) => Err(format!("`{}` cannot have operands", HALT)),
+      None => Err(format!("every target needs `{}`", HALT)),
+    }
+  }
+
+  pub fn opcode(&self, mnemonic: &str) -> Option<&Opcode> {
+    self
+      .opcodes
+      .iter()
+      .find(|opcode| opcode.mnemonic.eq_ignore_ascii_case(mnemonic))
+  }
+
+  pub fn opcode_of(&self, value: u8) -> Option<&Opcode> {
+    self.opcodes.iter().find(|opcode| opcode.value == value)
+  }
+
+  /// The operands of an instruction with the index of their first byte, a
+  /// `selected` operand being what the prefix or operator before it selects;
+  /// `None` for an unknown opcode.
+  pub fn operands(&self, word: Word) -> Option<Vec<(Operand, usize)>> {
+    let bytes = unpack32to4x8(word);
+    let opcode = self.opcode_of(bytes[0])?;
+    let mut operands = Vec::with_capacity(opcode.operands.len());
+    let mut selected = Operand::Register;
+    let mut byte = 1;
+
+    for operand in &opcode.operands {
+      match operand {
+        Operand::Selected => operands.push((selected, byte)),
+        Operand::Prefix => {
+          selected = opcode.prefix_of(bytes[byte]).map_or(Operand::Register, |prefix| prefix.selects);
+          operands.push((*operand, byte));
+        },
+        Operand::Operator => {
+          selected = self.operator_of(bytes[byte]).map_or(Operand::Register, |operator| operator.selects);
+          operands.push((*operand, byte));
+        },
+        _ => operands.push((*operand, byte)),
+      }
+
+      byte += operand.width();
+    }
+
+    Some(operands)
+  }
+
+  /// The opcode that ends the code of a task.
+  pub fn halt(&self) -> u8 {
+    self.opcode(HALT).expect("targets are checked to have OP_HLT").value
+  }
+
+  pub fn operator(&self, mnemonic: &str) -> Option<&Operator> {
+    self
+      .operators
+      .iter()
+      .find(|operator| operator.mnemonic.eq_ignore_ascii_case(mnemonic))
+  }
+
+  pub fn operator_of(&self, value: u8) -> Option<&Operator> {
+    self.operators.iter().find(|operator| operator.value == value)
+  }
+
+  /// The operator making a comparison of `ty` registers, or with
+  /// `Operand::Task` of the status of a task.
+  pub fn comparison(&self, selects: Operand, ty: Type, comparison: Comparison) -> Option<&Operator> {
+    self.operators.iter().find(|operator| {
+      operator.selects == selects && operator.comparison == comparison && (selects == Operand::Task || operator.ty == ty)
+    })
+  }
+
+  pub fn instrument(&self, mnemonic: &str) -> Option<&Instrument> {
+    self
+      .instruments
+      .iter()
+      .find(|instrument| instrument.mnemonic.eq_ignore_ascii_case(mnemonic))
+  }
+
+  pub fn instrument_of(&self, value: u8) -> Option<&Instrument> {
+    self.instruments.iter().find(|instrument| instrument.value == value)
+  }
+
+  /// The instrument a task list file names `gps`.
+  pub fn instrument_named(&self, name: &str) -> Option<&Instrument> {
+    self
+      .instruments
+      .iter()
+      .find(|instrument| instrument.name() == name)
+  }
+}
+
+fn members(value: &Json) -> &[(String, Json)] {
+  match value {
+    Json::Object(members) => members,
+    _ => &[],
+  }
+}
+
+/// Rejects the keys of the table at `path` that are not `allowed`.
+fn keys(table: &Json, path: &str, allowed: &[&str]) -> Result<(), String> {
+  match members(table).iter().find(|(key, _)| !allowed.contains(&key.as_str())) {
+    Some((key, _)) => Err(format!("unknown key `{}{}`", path, key)),
+    None => Ok(()),
+  }
+}
+
+fn string(table: &Json, key: &str) -> Result<Option<String>, String> {
+  match table.get(key) {
+    None => Ok(None),
+    Some(value) => value
+      .as_str()
+      .map(|value| Some(value.to_string()))
+      .ok_or_else(|| format!("`{}` has to be a string", key)),
+  }
+}
+
+fn strings(value: &Json, path: &str) -> Result<Vec<String>, String> {
+  value
+    .as_array()
+    .and_then(|values| values.iter().map(|value| value.as_str().map(str::to_string)).collect())
+    .ok_or_else(|| format!("`{}` has to be an array of strings", path))
+}
+
+fn byte(value: &Json, path: &str) -> Result<u8, String> {
+  value
+    .as_f64()
+    .filter(|value| (0.0..=255.0).contains(value) && value.fract() == 0.0)
+    .map(|value| value as u8)
+    .ok_or_else(|| format!("`{}` has to be a number from 0 to 255", path))
+}
+
+fn ty(table: &Json, path: &str, default: Type) -> Result<Type, String> {
+  match table.get("type").map(|value| value.as_str()) {
+    None => Ok(default),
+    Some(Some("i32")) => Ok(Type::I32),
+    Some(Some("f32")) => Ok(Type::F32),
+    Some(_) => Err(format!("`{}.type` has to be `i32` or `f32`", path)),
+  }
+}
+
+/// What an entry selects, one of `allowed`, `Register` by default.
+fn selects(table: &Json, path: &str, allowed: &[Operand]) -> Result<Operand, String> {
+  let name = match table.get("selects") {
+    None => return Ok(Operand::Register),
+    Some(value) => value.as_str(),
+  };
+
+  allowed
+    .iter()
+    .copied()
+    .find(|operand| Some(operand.name()) == name)
+    .ok_or_else(|| {
+      let names: Vec<&str> = allowed.iter().map(|operand| operand.name()).collect();
+
+      format!("`{}.selects` has to be one of {}", path, names.join(", "))
+    })
+}
+
+/// The mnemonics of a table whose entries are a value or an inline table
+/// with a `value` and other `allowed` keys, with the value and the entry.
+fn entries<'a>(table: &'a Json, path: &str, allowed: &[&str]) -> Result<Vec<(String, u8, &'a Json)>, String> {
+  if !matches!(table, Json::Object(_)) {
+    return Err(format!("`{}` has to be a table", path));
+  }
+
+  let table_path = path;
+  let mut entries: Vec<(String, u8, &Json)> = Vec::new();
+  for (mnemonic, entry) in members(table) {
+    let path = format!("{}.{}", table_path, mnemonic);
+    let value = match entry {
+      Json::Object(_) => {
+        keys(entry, &format!("{}.", path), allowed)?;
+        byte(entry.get("value").ok_or_else(|| format!("missing `{}.value`", path))?, &format!("{}.value", path))?
+      },
+      _ => byte(entry, &path)?,
+    };
+
+    if let Some((other, _, _)) = entries.iter().find(|(_, candidate, _)| *candidate == value) {
+      return Err(format!("`{}` and `{}` are both {} in `{}`", other, mnemonic, value, table_path));
+    }
+    entries.push((mnemonic.clone(), value, entry));
+  }
+
+  Ok(entries)
+}
+
+fn container(table: Option<&Json>) -> Result<Container, String> {
+  let mut container = Container {
+    extension: "splx".to_string(),
+    radix: 16,
+    separator: ",".to_string(),
+    header: [HeaderField::Group, HeaderField::Task, HeaderField::Freq, HeaderField::Length],
+  };
+  let table = match table {
+    Some(table) => table,
+    None => return Ok(container),
+  };
+  keys(table, "container.", &["extension", "radix", "separator", "header"])?;
+
+  if let Some(extension) = string(table, "extension")? {
+    container.extension = extension;
+  }
+  if let Some(radix) = table.get("radix") {
+    container.radix = radix
+      .as_f64()
+      .filter(|radix| (2.0..=36.0).contains(radix) && radix.fract() == 0.0)
+      .ok_or("`container.radix` has to be from 2 to 36")? as u32;
+  }
+  if let Some(separator) = string(table, "separator")? {
+    if separator.is_empty() || separator.chars().any(|ch| ch.is_ascii_alphanumeric() || ch == '\n') {
+      return Err(format!("`{}` cannot separate words", separator));
+    }
+    container.separator = separator;
+  }
+  if let Some(header) = table.get("header") {
+    let fields: Vec<HeaderField> = strings(header, "container.header")?
+      .iter()
+      .map(|name| {
+        HEADER_FIELDS
+          .iter()
+          .find(|(candidate, _)| candidate == name)
+          .map(|(_, field)| *field)
+          .ok_or_else(|| format!("unknown header field `{}`", name))
+      })
+      .collect::<Result<_, _>>()?;
+
+    container.header = match fields.as_slice() {
+      [a, b, c, d] if HEADER_FIELDS.iter().all(|(_, field)| fields.contains(field)) => [*a, *b, *c, *d],
+      _ => return Err("`container.header` has to order group, task, freq and length".to_string()),
+    };
+  }
+
+  Ok(container)
+}
+
+fn registers(table: &Json) -> Result<RegisterFile, String> {
+  keys(table, "registers.", &["integer", "float", "float_base"])?;
+
+  let names = |key: &str| match table.get(key) {
+    Some(value) => strings(value, &format!("registers.{}", key)),
+    None => Err(format!("missing `registers.{}`", key)),
+  };
+  let integer = names("integer")?;
+  let float = names("float")?;
+  let float_base = match table.get("float_base") {
+    Some(value) => byte(value, "registers.float_base")?,
+    None => u8::try_from(integer.len()).map_err(|_| "too many integer registers".to_string())?,
+  };
+
+  if integer.is_empty() || float.is_empty() {
+    return Err("a target needs integer and float registers".to_string());
+  }
+  if integer.len() > usize::from(float_base) || usize::from(float_base) + float.len() > 256 {
+    return Err("the registers do not fit below and from `registers.float_base`".to_string());
+  }
+  for (index, name) in integer.iter().chain(&float).enumerate() {
+    if integer.iter().chain(&float).skip(index + 1).any(|other| other.eq_ignore_ascii_case(name)) {
+      return Err(format!("register `{}` is declared twice", name));
+    }
+  }
+
+  Ok(RegisterFile {
+    integer,
+    float,
+    float_base,
+  })
+}
+
+fn opcodes(table: &Json) -> Result<Vec<Opcode>, String> {
+  let mut opcodes = Vec::new();
+
+  for (mnemonic, value, entry) in entries(table, "opcodes", &["value", "operands", "prefixes"])? {
+    let path = format!("opcodes.{}", mnemonic);
+    let error = |message: &str| Err(format!("`{}` {}", path, message));
+
+    // the assembler tells instructions from data words by it
+    if !mnemonic.starts_with("OP_") {
+      return error("does not start with `OP_`");
+    }
+
+    let operands: Vec<Operand> = match entry.get("operands") {
+      None => Vec::new(),
+      Some(operands) => strings(operands, &format!("{}.operands", path))?
+        .iter()
+        .map(|name| {
+          OPERANDS
+            .iter()
+            .find(|(candidate, _)| candidate == name)
+            .map(|(_, operand)| *operand)
+            .ok_or_else(|| format!("unknown operand `{}` in `{}`", name, path))
+        })
+        .collect::<Result<_, _>>()?,
+    };
+    let prefixes: Vec<Prefix> = match entry.get("prefixes") {
+      None => Vec::new(),
+      Some(prefixes) => entries(prefixes, &format!("{}.prefixes", path), &["value", "selects"])?
+        .into_iter()
+        .map(|(mnemonic, value, entry)| {
+          let selects = selects(
+            entry,
+            &format!("{}.prefixes.{}", path, mnemonic),
+            &[Operand::Register, Operand::Address, Operand::Task],
+          )?;
+
+          Ok(Prefix { mnemonic, value, selects })
+        })
+        .collect::<Result<_, String>>()?,
+    };
+
+    let width: usize = operands.iter().map(|operand| operand.width()).sum();
+    if !operands.is_empty() && width != 3 {
+      return error("has to lay out the 3 bytes after the opcode");
+    }
+    // the linker patches the address into the last byte
+    if operands.iter().position(|operand| *operand == Operand::Data).is_some_and(|index| index + 1 != operands.len()) {
+      return error("can only have `data` as its last operand");
+    }
+    for (index, operand) in operands.iter().enumerate() {
+      let before = &operands[..index];
+      let missing = match operand {
+        Operand::Parameter | Operand::Action => !before.contains(&Operand::Instrument),
+        Operand::Selected => !before.contains(&Operand::Prefix) && !before.contains(&Operand::Operator),
+        _ => false,
+      };
+
+      if missing {
+        return error(&format!("needs an instrument, prefix or operator before `{}`", operand.name()));
+      }
+    }
+    if operands.contains(&Operand::Prefix) == prefixes.is_empty() {
+      return error("needs `prefixes` exactly when it has a `prefix` operand");
+    }
+
+    opcodes.push(Opcode {
+      mnemonic,
+      value,
+      operands,
+      prefixes,
+    });
+  }
+
+  Ok(opcodes)
+}
+
+fn operators(table: Option<&Json>) -> Result<Vec<Operator>, String> {
+  let table = match table {
+    Some(table) => table,
+    None => return Ok(Vec::new()),
+  };
+
+  entries(table, "operators", &["value", "comparison", "type", "selects"])?
+    .into_iter()
+    .map(|(mnemonic, value, entry)| {
+      let path = format!("operators.{}", mnemonic);
+      let comparison = match entry.get("comparison").and_then(Json::as_str) {
+        Some("==") => Comparison::Equal,
+        Some("<>") => Comparison::NotEqual,
+        Some(">") => Comparison::Greater,
+        Some("<") => Comparison::Less,
+        Some(">=") => Comparison::GreaterEqual,
+        Some("<=") => Comparison::LessEqual,
+        _ => return Err(format!("`{}.comparison` has to be one of ==, <>, >, <, >= and <=", path)),
+      };
+
+      Ok(Operator {
+        ty: ty(entry, &path, Type::I32)?,
+        selects: selects(entry, &path, &[Operand::Register, Operand::Task])?,
+        mnemonic,
+        value,
+        comparison,
+      })
+    })
+    .collect()
+}
+
+fn instruments(table: Option<&Json>) -> Result<Vec<Instrument>, String> {
+  let table = match table {
+    Some(table) => table,
+    None => return Ok(Vec::new()),
+  };
+
+  entries(table, "instruments", &["value", "parameters", "actions"])?
+    .into_iter()
+    .map(|(mnemonic, value, entry)| {
+      let path = format!("instruments.{}", mnemonic);
+
+      if !mnemonic.starts_with("INST_") {
+        return Err(format!("`{}` does not start with `INST_`", path));
+      }
+
+      let parameters = match entry.get("parameters") {
+        None => Vec::new(),
+        Some(parameters) => entries(parameters, &format!("{}.parameters", path), &["value", "type"])?
+          .into_iter()
+          .map(|(mnemonic, value, entry)| {
+            // instruments read floats unless told otherwise
+            Ok(Parameter {
+              ty: ty(entry, &format!("{}.parameters.{}", path, mnemonic), Type::F32)?,
+              mnemonic,
+              value,
+            })
+          })
+          .collect::<Result<_, String>>()?,
+      };
+      let actions = match entry.get("actions") {
+        None => Vec::new(),
+        Some(actions) => entries(actions, &format!("{}.actions", path), &["value"])?
+          .into_iter()
+          .map(|(mnemonic, value, _)| (mnemonic, value))
+          .collect(),
+      };
+
+      Ok(Instrument {
+        mnemonic,
+        value,
+        parameters,
+        actions,
+      })
+    })
+    .collect()
+}
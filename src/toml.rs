@@ -0,0 +1,313 @@
+//! A reader for the subset of TOML that manifests use: `[table]` headers,
+//! `key = value` pairs, dotted and quoted keys, strings, numbers, booleans,
+//! arrays and inline tables. Documents are read into the same tree as JSON,
+//! tables becoming objects with their keys in document order.
+
+use std::fmt;
+
+use crate::json::Json;
+
+#[derive(Debug)]
+pub struct TomlError {
+  /// 1-based line of the document.
+  pub line: usize,
+  pub message: String,
+}
+
+impl fmt::Display for TomlError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "line {}: {}", self.line, self.message)
+  }
+}
+
+impl std::error::Error for TomlError {}
+
+pub fn parse(text: &str) -> Result<Json, TomlError> {
+  let mut parser = Parser { text, offset: 0 };
+  let mut root = Json::Object(Vec::new());
+  let mut table: Vec<String> = Vec::new();
+
+  loop {
+    parser.blank(true);
+
+    match parser.peek() {
+      None => return Ok(root),
+      Some('[') => {
+        if parser.rest().starts_with("[[") {
+          return Err(parser.error("arrays of tables are not supported"));
+        }
+
+        parser.offset += 1;
+        parser.blank(false);
+        let path = parser.key()?;
+        parser.blank(false);
+        parser.expect("]")?;
+
+        match object(&mut root, &path) {
+          Some(_) => table = path,
+          None => return Err(parser.error(&format!("`{}` is not a table", path.join(".")))),
+        }
+      },
+      Some(_) => {
+        let key = parser.key()?;
+        parser.blank(false);
+        parser.expect("=")?;
+        parser.blank(false);
+        let value = parser.value()?;
+
+        let (name, parents) = key.split_last().expect("keys have at least one part");
+        let path: Vec<String> = table.iter().chain(parents).cloned().collect();
+        let members = object(&mut root, &path).ok_or_else(|| parser.error(&format!("`{}` is not a table", key.join("."))))?;
+
+        if members.iter().any(|(existing, _)| existing == name) {
+          return Err(parser.error(&format!("`{}` is defined twice", key.join("."))));
+        }
+        members.push((name.clone(), value));
+      },
+    }
+
+    // a value or a header ends its line
+    parser.blank(false);
+    match parser.peek() {
+      None | Some('\n') => {},
+      Some(_) => return Err(parser.error("expected the end of the line")),
+    }
+  }
+}
+
+/// The members of the table at `path`, created if missing; `None` if a value
+/// other than a table is in the way.
+fn object<'a>(root: &'a mut Json, path: &[String]) -> Option<&'a mut Vec<(String, Json)>> {
+  let mut members = match root {
+    Json::Object(members) => members,
+    _ => return None,
+  };
+
+  for name in path {
+    let index = match members.iter().position(|(existing, _)| existing == name) {
+      Some(index) => index,
+      None => {
+        members.push((name.clone(), Json::Object(Vec::new())));
+        members.len() - 1
+      },
+    };
+
+    members = match &mut members[index].1 {
+      Json::Object(members) => members,
+      _ => return None,
+    };
+  }
+
+  Some(members)
+}
+
+struct Parser<'a> {
+  text: &'a str,
+  offset: usize,
+}
+
+impl<'a> Parser<'a> {
+  fn error(&self, message: &str) -> TomlError {
+    TomlError {
+      line: self.text[..self.offset].matches('\n').count() + 1,
+      message: message.to_string(),
+    }
+  }
+
+  fn rest(&self) -> &'a str {
+    &self.text[self.offset..]
+  }
+
+  fn peek(&self) -> Option<char> {
+    self.rest().chars().next()
+  }
+
+  /// Skips spaces, tabs and comments, and newlines too with `newlines`.
+  fn blank(&mut self, newlines: bool) {
+    while let Some(ch) = self.peek() {
+      match ch {
+        ' ' | '\t' | '\r' => self.offset += 1,
+        '\n' if newlines => self.offset += 1,
+        '#' => self.offset += self.rest().find('\n').unwrap_or(self.rest().len()),
+        _ => break,
+      }
+    }
+  }
+
+  fn expect(&mut self, literal: &str) -> Result<(), TomlError> {
+    if self.rest().starts_with(literal) {
+      self.offset += literal.len();
+      Ok(())
+    }
+    else {
+      Err(self.error(&format!("expected `{}`", literal)))
+    }
+  }
+
+  /// `name`, `"quoted name"` or `a.b.c`.
+  fn key(&mut self) -> Result<Vec<String>, TomlError> {
+    let mut path = Vec::new();
+
+    loop {
+      let part = match self.peek() {
+        Some('"') => self.string()?,
+        Some('\'') => self.literal_string()?,
+        _ => {
+          let length = self
+            .rest()
+            .find(|ch: char| !(ch.is_ascii_alphanumeric() || ch == '_' || ch == '-'))
+            .unwrap_or(self.rest().len());
+
+          if length == 0 {
+            return Err(self.error("expected a key"));
+          }
+          self.offset += length;
+          self.text[self.offset - length..self.offset].to_string()
+        },
+      };
+
+      path.push(part);
+      self.blank(false);
+
+      if self.peek() != Some('.') {
+        return Ok(path);
+      }
+      self.offset += 1;
+      self.blank(false);
+    }
+  }
+
+  fn value(&mut self) -> Result<Json, TomlError> {
+    match self.peek() {
+      Some('"') => self.string().map(Json::String),
+      Some('\'') => self.literal_string().map(Json::String),
+      Some('t') => self.expect("true").map(|_| Json::Bool(true)),
+      Some('f') => self.expect("false").map(|_| Json::Bool(false)),
+      Some('[') => {
+        self.offset += 1;
+        let mut values = Vec::new();
+
+        loop {
+          self.blank(true);
+          if self.peek() == Some(']') {
+            self.offset += 1;
+            return Ok(Json::Array(values));
+          }
+
+          values.push(self.value()?);
+          self.blank(true);
+
+          match self.peek() {
+            Some(',') => self.offset += 1,
+            Some(']') => {},
+            _ => return Err(self.error("expected `,` or `]`")),
+          }
+        }
+      },
+      Some('{') => {
+        self.offset += 1;
+        let mut table = Json::Object(Vec::new());
+
+        self.blank(false);
+        if self.peek() == Some('}') {
+          self.offset += 1;
+          return Ok(table);
+        }
+
+        loop {
+          self.blank(false);
+          let key = self.key()?;
+          self.expect("=")?;
+          self.blank(false);
+          let value = self.value()?;
+
+          let (name, parents) = key.split_last().expect("keys have at least one part");
+          let members = object(&mut table, parents).ok_or_else(|| self.error(&format!("`{}` is not a table", key.join("."))))?;
+          if members.iter().any(|(existing, _)| existing == name) {
+            return Err(self.error(&format!("`{}` is defined twice", key.join("."))));
+          }
+          members.push((name.clone(), value));
+          self.blank(false);
+
+          match self.peek() {
+            Some(',') => self.offset += 1,
+            Some('}') => {
+              self.offset += 1;
+              return Ok(table);
+            },
+            _ => return Err(self.error("expected `,` or `}`")),
+          }
+        }
+      },
+      Some(ch) if ch == '+' || ch == '-' || ch.is_ascii_digit() => {
+        let length = self
+          .rest()
+          .find(|ch: char| !(ch.is_ascii_digit() || "+-._eE".contains(ch)))
+          .unwrap_or(self.rest().len());
+        let number = self.text[self.offset..self.offset + length].replace('_', "");
+        let value = number.parse().map_err(|_| self.error(&format!("invalid number `{}`", number)))?;
+
+        self.offset += length;
+        Ok(Json::Number(value))
+      },
+      Some(_) => Err(self.error("expected a value")),
+      None => Err(self.error("unexpected end of the document")),
+    }
+  }
+
+  fn string(&mut self) -> Result<String, TomlError> {
+    self.expect("\"")?;
+    let mut value = String::new();
+
+    loop {
+      let ch = match self.peek() {
+        Some('\n') | None => return Err(self.error("unterminated string")),
+        Some(ch) => ch,
+      };
+      self.offset += ch.len_utf8();
+
+      match ch {
+        '"' => return Ok(value),
+        '\\' => {
+          let escape = self.peek().ok_or_else(|| self.error("unterminated string"))?;
+          self.offset += 1;
+
+          match escape {
+            '"' | '\\' => value.push(escape),
+            'b' => value.push('\u{8}'),
+            'f' => value.push('\u{c}'),
+            'n' => value.push('\n'),
+            'r' => value.push('\r'),
+            't' => value.push('\t'),
+            'u' => {
+              let code = self
+                .text
+                .get(self.offset..self.offset + 4)
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                .ok_or_else(|| self.error("invalid \\u escape"))?;
+              self.offset += 4;
+              value.push(char::from_u32(code).unwrap_or('\u{fffd}'));
+            },
+            _ => return Err(self.error("invalid escape")),
+          }
+        },
+        ch => value.push(ch),
+      }
+    }
+  }
+
+  /// `'...'`, without escapes.
+  fn literal_string(&mut self) -> Result<String, TomlError> {
+    self.expect("'")?;
+
+    match self.rest().find(['\'', '\n']) {
+      Some(length) if self.rest()[length..].starts_with('\'') => {
+        let value = self.rest()[..length].to_string();
+
+        self.offset += length + 1;
+        Ok(value)
+      },
+      _ => Err(self.error("unterminated string")),
+    }
+  }
+}
@@ -0,0 +1,77 @@
+use std::env;
+use std::fs;
+use std::process;
+
+use splice::vm::cost::{Budget, CostTable, Report};
+use splice::vm::loader;
+
+const USAGE: &str = "Usage: splcost [--target <name> | --costs <file>] [--budget <file>] <file.splx|file.splc>...
+
+Estimates the cost of one run of every task from a per-opcode cost table:
+instruction count, worst-case execution time, OP_ACT actions (camera shots,
+ADCS mode changes...) and data words. The targets `reference` (the default)
+and `opssat` are built in; --costs reads `MNEMONIC MICROSECONDS` lines for
+OP_* and INST_* mnemonics over the table of a first `target <name>` line.
+
+--budget reads `GROUP LIMIT VALUE` lines, GROUP being a group id or `*` and
+LIMIT one of instructions, wcet (µs), ram (words), actions or an INST_*
+mnemonic, checked against the sum over the tasks of every group. Exits with 1
+when a group is over budget.";
+
+fn fail(message: String) -> ! {
+    eprintln!("{}", message);
+    process::exit(1);
+}
+
+fn read(path: &str) -> String
+{
+    fs::read_to_string(path).unwrap_or_else(|err| fail(format!("{}: {}", path, err)))
+}
+
+fn main()
+{
+    let mut table = None;
+    let mut budgets = Vec::new();
+    let mut inputs = Vec::new();
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next()
+    {
+        match arg.as_str()
+        {
+            "--target" => {
+                let name = args.next().unwrap_or_else(|| fail(USAGE.to_string()));
+                table = Some(CostTable::builtin(&name).unwrap_or_else(|| fail(format!("unknown target `{}`", name))));
+            },
+            "--costs" => {
+                let path = args.next().unwrap_or_else(|| fail(USAGE.to_string()));
+                table = Some(CostTable::parse(&read(&path)).unwrap_or_else(|err| fail(format!("{}: {}", path, err))));
+            },
+            "--budget" => {
+                let path = args.next().unwrap_or_else(|| fail(USAGE.to_string()));
+                budgets.extend(Budget::parse(&read(&path)).unwrap_or_else(|err| fail(format!("{}: {}", path, err))));
+            },
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            },
+            _ if arg.starts_with('-') => fail(USAGE.to_string()),
+            _ => inputs.push(arg),
+        }
+    }
+
+    if inputs.is_empty()
+    {
+        fail(USAGE.to_string());
+    }
+
+    let table = table.unwrap_or_else(|| CostTable::builtin("reference").expect("the reference target is built in"));
+    let tasks = loader::load(&inputs).unwrap_or_else(|err| fail(err));
+    let report = Report::new(&tasks, &table, &budgets);
+
+    print!("{}", report);
+    if !report.violations.is_empty()
+    {
+        process::exit(1);
+    }
+}
@@ -0,0 +1,77 @@
+use std::env;
+use std::io::{self, BufRead, Write};
+use std::process;
+
+use splice::vm::{loader, Debugger, Machine};
+
+const USAGE: &str = "Usage: spldbg [-t <seconds>] <file.splx|file.splc>...
+
+Loads the tasks like splvm and stops before the first one runs, over a timeline
+of the given number of seconds (60 by default). Tasks assembled from .splc
+files show the source line of every instruction. Type `help` for commands.";
+
+fn fail(message: String) -> ! {
+    eprintln!("{}", message);
+    process::exit(1);
+}
+
+fn main()
+{
+    let mut duration = 60;
+    let mut inputs = Vec::new();
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next()
+    {
+        match arg.as_str()
+        {
+            "-t" => {
+                duration = args.next()
+                    .and_then(|seconds| seconds.parse().ok())
+                    .unwrap_or_else(|| fail(USAGE.to_string()));
+            },
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            },
+            _ if arg.starts_with('-') => fail(USAGE.to_string()),
+            _ => inputs.push(arg),
+        }
+    }
+
+    if inputs.is_empty()
+    {
+        fail(USAGE.to_string());
+    }
+
+    let tasks = loader::load(&inputs).unwrap_or_else(|err| fail(err));
+    let machine = Machine::new(tasks).unwrap_or_else(|err| fail(err));
+    let mut debugger = Debugger::new(machine, duration);
+
+    let stdin = io::stdin();
+    let mut stdout = io::stdout();
+    let mut lines = stdin.lock().lines();
+
+    loop
+    {
+        stdout.write_all(b"(spldbg) ").expect("Couldn't write to stdout");
+        stdout.flush().expect("Couldn't flush stdout");
+
+        let line = match lines.next()
+        {
+            Some(Ok(line)) => line,
+            _ => break,
+        };
+
+        match line.trim()
+        {
+            "quit" | "q" => break,
+            command => match debugger.command(command)
+            {
+                Ok(output) if output.is_empty() => {},
+                Ok(output) => println!("{}", output),
+                Err(message) => println!("error: {}", message),
+            }
+        }
+    }
+}
@@ -0,0 +1,86 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process;
+
+use splice::asm;
+use splice::debug;
+use splice::link::Linker;
+use splice::target::Target;
+
+const USAGE: &str = "Usage: splink [--target <name|file.toml>] -o <output.splx> [-m <output.map>] <file.splc>...
+
+Assembles every task for the target, OPS-SAT by default, checks that no (group, task) pair is defined twice and
+that all cross-task references resolve, then writes the tasks ordered by group
+and task id into a single .splx file. The link map is written next to the
+output file with a .map extension unless -m is given.";
+
+fn fail(message: String) -> ! {
+    eprintln!("{}", message);
+    process::exit(1);
+}
+
+fn main()
+{
+    let mut output = None;
+    let mut map = None;
+    let mut target = None;
+    let mut inputs = Vec::new();
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next()
+    {
+        match arg.as_str()
+        {
+            "-o" => output = args.next(),
+            "-m" => map = args.next(),
+            "--target" => target = args.next(),
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            },
+            _ => inputs.push(arg),
+        }
+    }
+
+    let output = match output
+    {
+        Some(output) if !inputs.is_empty() => output,
+        _ => {
+            println!("{}", USAGE);
+            process::exit(1);
+        }
+    };
+    let map = map.unwrap_or_else(|| Path::new(&output).with_extension("map").display().to_string());
+
+    let target = Target::load(target.as_deref().unwrap_or("opssat")).unwrap_or_else(|err| fail(err));
+
+    let mut linker = Linker::for_target(&target);
+    for input in &inputs
+    {
+        let task = asm::assemble_file_for(&target, Path::new(input)).unwrap_or_else(|err| fail(err.to_string()));
+        linker.add(input, task);
+    }
+
+    let image = match linker.link()
+    {
+        Ok(image) => image,
+        Err(errors) => {
+            for error in &errors
+            {
+                eprintln!("error: {}", error);
+            }
+            fail(format!("linking failed with {} error(s)", errors.len()));
+        }
+    };
+
+    fs::write(&output, image.to_splx()).unwrap_or_else(|err| fail(format!("{}: {}", output, err)));
+    fs::write(&map, image.map()).unwrap_or_else(|err| fail(format!("{}: {}", map, err)));
+
+    if let Some(debug) = image.debug()
+    {
+        let path = debug::sidecar(Path::new(&output));
+
+        fs::write(&path, debug.to_string()).unwrap_or_else(|err| fail(format!("{}: {}", path.display(), err)));
+    }
+}
@@ -0,0 +1,85 @@
+use std::env;
+use std::fs;
+use std::process;
+
+use splice::vm::decode::{self, Decoder};
+use splice::vm::{loader, Machine};
+
+const USAGE: &str = "Usage: splout [--csv | --json] <dump> <file.splx|file.splc>...
+
+Decodes an OP_STR output dump, one `TIME GROUP.TASK WORD BITS` line per value
+as written by `splvm --output file.dump`, against the tasks that produced it.
+Every value is tagged with its task, word, format (integer, float or binary)
+and register, and, for tasks assembled from .splc files or with a .spld debug
+sidecar, with the variable or instrument parameter it holds and its source
+line, the .spl one when there is a sidecar. Prints a table, or CSV or JSON.";
+
+fn fail(message: String) -> ! {
+    eprintln!("{}", message);
+    process::exit(1);
+}
+
+fn main()
+{
+    let mut csv = false;
+    let mut json = false;
+    let mut inputs = Vec::new();
+
+    for arg in env::args().skip(1)
+    {
+        match arg.as_str()
+        {
+            "--csv" => csv = true,
+            "--json" => json = true,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            },
+            _ if arg.starts_with('-') => fail(USAGE.to_string()),
+            _ => inputs.push(arg),
+        }
+    }
+
+    if inputs.len() < 2 || (csv && json)
+    {
+        fail(USAGE.to_string());
+    }
+
+    let path = inputs.remove(0);
+    let dump = fs::read_to_string(&path).unwrap_or_else(|err| fail(format!("{}: {}", path, err)));
+    let tasks = loader::load(&inputs).unwrap_or_else(|err| fail(err));
+    let machine = Machine::new(tasks).unwrap_or_else(|err| fail(err));
+    let records = Decoder::new(&machine.tasks)
+        .decode_dump(&dump)
+        .unwrap_or_else(|err| fail(format!("{}: {}", path, err)));
+
+    if csv
+    {
+        print!("{}", decode::to_csv(&records));
+    }
+    else if json
+    {
+        println!("{}", decode::to_json(&records));
+    }
+    else
+    {
+        println!(
+            "{:>6}  {:<5}  {:>4}  {:<7}  {:<7}  {:<12}  {:<14}  LOCATION",
+            "TIME", "TASK", "WORD", "FORMAT", "REG", "VARIABLE", "VALUE"
+        );
+        for record in &records
+        {
+            println!(
+                "{:>5}s  {:<5}  {:>4}  {:<7}  {:<7}  {:<12}  {:<14}  {}",
+                record.time,
+                format!("{}.{}", record.group_id, record.task_id),
+                record.word,
+                record.format,
+                record.register,
+                record.variable.as_deref().unwrap_or("-"),
+                record.value.to_string(),
+                record.location.as_deref().unwrap_or("-")
+            );
+        }
+    }
+}
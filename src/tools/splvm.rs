@@ -0,0 +1,151 @@
+use std::cell::RefCell;
+use std::env;
+use std::fs;
+use std::process;
+use std::rc::Rc;
+
+use splice::asm::isa::{name_of, ACTIONS, INST_GPS, INST_IMG};
+use splice::vm::decode::{self, Decoder};
+use splice::vm::instruments::{Camera, Gps};
+use splice::vm::orbit::{Elements, Orbit};
+use splice::vm::telemetry::Telemetry;
+use splice::vm::trace::{self, Trace};
+use splice::vm::{loader, Machine, Scheduler, Status};
+
+const USAGE: &str = "Usage: splvm [-t <seconds>] [--orbit <file.tle>] [--telemetry <file.csv|file.json>]...
+             [--record <trace> | --replay <trace>] [--output <file.csv|file.json|file.dump>]
+             <file.splx|file.splc>...
+
+Loads the tasks of every .splx file, plus the .splc files assembled and linked
+together, and runs them on the scheduler for the given number of one second
+ticks (1 by default, which runs every task once). Every run is logged with its
+status and OP_STR output, followed by the capture requests of the simulated
+camera and, over more than one tick, a per-task summary.
+
+--orbit moves the simulated GPS along the orbit of a two-line element set,
+propagated with J2 from its epoch, which is mission time 0.
+
+--telemetry serves instrument reads from timestamped readings, interpolated
+at mission time, the first timestamp of the file being mission time 0.
+
+--record writes every executed instruction, register and data write,
+instrument read and output to a trace file. --replay runs the tasks again over
+the timeline of a trace, with the instruments serving the values it recorded,
+and fails at the first event that differs from the trace.
+
+--output writes the OP_STR output tagged with its task, word, format and the
+variable it holds, as CSV, JSON or the raw dump read by splout.";
+
+fn fail(message: String) -> ! {
+    eprintln!("{}", message);
+    process::exit(1);
+}
+
+fn main()
+{
+    let mut duration = 1;
+    let mut record = None;
+    let mut replay = None;
+    let mut telemetry = Vec::new();
+    let mut orbit = None;
+    let mut output = None;
+    let mut inputs = Vec::new();
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next()
+    {
+        match arg.as_str()
+        {
+            "-t" => {
+                duration = args.next()
+                    .and_then(|seconds| seconds.parse().ok())
+                    .unwrap_or_else(|| fail(USAGE.to_string()));
+            },
+            "--orbit" => orbit = Some(args.next().unwrap_or_else(|| fail(USAGE.to_string()))),
+            "--telemetry" => telemetry.push(args.next().unwrap_or_else(|| fail(USAGE.to_string()))),
+            "--record" => record = Some(args.next().unwrap_or_else(|| fail(USAGE.to_string()))),
+            "--output" => output = Some(args.next().unwrap_or_else(|| fail(USAGE.to_string()))),
+            "--replay" => replay = Some(args.next().unwrap_or_else(|| fail(USAGE.to_string()))),
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            },
+            _ if arg.starts_with('-') => fail(USAGE.to_string()),
+            _ => inputs.push(arg),
+        }
+    }
+
+    if inputs.is_empty() || (record.is_some() && replay.is_some())
+    {
+        fail(USAGE.to_string());
+    }
+
+    let tasks = loader::load(&inputs).unwrap_or_else(|err| fail(err));
+
+    let camera = Rc::new(RefCell::new(Camera::default()));
+    let mut machine = Machine::new(tasks).unwrap_or_else(|err| fail(err));
+    machine.attach(INST_IMG, Box::new(camera.clone()));
+    if let Some(path) = &orbit
+    {
+        let text = fs::read_to_string(path).unwrap_or_else(|err| fail(format!("{}: {}", path, err)));
+        let elements = Elements::from_tle(&text).unwrap_or_else(|err| fail(format!("{}: {}", path, err)));
+        machine.attach(INST_GPS, Box::new(Gps::with_orbit(Orbit::new(elements))));
+    }
+    for path in &telemetry
+    {
+        let text = fs::read_to_string(path).unwrap_or_else(|err| fail(format!("{}: {}", path, err)));
+        let parsed = if path.ends_with(".json") { Telemetry::parse_json(&text) } else { Telemetry::parse_csv(&text) };
+        parsed.unwrap_or_else(|err| fail(format!("{}: {}", path, err))).attach(&mut machine);
+    }
+
+    let mut scheduler = Scheduler::new(machine);
+    if let Some(path) = &replay
+    {
+        let text = fs::read_to_string(path).unwrap_or_else(|err| fail(format!("{}: {}", path, err)));
+        let recorded = Trace::parse(&text).unwrap_or_else(|err| fail(format!("{}: {}", path, err)));
+        duration = recorded.duration;
+        trace::replay(&mut scheduler, &recorded).unwrap_or_else(|err| fail(format!("{}: {}", path, err)));
+    }
+    else if let Some(path) = &record
+    {
+        let recorded = trace::record(&mut scheduler, duration);
+        fs::write(path, recorded.to_text()).unwrap_or_else(|err| fail(format!("{}: {}", path, err)));
+    }
+    else
+    {
+        scheduler.run_for(duration);
+    }
+
+    for entry in &scheduler.log
+    {
+        println!("{}", entry);
+        for output in &scheduler.machine.output[entry.output.clone()]
+        {
+            println!("{:>6}s  {}.{}: {}", output.time, output.group_id, output.task_id, output.value);
+        }
+    }
+    if let Some(path) = &output
+    {
+        let records = Decoder::new(&scheduler.machine.tasks)
+            .decode(&scheduler.machine.output)
+            .unwrap_or_else(|err| fail(err));
+        let text = if path.ends_with(".csv") { decode::to_csv(&records) }
+            else if path.ends_with(".json") { decode::to_json(&records).to_string() }
+            else { decode::to_dump(&records) };
+        fs::write(path, text).unwrap_or_else(|err| fail(format!("{}: {}", path, err)));
+    }
+    for capture in &camera.borrow().captures
+    {
+        let format = name_of(ACTIONS, "A_IMG_DO_", capture.format).map_or("?", |name| &name["A_IMG_DO_".len()..]);
+        println!("capture {} {} at {}s, exposure {:?}s", capture.number, format, capture.time, capture.exposure);
+    }
+    if duration > 1
+    {
+        print!("\n{}", scheduler.summary());
+    }
+
+    if scheduler.log.iter().any(|entry| entry.status == Status::Fault)
+    {
+        process::exit(1);
+    }
+}
@@ -0,0 +1,346 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::asm::isa::*;
+use crate::asm::Task;
+use crate::vm::scheduler::Frequency;
+
+/// Microseconds per instruction on a target, plus the bus time of every
+/// `OP_GET`, `OP_SET` and `OP_ACT` on an instrument.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CostTable {
+  pub target: String,
+  /// Indexed by opcode.
+  pub opcodes: [u32; 16],
+  pub instruments: BTreeMap<u8, u32>,
+}
+
+impl CostTable {
+  /// The tables of the targets known by name: `reference`, 1 µs for every
+  /// instruction, and `opssat`, estimates for the OPS-SAT experimenter
+  /// platform until they are replaced by measurements.
+  pub fn builtin(target: &str) -> Option<CostTable> {
+    let (opcodes, instruments): ([u32; 16], &[(u8, u32)]) = match target {
+      "reference" => ([1; 16], &[]),
+      "opssat" => (
+        // NOP MOV LEA CMP SET GET ACT HLT STR FMA FSD SIN COS TAN POW NOR
+        [2, 4, 6, 5, 20, 20, 40, 2, 30, 8, 12, 40, 40, 40, 60, 4],
+        &[
+          (INST_ADC, 2_000),
+          (INST_GPS, 1_000),
+          (INST_IMG, 5_000),
+          (INST_NMF, 500),
+          (INST_SDR, 5_000),
+        ],
+      ),
+      _ => return None,
+    };
+
+    Some(CostTable {
+      target: target.to_string(),
+      opcodes,
+      instruments: instruments.iter().copied().collect(),
+    })
+  }
+
+  /// Parses one `MNEMONIC MICROSECONDS` cost per line, for `OP_*` and
+  /// `INST_*` mnemonics, overriding a built-in table given by a first
+  /// `target NAME` line (`reference` by default). `#` starts a comment.
+  pub fn parse(text: &str) -> Result<CostTable, String> {
+    let mut table = CostTable::builtin("reference").expect("the reference target is built in");
+    let mut entries = 0;
+
+    for (index, line) in text.lines().enumerate() {
+      let line = line.split('#').next().unwrap_or("").trim();
+      if line.is_empty() {
+        continue;
+      }
+
+      let error = |message: String| format!("line {}: {}", index + 1, message);
+      let fields: Vec<&str> = line.split_whitespace().collect();
+      let (key, value) = match fields.as_slice() {
+        [key, value] => (*key, *value),
+        _ => return Err(error("expected `MNEMONIC MICROSECONDS`".to_string())),
+      };
+
+      if key == "target" {
+        if entries > 0 {
+          return Err(error("`target` has to come before the costs".to_string()));
+        }
+        table = CostTable::builtin(value).ok_or_else(|| error(format!("unknown target `{}`", value)))?;
+        continue;
+      }
+
+      let value: u32 = value
+        .parse()
+        .map_err(|_| error(format!("invalid cost `{}`", value)))?;
+
+      entries += 1;
+      if let Some(opcode) = lookup(OPCODES, key) {
+        table.opcodes[usize::from(opcode)] = value;
+      }
+      else if let Some(instrument) = lookup(INSTRUMENTS, key) {
+        table.instruments.insert(instrument, value);
+      }
+      else {
+        return Err(error(format!("unknown mnemonic `{}`", key)));
+      }
+    }
+
+    Ok(table)
+  }
+
+  /// The cost of an instruction, instrument access included.
+  pub fn cost(&self, word: Word) -> u32 {
+    let [opcode, instrument, _, _] = unpack32to4x8(word);
+    let access = match opcode {
+      OP_GET | OP_SET | OP_ACT => self.instruments.get(&instrument).copied().unwrap_or(0),
+      _ => 0,
+    };
+
+    self.opcodes.get(usize::from(opcode)).copied().unwrap_or(0) + access
+  }
+}
+
+/// The static cost of one run of a task.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TaskCost {
+  pub group_id: u8,
+  pub task_id: u8,
+  /// `None` for a header byte that is no frequency.
+  pub freq: Option<Frequency>,
+  /// Code words, `OP_HLT` included.
+  pub instructions: usize,
+  /// Worst-case execution time in µs. The ISA has no jumps, so the worst case
+  /// runs every instruction; a false `OP_CMP` only shortens a run.
+  pub wcet: u64,
+  /// Data words of the task.
+  pub ram: usize,
+  /// `OP_ACT` instructions by instrument and action.
+  pub actions: BTreeMap<(u8, u8), usize>,
+}
+
+impl TaskCost {
+  pub fn new(task: &Task, table: &CostTable) -> TaskCost {
+    let mut actions = BTreeMap::new();
+
+    for word in &task.code {
+      let [opcode, instrument, action, _] = unpack32to4x8(*word);
+
+      if opcode == OP_ACT {
+        *actions.entry((instrument, action)).or_insert(0) += 1;
+      }
+    }
+
+    TaskCost {
+      group_id: task.header.group_id,
+      task_id: task.header.task_id,
+      freq: Frequency::from_byte(task.header.freq),
+      instructions: task.code.len(),
+      wcet: task.code.iter().map(|word| u64::from(table.cost(*word))).sum(),
+      ram: task.data.len(),
+      actions,
+    }
+  }
+
+  /// Actions on `instrument`, or on any instrument.
+  pub fn action_count(&self, instrument: Option<u8>) -> usize {
+    self
+      .actions
+      .iter()
+      .filter(|((id, _), _)| instrument.is_none() || instrument == Some(*id))
+      .map(|(_, count)| count)
+      .sum()
+  }
+}
+
+/// What a budget limits, summed over the tasks of a group.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Limit {
+  Instructions,
+  /// Worst-case execution time in µs of one run of every task.
+  Wcet,
+  Ram,
+  /// `OP_ACT` instructions, on one instrument or on any.
+  Actions(Option<u8>),
+}
+
+impl Limit {
+  /// `instructions`, `wcet`, `ram`, `actions` or an `INST_*` mnemonic for the
+  /// actions on that instrument.
+  pub fn parse(name: &str) -> Option<Limit> {
+    match name {
+      "instructions" => Some(Limit::Instructions),
+      "wcet" => Some(Limit::Wcet),
+      "ram" => Some(Limit::Ram),
+      "actions" => Some(Limit::Actions(None)),
+      _ => lookup(INSTRUMENTS, name).map(|instrument| Limit::Actions(Some(instrument))),
+    }
+  }
+}
+
+impl fmt::Display for Limit {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Limit::Instructions => write!(f, "instructions"),
+      Limit::Wcet => write!(f, "wcet"),
+      Limit::Ram => write!(f, "ram"),
+      Limit::Actions(None) => write!(f, "actions"),
+      Limit::Actions(Some(instrument)) => write!(f, "{}", name_of(INSTRUMENTS, "INST_", *instrument).unwrap_or("?")),
+    }
+  }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Budget {
+  /// `None` for every group.
+  pub group_id: Option<u8>,
+  pub limit: Limit,
+  pub value: u64,
+}
+
+impl Budget {
+  /// Parses one `GROUP LIMIT VALUE` budget per line, `GROUP` being a group id
+  /// or `*` for every group and `LIMIT` one of `instructions`, `wcet` (µs),
+  /// `ram` (words), `actions` or an `INST_*` mnemonic for the actions on that
+  /// instrument. `#` starts a comment.
+  pub fn parse(text: &str) -> Result<Vec<Budget>, String> {
+    let mut budgets = Vec::new();
+
+    for (index, line) in text.lines().enumerate() {
+      let line = line.split('#').next().unwrap_or("").trim();
+      if line.is_empty() {
+        continue;
+      }
+
+      let error = |message: String| format!("line {}: {}", index + 1, message);
+      let fields: Vec<&str> = line.split_whitespace().collect();
+      let (group, limit, value) = match fields.as_slice() {
+        [group, limit, value] => (*group, *limit, *value),
+        _ => return Err(error("expected `GROUP LIMIT VALUE`".to_string())),
+      };
+
+      budgets.push(Budget {
+        group_id: match group {
+          "*" => None,
+          _ => Some(group.parse().map_err(|_| error(format!("invalid group `{}`", group)))?),
+        },
+        limit: Limit::parse(limit).ok_or_else(|| error(format!("unknown limit `{}`", limit)))?,
+        value: value.parse().map_err(|_| error(format!("invalid value `{}`", value)))?,
+      });
+    }
+
+    Ok(budgets)
+  }
+}
+
+/// A group over one of its budgets.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Violation {
+  pub group_id: u8,
+  pub limit: Limit,
+  pub value: u64,
+  pub budget: u64,
+}
+
+impl fmt::Display for Violation {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let unit = match self.limit {
+      Limit::Wcet => "µs",
+      _ => "",
+    };
+
+    write!(
+      f,
+      "group {}: {} is {}{}, over the budget of {}{}",
+      self.group_id, self.limit, self.value, unit, self.budget, unit
+    )
+  }
+}
+
+/// The cost of every task and the budgets they break.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Report {
+  pub target: String,
+  pub tasks: Vec<TaskCost>,
+  pub violations: Vec<Violation>,
+}
+
+impl Report {
+  pub fn new(tasks: &[Task], table: &CostTable, budgets: &[Budget]) -> Report {
+    let mut costs: Vec<TaskCost> = tasks.iter().map(|task| TaskCost::new(task, table)).collect();
+    costs.sort_by_key(|cost| (cost.group_id, cost.task_id));
+
+    let mut groups: Vec<u8> = costs.iter().map(|cost| cost.group_id).collect();
+    groups.dedup();
+
+    let mut violations = Vec::new();
+    for group_id in groups {
+      let members: Vec<&TaskCost> = costs.iter().filter(|cost| cost.group_id == group_id).collect();
+
+      for budget in budgets.iter().filter(|budget| budget.group_id.is_none() || budget.group_id == Some(group_id)) {
+        let value: u64 = members
+          .iter()
+          .map(|cost| match budget.limit {
+            Limit::Instructions => cost.instructions as u64,
+            Limit::Wcet => cost.wcet,
+            Limit::Ram => cost.ram as u64,
+            Limit::Actions(instrument) => cost.action_count(instrument) as u64,
+          })
+          .sum();
+
+        if value > budget.value {
+          violations.push(Violation {
+            group_id,
+            limit: budget.limit,
+            value,
+            budget: budget.value,
+          });
+        }
+      }
+    }
+
+    Report {
+      target: table.target.clone(),
+      tasks: costs,
+      violations,
+    }
+  }
+}
+
+impl fmt::Display for Report {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    writeln!(f, "target {}", self.target)?;
+    writeln!(f, "{:<7}{:<8}{:>6}{:>12}{:>6}  ACTIONS", "TASK", "FREQ", "INSTR", "WCET", "RAM")?;
+
+    for cost in &self.tasks {
+      let actions: Vec<String> = cost
+        .actions
+        .iter()
+        .map(|((instrument, action), count)| {
+          let prefix = format!("A_{}_", name_of(INSTRUMENTS, "INST_", *instrument).map_or("", |name| &name[5..]));
+
+          format!("{}x {}", count, name_of(ACTIONS, &prefix, *action).unwrap_or("?"))
+        })
+        .collect();
+
+      let line = format!(
+        "{:<7}{:<8}{:>6}{:>10}µs{:>6}  {}",
+        format!("{}.{}", cost.group_id, cost.task_id),
+        cost.freq.map_or_else(|| "?".to_string(), |freq| freq.to_string()),
+        cost.instructions,
+        cost.wcet,
+        cost.ram,
+        actions.join(", ")
+      );
+
+      writeln!(f, "{}", line.trim_end())?;
+    }
+
+    for violation in &self.violations {
+      writeln!(f, "error: {}", violation)?;
+    }
+
+    Ok(())
+  }
+}
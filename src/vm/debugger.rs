@@ -0,0 +1,516 @@
+use std::fmt;
+
+use crate::asm::isa::*;
+use crate::asm::preprocess::Location;
+use crate::asm::Word;
+use crate::vm::machine::{Machine, TaskState};
+use crate::vm::registers::{Registers, FREG_BASE, REGISTER_COUNT};
+use crate::vm::scheduler::{Event, Scheduler};
+
+pub const HELP: &str = "\
+step [N]               (s) execute N instructions, 1 by default
+continue               (c) run until a breakpoint, a watchpoint or the end of the timeline
+finish                 run until the current task ends
+break G.T WORD         (b) stop before word WORD of task G.T (the header is word 0)
+break FILE:LINE        stop before the instructions of a source line, .splc or .spl
+watch G.T ADDRESS      (w) stop when a data word of task G.T changes, also by variable name
+watch INST_* P_*       stop when OP_SET writes an instrument property
+delete N               remove breakpoint or watchpoint N
+info                   list breakpoints and watchpoints
+where                  show the next instruction
+regs                   show the integer and float registers
+ram G.T                show the data words of task G.T
+output                 show the OP_STR output so far
+log                    show the task runs so far
+quit                   (q) leave the debugger";
+
+#[derive(Clone, Debug, PartialEq)]
+enum Point {
+  Word { group_id: u8, task_id: u8, word: usize },
+  Line { file: String, line: usize },
+  Data { group_id: u8, task_id: u8, address: u8 },
+  Property { instrument: u8, param: u8 },
+}
+
+impl fmt::Display for Point {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Point::Word { group_id, task_id, word } => write!(f, "break {}.{} word {}", group_id, task_id, word),
+      Point::Line { file, line } => write!(f, "break {}:{}", file, line),
+      Point::Data {
+        group_id,
+        task_id,
+        address,
+      } => write!(f, "watch {}.{} address {}", group_id, task_id, address),
+      Point::Property { instrument, param } => {
+        let instrument = name_of(INSTRUMENTS, "INST_", *instrument).unwrap_or("?");
+        let prefix = format!("P_{}_", instrument.trim_start_matches("INST_"));
+
+        write!(
+          f,
+          "watch {} {}",
+          instrument,
+          name_of(PARAMETERS, &prefix, *param).unwrap_or("?")
+        )
+      },
+    }
+  }
+}
+
+/// Interactive control of a scheduler: every command of `HELP` but `quit`
+/// is handled by `command`, which returns the text to show.
+#[derive(Debug)]
+pub struct Debugger {
+  pub scheduler: Scheduler,
+  /// The mission time at which the timeline ends.
+  pub end: u32,
+  points: Vec<(usize, Point)>,
+  next_id: usize,
+}
+
+fn parse_task(text: &str) -> Result<(u8, u8), String> {
+  text
+    .split_once('.')
+    .and_then(|(group, task)| Some((group.parse().ok()?, task.parse().ok()?)))
+    .ok_or_else(|| format!("expected a task as GROUP.TASK, found `{}`", text))
+}
+
+fn parse_number<T: std::str::FromStr>(text: Option<&str>, what: &str) -> Result<T, String> {
+  text
+    .and_then(|text| text.parse().ok())
+    .ok_or_else(|| format!("expected {}", what))
+}
+
+fn matches_line(location: &Location, file: &str, line: usize) -> bool {
+  (location.line == line && location.file.ends_with(file))
+    || location
+      .expanded_from
+      .as_ref()
+      .is_some_and(|invocation| matches_line(invocation, file, line))
+}
+
+/// Whether word `word` of `task` comes from line `line` of `file`, either as
+/// a `.splc` line or, through its debug information, as a `.spl` one. Only
+/// the first of the words a `.spl` line compiles to counts.
+fn at_line(task: &TaskState, word: usize, file: &str, line: usize) -> bool {
+  let assembled = task
+    .sources
+    .get(word)
+    .is_some_and(|source| matches_line(&source.location, file, line));
+  let compiled = task.debug.as_ref().is_some_and(|debug| {
+    let on_line = |word: usize| debug.span(word).is_some_and(|span| span.start.line + 1 == line);
+
+    debug.file.ends_with(file) && on_line(word) && !on_line(word - 1)
+  });
+
+  assembled || compiled
+}
+
+fn format_word(word: Word) -> String {
+  format!("{:#010x} ({}, {:?})", word, word as i32, f32::from_bits(word))
+}
+
+impl Debugger {
+  /// Debugs `machine` over a timeline of `duration` seconds.
+  pub fn new(machine: Machine, duration: u32) -> Debugger {
+    Debugger {
+      end: machine.time + duration,
+      scheduler: Scheduler::new(machine),
+      points: Vec::new(),
+      next_id: 1,
+    }
+  }
+
+  pub fn command(&mut self, line: &str) -> Result<String, String> {
+    let mut words = line.split_whitespace();
+    let command = match words.next() {
+      Some(command) => command,
+      None => return Ok(String::new()),
+    };
+    let args: Vec<&str> = words.collect();
+
+    match command {
+      "help" | "h" => Ok(HELP.to_string()),
+      "step" | "s" => {
+        let count = match args.first() {
+          Some(count) => parse_number(Some(count), "a number of instructions")?,
+          None => 1,
+        };
+
+        self.step(count)
+      },
+      "continue" | "c" => self.resume(|_| false),
+      "finish" => {
+        if self.scheduler.current().is_none() {
+          return Err("no task is running".to_string());
+        }
+
+        self.resume(|event| matches!(event, Event::Finished { .. }))
+      },
+      "break" | "b" => self.add_break(&args),
+      "watch" | "w" => self.add_watch(&args),
+      "delete" | "d" => {
+        let id: usize = parse_number(args.first().copied(), "a breakpoint or watchpoint number")?;
+        let before = self.points.len();
+
+        self.points.retain(|(candidate, _)| *candidate != id);
+
+        if self.points.len() == before {
+          return Err(format!("there is no breakpoint or watchpoint {}", id));
+        }
+
+        Ok(format!("deleted {}", id))
+      },
+      "info" | "i" => Ok(
+        self
+          .points
+          .iter()
+          .map(|(id, point)| format!("{:>3}  {}", id, point))
+          .collect::<Vec<_>>()
+          .join("\n"),
+      ),
+      "where" => Ok(self.position()),
+      "regs" | "r" => Ok(self.registers()),
+      "ram" => {
+        let (group_id, task_id) = parse_task(args.first().copied().unwrap_or(""))?;
+        let machine = &self.scheduler.machine;
+        let task = machine
+          .find(group_id, task_id)
+          .map(|index| &machine.tasks[index])
+          .ok_or_else(|| format!("task {}.{} is not loaded", group_id, task_id))?;
+
+        let name = |offset: usize| {
+          task
+            .debug
+            .as_ref()
+            .and_then(|debug| debug.variable((offset + 1) as u8))
+            .map_or("", |variable| variable.name.as_str())
+        };
+        let width = (0..task.ram.len()).map(|offset| name(offset).len()).max().unwrap_or(0);
+
+        Ok(
+          task
+            .ram
+            .iter()
+            .enumerate()
+            .map(|(offset, word)| {
+              let name = match width {
+                0 => String::new(),
+                _ => format!("{:<width$}  ", name(offset), width = width),
+              };
+
+              format!("{:>3}  {}{}", offset + 1, name, format_word(*word))
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+        )
+      },
+      "output" => Ok(
+        self
+          .scheduler
+          .machine
+          .output
+          .iter()
+          .map(|output| format!("{:>6}s  {}.{}: {}", output.time, output.group_id, output.task_id, output.value))
+          .collect::<Vec<_>>()
+          .join("\n"),
+      ),
+      "log" => Ok(
+        self
+          .scheduler
+          .log
+          .iter()
+          .map(|entry| entry.to_string())
+          .collect::<Vec<_>>()
+          .join("\n"),
+      ),
+      _ => Err(format!("unknown command `{}`, try `help`", command)),
+    }
+  }
+
+  fn add_point(&mut self, point: Point) -> Result<String, String> {
+    let id = self.next_id;
+    let message = format!("{}  {}", id, point);
+
+    self.next_id += 1;
+    self.points.push((id, point));
+
+    Ok(message)
+  }
+
+  fn add_break(&mut self, args: &[&str]) -> Result<String, String> {
+    match args {
+      [task, word] => {
+        let (group_id, task_id) = parse_task(task)?;
+        let word = parse_number(Some(word), "a word index")?;
+
+        self.add_point(Point::Word { group_id, task_id, word })
+      },
+      [location] => {
+        let (file, line) = location
+          .rsplit_once(':')
+          .and_then(|(file, line)| Some((file, line.parse().ok()?)))
+          .ok_or_else(|| format!("expected FILE:LINE, found `{}`", location))?;
+        let known = self
+          .scheduler
+          .machine
+          .tasks
+          .iter()
+          .any(|task| (1..=task.code.len()).any(|word| at_line(task, word, file, line)));
+
+        if !known {
+          return Err(format!("no instruction comes from {}:{}", file, line));
+        }
+
+        self.add_point(Point::Line {
+          file: file.to_string(),
+          line,
+        })
+      },
+      _ => Err("expected `break G.T WORD` or `break FILE:LINE`".to_string()),
+    }
+  }
+
+  fn add_watch(&mut self, args: &[&str]) -> Result<String, String> {
+    match args {
+      [instrument, param] if instrument.to_ascii_uppercase().starts_with("INST_") => {
+        let instrument = lookup(INSTRUMENTS, instrument).ok_or_else(|| format!("unknown instrument `{}`", instrument))?;
+        let param = lookup(PARAMETERS, param).ok_or_else(|| format!("unknown parameter `{}`", param))?;
+
+        self.add_point(Point::Property { instrument, param })
+      },
+      [task, address] => {
+        let (group_id, task_id) = parse_task(task)?;
+        let address = match self.variable(group_id, task_id, address) {
+          Some(variable) => variable,
+          None => parse_number(Some(address), "a data address or a variable name")?,
+        };
+
+        self.add_point(Point::Data {
+          group_id,
+          task_id,
+          address,
+        })
+      },
+      _ => Err("expected `watch G.T ADDRESS` or `watch INST_* P_*`".to_string()),
+    }
+  }
+
+  /// The address of a variable of task G.T, by its debug information.
+  fn variable(&self, group_id: u8, task_id: u8, name: &str) -> Option<u8> {
+    let machine = &self.scheduler.machine;
+    let task = &machine.tasks[machine.find(group_id, task_id)?];
+
+    task
+      .debug
+      .as_ref()?
+      .variables
+      .iter()
+      .find(|variable| variable.name == name)
+      .map(|variable| variable.address)
+  }
+
+  fn data(&self, point: &Point) -> Option<Word> {
+    match point {
+      Point::Data {
+        group_id,
+        task_id,
+        address,
+      } => {
+        let machine = &self.scheduler.machine;
+        let task = &machine.tasks[machine.find(*group_id, *task_id)?];
+
+        usize::from(*address)
+          .checked_sub(1)
+          .and_then(|offset| task.ram.get(offset))
+          .copied()
+      },
+      _ => None,
+    }
+  }
+
+  /// Executes one scheduler event and returns it together with the message
+  /// of the breakpoint or watchpoint it hit, if any.
+  fn advance(&mut self) -> Result<(Event, Option<String>), String> {
+    let machine = &self.scheduler.machine;
+
+    if self.scheduler.current().is_none() && machine.time >= self.end {
+      return Err(format!("the timeline ends at {}s", self.end));
+    }
+
+    let before: Vec<Option<Word>> = self.points.iter().map(|(_, point)| self.data(point)).collect();
+    let event = self.scheduler.step();
+    let machine = &self.scheduler.machine;
+
+    let executed = match event {
+      Event::Executed { index, pc } | Event::Finished { index, pc, .. } => machine.tasks[index].code.get(pc).copied(),
+      _ => None,
+    };
+
+    if let Some(word) = executed {
+      let [opcode, b1, b2, b3] = unpack32to4x8(word);
+
+      for ((id, point), before) in self.points.iter().zip(before) {
+        let after = self.data(point);
+
+        if after != before {
+          let after = after.map_or("-".to_string(), format_word);
+          return Ok((event, Some(format!("watchpoint {}: {} is now {}", id, point, after))));
+        }
+
+        if let Point::Property { instrument, param } = point {
+          if opcode == OP_SET && b1 == *instrument && b2 == *param {
+            let value = if Registers::is_valid(b3) {
+              machine.registers.get(b3).to_string()
+            }
+            else {
+              format!("- (invalid register 0x{:02x})", b3)
+            };
+            return Ok((event, Some(format!("watchpoint {}: {} set to {}", id, point, value))));
+          }
+        }
+      }
+    }
+
+    if let Some((index, pc)) = self.scheduler.current() {
+      let task = &machine.tasks[index];
+      let header = task.header;
+
+      for (id, point) in &self.points {
+        let hit = match point {
+          Point::Word { group_id, task_id, word } => {
+            header.group_id == *group_id && header.task_id == *task_id && *word == pc + 1
+          },
+          Point::Line { file, line } => at_line(task, pc + 1, file, *line),
+          _ => false,
+        };
+
+        if hit {
+          return Ok((event, Some(format!("breakpoint {}: {}", id, point))));
+        }
+      }
+    }
+
+    Ok((event, None))
+  }
+
+  fn describe(&self, event: Event) -> Option<String> {
+    let machine = &self.scheduler.machine;
+
+    match event {
+      Event::Started { index } => {
+        let header = machine.tasks[index].header;
+        Some(format!("{:>6}s  task {}.{}  started", machine.time, header.group_id, header.task_id))
+      },
+      Event::Finished { entry, .. } => Some(self.scheduler.log[entry].to_string()),
+      _ => None,
+    }
+  }
+
+  fn step(&mut self, count: usize) -> Result<String, String> {
+    let mut lines = Vec::new();
+    let mut executed = 0;
+
+    while executed < count {
+      let (event, hit) = match self.advance() {
+        Ok(result) => result,
+        Err(message) => {
+          lines.push(message);
+          break;
+        },
+      };
+
+      if let Event::Executed { .. } | Event::Finished { .. } = event {
+        executed += 1;
+      }
+
+      lines.extend(self.describe(event));
+
+      if let Some(hit) = hit {
+        lines.push(hit);
+        break;
+      }
+    }
+
+    lines.push(self.position());
+    Ok(lines.join("\n"))
+  }
+
+  fn resume(&mut self, stop: impl Fn(&Event) -> bool) -> Result<String, String> {
+    let mut lines = Vec::new();
+
+    loop {
+      let (event, hit) = match self.advance() {
+        Ok(result) => result,
+        Err(message) => {
+          lines.push(message);
+          break;
+        },
+      };
+
+      if let Some(hit) = hit {
+        lines.extend(self.describe(event));
+        lines.push(hit);
+        break;
+      }
+
+      if stop(&event) {
+        lines.extend(self.describe(event));
+        break;
+      }
+    }
+
+    lines.push(self.position());
+    Ok(lines.join("\n"))
+  }
+
+  /// The next instruction to execute, with its source line when known.
+  pub fn position(&self) -> String {
+    let machine = &self.scheduler.machine;
+
+    match self.scheduler.current() {
+      Some((index, pc)) => {
+        let task = &machine.tasks[index];
+        let word = task.code[pc];
+        let mut text = format!(
+          "{:>6}s  task {}.{}  word {}  {:08x}",
+          machine.time,
+          task.header.group_id,
+          task.header.task_id,
+          pc + 1,
+          word
+        );
+
+        if let Some(source) = task.sources.get(pc + 1) {
+          text.push_str(&format!("  {}  ; {}", source.original.trim(), source.location));
+        }
+        if let Some(location) = task.debug.as_ref().and_then(|debug| debug.location(pc + 1)) {
+          text.push_str(&format!("  ({})", location));
+        }
+
+        text
+      },
+      None => format!("{:>6}s  between tasks", machine.time),
+    }
+  }
+
+  fn registers(&self) -> String {
+    let registers = &self.scheduler.machine.registers;
+    let mut lines = Vec::new();
+
+    for offset in 0..REGISTER_COUNT as u8 {
+      let int = offset;
+      let float = FREG_BASE + offset;
+
+      lines.push(format!(
+        "{:<7} {:>12}    {:<7} {:?}",
+        name_of(REGISTERS, "IREG_", int).unwrap_or("?"),
+        registers.get(int),
+        name_of(REGISTERS, "FREG_", float).unwrap_or("?"),
+        registers.get(float).as_float()
+      ));
+    }
+
+    lines.join("\n")
+  }
+}
@@ -0,0 +1,318 @@
+use std::fmt;
+
+use crate::asm::isa::*;
+use crate::json::Json;
+use crate::vm::machine::{Output, OutputValue, TaskState};
+
+/// How an `OP_STR` prefix formats its register.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+  /// `PRE_STR_ALU`.
+  Integer,
+  /// `PRE_STR_FPU`.
+  Float,
+  /// `PRE_STR_BIN`, a 32 digit bitstring.
+  Binary,
+}
+
+impl Format {
+  pub fn from_prefix(prefix: u8) -> Option<Format> {
+    match prefix {
+      PRE_STR_ALU => Some(Format::Integer),
+      PRE_STR_FPU => Some(Format::Float),
+      PRE_STR_BIN => Some(Format::Binary),
+      _ => None,
+    }
+  }
+
+  /// The value of the raw bits of the register.
+  pub fn value(self, bits: u32) -> OutputValue {
+    match self {
+      Format::Integer => OutputValue::Int(bits as i32),
+      Format::Float => OutputValue::Float(f32::from_bits(bits)),
+      Format::Binary => OutputValue::Bin(bits),
+    }
+  }
+}
+
+impl fmt::Display for Format {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.pad(match self {
+      Format::Integer => "integer",
+      Format::Float => "float",
+      Format::Binary => "binary",
+    })
+  }
+}
+
+fn bits(value: OutputValue) -> u32 {
+  match value {
+    OutputValue::Int(value) => value as u32,
+    OutputValue::Float(value) => value.to_bits(),
+    OutputValue::Bin(value) => value,
+  }
+}
+
+/// An output value tagged with where it comes from.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Record {
+  pub time: u32,
+  pub group_id: u8,
+  pub task_id: u8,
+  /// Index of the `OP_STR` word within the task, the header being word 0.
+  pub word: usize,
+  pub format: Format,
+  pub value: OutputValue,
+  /// The output register, e.g. `FREG_A`.
+  pub register: String,
+  /// The data label or instrument parameter last loaded into the register,
+  /// when the task has sources or a debug sidecar.
+  pub variable: Option<String>,
+  /// The `.spl` position of the `OP_STR` when the task has a debug sidecar,
+  /// its `.splc` line when it has sources.
+  pub location: Option<String>,
+}
+
+/// Decodes output against the tasks that produced it.
+pub struct Decoder<'a> {
+  tasks: &'a [TaskState],
+}
+
+impl<'a> Decoder<'a> {
+  pub fn new(tasks: &'a [TaskState]) -> Decoder<'a> {
+    Decoder { tasks }
+  }
+
+  /// Tags the output of a machine run.
+  pub fn decode(&self, output: &[Output]) -> Result<Vec<Record>, String> {
+    output
+      .iter()
+      .map(|output| self.record(output.time, output.group_id, output.task_id, output.word, bits(output.value)))
+      .collect()
+  }
+
+  /// Decodes a dump with one `TIME GROUP.TASK WORD BITS` line per output,
+  /// `BITS` being the 8 hex digits of the register; the format comes from the
+  /// `OP_STR` at that word. Blank lines and lines starting with `#` are
+  /// skipped.
+  pub fn decode_dump(&self, text: &str) -> Result<Vec<Record>, String> {
+    let mut records = Vec::new();
+
+    for (index, line) in text.lines().enumerate() {
+      let line = line.trim();
+      if line.is_empty() || line.starts_with('#') {
+        continue;
+      }
+
+      let (time, group_id, task_id, word, bits) =
+        dump_line(line).ok_or_else(|| format!("line {}: expected `TIME GROUP.TASK WORD BITS`", index + 1))?;
+
+      records.push(
+        self
+          .record(time, group_id, task_id, word, bits)
+          .map_err(|err| format!("line {}: {}", index + 1, err))?,
+      );
+    }
+
+    Ok(records)
+  }
+
+  fn record(&self, time: u32, group_id: u8, task_id: u8, word: usize, bits: u32) -> Result<Record, String> {
+    let task = self
+      .tasks
+      .iter()
+      .find(|task| task.header.group_id == group_id && task.header.task_id == task_id)
+      .ok_or_else(|| format!("no task {}.{}", group_id, task_id))?;
+    let [opcode, prefix, _, register] = word
+      .checked_sub(1)
+      .and_then(|pc| task.code.get(pc))
+      .map(|code| unpack32to4x8(*code))
+      .ok_or_else(|| format!("task {}.{} has no code word {}", group_id, task_id, word))?;
+    let format = match Format::from_prefix(prefix) {
+      Some(format) if opcode == OP_STR => format,
+      _ => return Err(format!("word {} of task {}.{} is not an OP_STR", word, group_id, task_id)),
+    };
+    let register_id = register;
+    let register = name_of(REGISTERS, "", register).unwrap_or("?").to_string();
+
+    Ok(Record {
+      time,
+      group_id,
+      task_id,
+      word,
+      format,
+      value: format.value(bits),
+      variable: variable(task, word, &register).or_else(|| self.variable(task, word, register_id)),
+      location: task
+        .debug
+        .as_ref()
+        .and_then(|debug| debug.location(word))
+        .or_else(|| task.sources.get(word).map(|source| source.location.to_string())),
+      register,
+    })
+  }
+
+  /// What `register` holds at `word` from the code words and the debug
+  /// sidecars, as `variable` finds it from the sources: the variable of the
+  /// last `OP_LEA` into it or `OP_MOV, PRE_MOV_RAM` of it, or the parameter
+  /// of the last `OP_GET` into it.
+  fn variable(&self, task: &TaskState, word: usize, register: u8) -> Option<String> {
+    let name = |task_id: u8, address: u8| {
+      let owner = self
+        .tasks
+        .iter()
+        .find(|owner| owner.header.group_id == task.header.group_id && owner.header.task_id == task_id)?;
+
+      owner.debug.as_ref()?.variable(address).map(|variable| variable.name.clone())
+    };
+
+    for code in task.code.get(..word.checked_sub(1)?)?.iter().rev() {
+      let [opcode, b, c, d] = unpack32to4x8(*code);
+
+      match opcode {
+        OP_LEA if b == register => return name(c, d),
+        OP_MOV if b == PRE_MOV_RAM => {
+          // a store leaves the register as it is, and only names it by variable
+          if let Some(name) = name(task.header.task_id, d).filter(|_| c == register) {
+            return Some(name);
+          }
+        },
+        OP_GET if d == register => {
+          let instrument = name_of(INSTRUMENTS, "INST_", b)?;
+          let prefix = format!("P_{}_", &instrument["INST_".len()..]);
+
+          return name_of(PARAMETERS, &prefix, c).map(str::to_string);
+        },
+        OP_CMP | OP_STR | OP_SET | OP_ACT | OP_NOP | OP_HLT => {},
+        _ if d == register => return None,
+        _ => {},
+      }
+    }
+
+    None
+  }
+}
+
+fn dump_line(line: &str) -> Option<(u32, u8, u8, usize, u32)> {
+  let mut fields = line.split_whitespace();
+  let time = fields.next()?.parse().ok()?;
+  let (group_id, task_id) = fields.next()?.split_once('.')?;
+  let word = fields.next()?.parse().ok()?;
+  let bits = u32::from_str_radix(fields.next()?, 16).ok()?;
+
+  if fields.next().is_some() {
+    return None;
+  }
+
+  Some((time, group_id.parse().ok()?, task_id.parse().ok()?, word, bits))
+}
+
+/// The name of what `register` holds at `word`: the data label of the last
+/// `OP_LEA` into it or `OP_MOV, PRE_MOV_RAM` of it, or the parameter of the
+/// last `OP_GET` into it, looking back in code order. Any other instruction
+/// writing the register hides what it held.
+fn variable(task: &TaskState, word: usize, register: &str) -> Option<String> {
+  let label = |operand: &str| operand.split_once('.').map(|(_, label)| label.to_string());
+
+  for source in task.sources.get(1..word)?.iter().rev() {
+    let fields: Vec<&str> = source.text.split(',').map(str::trim).filter(|field| !field.is_empty()).collect();
+    let is = |index: usize| fields.get(index).is_some_and(|field| field.eq_ignore_ascii_case(register));
+    let opcode = fields[0].to_ascii_uppercase();
+
+    match opcode.as_str() {
+      "OP_LEA" if is(1) => return fields.get(2).and_then(|operand| label(operand)),
+      "OP_MOV" if is(2) && fields[1].eq_ignore_ascii_case("PRE_MOV_RAM") => {
+        // A store leaves the register as it is, and only names it by label.
+        if let Some(label) = fields.get(3).and_then(|operand| label(operand)) {
+          return Some(label);
+        }
+      },
+      "OP_GET" if is(3) => return fields.get(2).map(|param| param.to_ascii_uppercase()),
+      "OP_CMP" | "OP_STR" | "OP_SET" | "OP_ACT" => {},
+      _ if is(fields.len() - 1) => return None,
+      _ => {},
+    }
+  }
+
+  None
+}
+
+fn csv_field(text: &str) -> String {
+  if text.contains(',') || text.contains('"') {
+    format!("\"{}\"", text.replace('"', "\"\""))
+  }
+  else {
+    text.to_string()
+  }
+}
+
+pub fn to_csv(records: &[Record]) -> String {
+  let mut text = String::from("time,group,task,word,format,register,variable,value,location\n");
+
+  for record in records {
+    text.push_str(&format!(
+      "{},{},{},{},{},{},{},{},{}\n",
+      record.time,
+      record.group_id,
+      record.task_id,
+      record.word,
+      record.format,
+      record.register,
+      csv_field(record.variable.as_deref().unwrap_or("")),
+      record.value,
+      csv_field(record.location.as_deref().unwrap_or(""))
+    ));
+  }
+
+  text
+}
+
+/// An array of records; binary values are strings of 32 digits.
+pub fn to_json(records: &[Record]) -> Json {
+  let optional = |text: &Option<String>| text.as_deref().map_or(Json::Null, Json::from);
+
+  Json::Array(
+    records
+      .iter()
+      .map(|record| {
+        Json::object(vec![
+          ("time", f64::from(record.time).into()),
+          ("group", f64::from(record.group_id).into()),
+          ("task", f64::from(record.task_id).into()),
+          ("word", (record.word as f64).into()),
+          ("format", record.format.to_string().into()),
+          ("register", record.register.as_str().into()),
+          ("variable", optional(&record.variable)),
+          (
+            "value",
+            match record.value {
+              OutputValue::Int(value) => f64::from(value).into(),
+              // The shortest decimal of the f32, not of its f64 widening.
+              OutputValue::Float(value) => format!("{:?}", value).parse::<f64>().map_or(Json::Null, Json::from),
+              OutputValue::Bin(value) => format!("{:032b}", value).into(),
+            },
+          ),
+          ("location", optional(&record.location)),
+        ])
+      })
+      .collect(),
+  )
+}
+
+/// The dump read by `Decoder::decode_dump`.
+pub fn to_dump(records: &[Record]) -> String {
+  let mut text = String::new();
+
+  for record in records {
+    text.push_str(&format!(
+      "{} {}.{} {} {:08x}\n",
+      record.time,
+      record.group_id,
+      record.task_id,
+      record.word,
+      bits(record.value)
+    ));
+  }
+
+  text
+}
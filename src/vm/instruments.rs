@@ -0,0 +1,313 @@
+use std::cell::RefCell;
+use std::f32::consts::{E, PI};
+use std::fmt;
+use std::rc::Rc;
+
+use crate::asm::isa::*;
+use crate::vm::orbit::Orbit;
+use crate::vm::registers::Value;
+
+/// A device addressed by `OP_GET`, `OP_SET` and `OP_ACT`. Every call gets the
+/// mission time in seconds, so backends can be deterministic functions of
+/// time. Errors stop the task and are reported with the instrument name.
+pub trait Instrument: fmt::Debug {
+  /// `OP_GET, INST_*, param, REG`: the value to load into the register.
+  fn get(&mut self, param: u8, time: u32) -> Result<Value, String>;
+
+  /// `OP_SET, INST_*, param, REG`.
+  fn set(&mut self, param: u8, _value: Value, _time: u32) -> Result<(), String> {
+    Err(format!("parameter {:#04x} cannot be set", param))
+  }
+
+  /// `OP_ACT, INST_*, action, REG`.
+  fn act(&mut self, action: u8, _argument: Value, _time: u32) -> Result<(), String> {
+    Err(format!("unknown action {:#04x}", action))
+  }
+}
+
+/// Lets the caller keep a handle on a backend attached to a machine, e.g. to
+/// read the captures of a `Camera` after a run.
+impl<T: Instrument> Instrument for Rc<RefCell<T>> {
+  fn get(&mut self, param: u8, time: u32) -> Result<Value, String> {
+    self.borrow_mut().get(param, time)
+  }
+
+  fn set(&mut self, param: u8, value: Value, time: u32) -> Result<(), String> {
+    self.borrow_mut().set(param, value, time)
+  }
+
+  fn act(&mut self, action: u8, argument: Value, time: u32) -> Result<(), String> {
+    self.borrow_mut().act(action, argument, time)
+  }
+}
+
+fn unknown_parameter(param: u8) -> String {
+  format!("unknown parameter {:#04x}", param)
+}
+
+/// The stand-in of every instrument but `INST_SDR`, which is not supported.
+pub fn simulated() -> Vec<(u8, Box<dyn Instrument>)> {
+  vec![
+    (INST_ADC, Box::new(Adcs::default())),
+    (INST_GPS, Box::new(Gps::default())),
+    (INST_IMG, Box::new(Camera::default())),
+    (INST_FPU, Box::new(Fpu)),
+    (INST_NMF, Box::new(Nmf::default())),
+    (INST_VXM, Box::new(Vxm::default())),
+  ]
+}
+
+/// Attitude determination and control. The mode is the `A_ADC_*` action that
+/// selected it, `A_ADC_UNSET` at first; the vectors are fixed readings.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Adcs {
+  pub mode: u8,
+  /// Magnetic field in body frame, in µT.
+  pub magnetic: [f32; 3],
+  /// Unit sun vector in body frame.
+  pub sun: [f32; 3],
+  /// Angular velocity, in degrees per second.
+  pub angular: [f32; 3],
+  pub quaternion: [f32; 4],
+  /// Magnetorquer dipoles, in A·m².
+  pub torquer: [f32; 3],
+}
+
+impl Default for Adcs {
+  fn default() -> Adcs {
+    Adcs {
+      mode: A_ADC_UNSET,
+      magnetic: [18.2, -4.6, 41.3],
+      sun: [0.6, 0.0, 0.8],
+      angular: [0.0, 0.0, 0.0],
+      quaternion: [1.0, 0.0, 0.0, 0.0],
+      torquer: [0.0, 0.0, 0.0],
+    }
+  }
+}
+
+impl Instrument for Adcs {
+  fn get(&mut self, param: u8, _time: u32) -> Result<Value, String> {
+    let value = match param {
+      P_ADC_MODE => return Ok(Value::Int(i32::from(self.mode))),
+      P_ADC_MAGX..=P_ADC_MAGZ => self.magnetic[usize::from(param - P_ADC_MAGX)],
+      P_ADC_SUNX..=P_ADC_SUNZ => self.sun[usize::from(param - P_ADC_SUNX)],
+      P_ADC_ANGX..=P_ADC_ANGZ => self.angular[usize::from(param - P_ADC_ANGX)],
+      P_ADC_QTNA..=P_ADC_QTND => self.quaternion[usize::from(param - P_ADC_QTNA)],
+      P_ADC_MTQX..=P_ADC_MTQZ => self.torquer[usize::from(param - P_ADC_MTQX)],
+      _ => return Err(unknown_parameter(param)),
+    };
+
+    Ok(Value::Float(value))
+  }
+
+  fn set(&mut self, param: u8, value: Value, _time: u32) -> Result<(), String> {
+    match param {
+      P_ADC_MTQX..=P_ADC_MTQZ => self.torquer[usize::from(param - P_ADC_MTQX)] = value.as_float(),
+      _ => return Err(format!("parameter {:#04x} cannot be set, use OP_ACT to change the mode", param)),
+    }
+
+    Ok(())
+  }
+
+  fn act(&mut self, action: u8, _argument: Value, _time: u32) -> Result<(), String> {
+    match action {
+      A_ADC_NADIR..=A_ADC_UNSET => self.mode = action,
+      _ => return Err(format!("unknown action {:#04x}", action)),
+    }
+
+    Ok(())
+  }
+}
+
+/// GPS receiver at a fixed position, where the time is `epoch` plus mission
+/// time, or moving along `orbit`, where the time is the GPS time of week.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Gps {
+  /// Degrees.
+  pub latitude: f32,
+  /// Degrees.
+  pub longitude: f32,
+  /// Kilometres.
+  pub altitude: f32,
+  /// GPS time at mission time 0, in seconds.
+  pub epoch: f32,
+  /// Propagated from mission time 0 at the epoch of its elements.
+  pub orbit: Option<Orbit>,
+}
+
+impl Default for Gps {
+  fn default() -> Gps {
+    Gps {
+      latitude: 48.1,
+      longitude: 11.6,
+      altitude: 515.0,
+      epoch: 0.0,
+      orbit: None,
+    }
+  }
+}
+
+impl Gps {
+  pub fn with_orbit(orbit: Orbit) -> Gps {
+    Gps {
+      orbit: Some(orbit),
+      ..Gps::default()
+    }
+  }
+}
+
+impl Instrument for Gps {
+  fn get(&mut self, param: u8, time: u32) -> Result<Value, String> {
+    let value = match (&self.orbit, param) {
+      (Some(orbit), P_GPS_LATT) => orbit.position(f64::from(time)).latitude as f32,
+      (Some(orbit), P_GPS_LONG) => orbit.position(f64::from(time)).longitude as f32,
+      (Some(orbit), P_GPS_ALTT) => orbit.position(f64::from(time)).altitude as f32,
+      (Some(orbit), P_GPS_TIME) => orbit.gps_time_of_week(f64::from(time)) as f32,
+      (None, P_GPS_LATT) => self.latitude,
+      (None, P_GPS_LONG) => self.longitude,
+      (None, P_GPS_ALTT) => self.altitude,
+      (None, P_GPS_TIME) => self.epoch + time as f32,
+      _ => return Err(unknown_parameter(param)),
+    };
+
+    Ok(Value::Float(value))
+  }
+}
+
+/// A capture request of the camera.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Capture {
+  /// 1-based capture number, as read back by `P_IMG_NUMBER`.
+  pub number: i32,
+  pub time: u32,
+  /// One of the `A_IMG_DO_*` actions.
+  pub format: u8,
+  pub gain: [f32; 3],
+  pub exposure: f32,
+}
+
+/// Camera that records capture requests instead of taking pictures.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Camera {
+  pub gain: [f32; 3],
+  /// Seconds.
+  pub exposure: f32,
+  pub captures: Vec<Capture>,
+}
+
+impl Default for Camera {
+  fn default() -> Camera {
+    Camera {
+      gain: [1.0, 1.0, 1.0],
+      exposure: 0.01,
+      captures: Vec::new(),
+    }
+  }
+}
+
+impl Instrument for Camera {
+  fn get(&mut self, param: u8, _time: u32) -> Result<Value, String> {
+    match param {
+      P_IMG_GAIN_R..=P_IMG_GAIN_B => Ok(Value::Float(self.gain[usize::from(param - P_IMG_GAIN_R)])),
+      P_IMG_EXPOSE => Ok(Value::Float(self.exposure)),
+      // Captures complete at once, so the camera is always idle.
+      P_IMG_STATUS => Ok(Value::Int(0)),
+      P_IMG_NUMBER => Ok(Value::Int(self.captures.len() as i32)),
+      _ => Err(unknown_parameter(param)),
+    }
+  }
+
+  fn set(&mut self, param: u8, value: Value, _time: u32) -> Result<(), String> {
+    match param {
+      P_IMG_GAIN_R..=P_IMG_GAIN_B => self.gain[usize::from(param - P_IMG_GAIN_R)] = value.as_float(),
+      P_IMG_EXPOSE => self.exposure = value.as_float(),
+      _ => return Err(format!("parameter {:#04x} cannot be set", param)),
+    }
+
+    Ok(())
+  }
+
+  fn act(&mut self, action: u8, _argument: Value, time: u32) -> Result<(), String> {
+    match action {
+      A_IMG_DO_JPG..=A_IMG_DO_PNG => {
+        self.captures.push(Capture {
+          number: self.captures.len() as i32 + 1,
+          time,
+          format: action,
+          gain: self.gain,
+          exposure: self.exposure,
+        });
+
+        Ok(())
+      },
+      _ => Err(format!("unknown action {:#04x}", action)),
+    }
+  }
+}
+
+/// The constants loaded by `OP_GET, INST_FPU, P_FPU_*`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Fpu;
+
+impl Instrument for Fpu {
+  fn get(&mut self, param: u8, _time: u32) -> Result<Value, String> {
+    let value = match param {
+      P_FPU_NIL => 0.0,
+      P_FPU_ONE => 1.0,
+      P_FPU_EXP => E,
+      P_FPU_PIE => PI,
+      _ => return Err(unknown_parameter(param)),
+    };
+
+    Ok(Value::Float(value))
+  }
+}
+
+/// NanoSat MO Framework of the spacecraft; only its clock is simulated.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Nmf {
+  /// NMF time at mission time 0, in seconds.
+  pub epoch: i32,
+}
+
+impl Instrument for Nmf {
+  fn get(&mut self, param: u8, time: u32) -> Result<Value, String> {
+    match param {
+      P_NMF_TIME => Ok(Value::Int(self.epoch.wrapping_add(time as i32))),
+      _ => Err(unknown_parameter(param)),
+    }
+  }
+}
+
+/// Parameters of the VM itself.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Vxm {
+  pub precision: f32,
+  pub time_scale: i32,
+  /// Log debug level.
+  pub debug: i32,
+}
+
+impl Instrument for Vxm {
+  fn get(&mut self, param: u8, time: u32) -> Result<Value, String> {
+    match param {
+      P_VXM_TIME => Ok(Value::Int(time as i32)),
+      P_VXM_PRSN => Ok(Value::Float(self.precision)),
+      P_VXM_TLSC => Ok(Value::Int(self.time_scale)),
+      P_VXM_DBUG => Ok(Value::Int(self.debug)),
+      _ => Err(unknown_parameter(param)),
+    }
+  }
+
+  fn set(&mut self, param: u8, value: Value, _time: u32) -> Result<(), String> {
+    match param {
+      P_VXM_PRSN => self.precision = value.as_float(),
+      P_VXM_TLSC => self.time_scale = value.as_int(),
+      P_VXM_DBUG => self.debug = value.as_int(),
+      _ => return Err(format!("parameter {:#04x} cannot be set", param)),
+    }
+
+    Ok(())
+  }
+}
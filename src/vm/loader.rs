@@ -0,0 +1,97 @@
+use std::fs;
+use std::path::Path;
+
+use crate::asm::{self, Task};
+use crate::compiler;
+use crate::debug;
+use crate::link::Linker;
+use crate::splx;
+use crate::target::Target;
+
+/// Loads the tasks of every `.splx` file, plus the `.splc` files and the
+/// tasks compiled from `.spl` files assembled and linked together. Assembled
+/// tasks keep their source lines, and compiled tasks, or tasks with a `.spld`
+/// sidecar, their debug information.
+pub fn load(inputs: &[String]) -> Result<Vec<Task>, String> {
+  load_with(Target::opssat(), inputs, "the VM only runs OPS-SAT executables")
+}
+
+/// Like `load`, with the ISA and the executable format of `target`.
+pub fn load_for(target: &Target, inputs: &[String]) -> Result<Vec<Task>, String> {
+  load_with(target, inputs, "pass --target <profile> if it was linked for another target")
+}
+
+/// `hint` follows the error of an executable that does not parse.
+fn load_with(target: &Target, inputs: &[String], hint: &str) -> Result<Vec<Task>, String> {
+  let mut tasks = Vec::new();
+  let mut linker = Linker::for_target(target);
+  let mut assembled = false;
+
+  for input in inputs {
+    if input.ends_with(".splc") {
+      let task = asm::assemble_file_for(target, Path::new(input)).map_err(|err| err.to_string())?;
+
+      linker.add(input, task);
+      assembled = true;
+    }
+    else if input.ends_with(".spl") {
+      for (path, task) in compile_file_for(target, input)? {
+        linker.add(&path, task);
+      }
+      assembled = true;
+    }
+    else {
+      let text = fs::read_to_string(input).map_err(|err| format!("{}: {}", input, err))?;
+      let mut parsed = splx::parse_for(target, &text)
+        .map_err(|err| format!("{}: {}; {}", input, err.not_executable_of(target), hint))?;
+
+      // the debug information the assembler or the linker wrote next to it
+      if let Some(info) = debug::read(Path::new(input))? {
+        info.attach(&mut parsed);
+      }
+      tasks.extend(parsed);
+    }
+  }
+
+  if assembled {
+    let image = linker.link().map_err(|errors| {
+      let mut message: String = errors.iter().map(|error| format!("error: {}\n", error)).collect();
+
+      message.push_str(&format!("linking failed with {} error(s)", errors.len()));
+      message
+    })?;
+
+    tasks.extend(image.units.into_iter().map(|unit| unit.task));
+  }
+
+  Ok(tasks)
+}
+
+/// Compiles a `.spl` file and assembles its tasks in memory, each as if it
+/// had been written to its `.splc` file next to the source.
+pub fn compile_file(input: &str) -> Result<Vec<(String, Task)>, String> {
+  compile_file_for(Target::opssat(), input)
+}
+
+/// Like `compile_file`, for `target`.
+pub fn compile_file_for(target: &Target, input: &str) -> Result<Vec<(String, Task)>, String> {
+  let source = fs::read_to_string(input).map_err(|err| format!("{}: {}", input, err))?;
+  let units = compiler::compile_for(target, input, &source).map_err(|errors| {
+    let mut message: String = errors.iter().map(|error| format!("error: {}\n", error)).collect();
+
+    message.push_str(&format!("compilation failed with {} error(s)", errors.len()));
+    message
+  })?;
+  let directory = Path::new(input).parent().unwrap_or_else(|| Path::new(""));
+
+  units
+    .iter()
+    .map(|unit| {
+      let path = directory.join(unit.file_name());
+      let mut task = asm::assemble_for(target, &path, &unit.assembly).map_err(|err| err.to_string())?;
+
+      task.debug = Some(unit.debug.assembled(&task.sources));
+      Ok((path.display().to_string(), task))
+    })
+    .collect()
+}
@@ -0,0 +1,670 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::asm::isa::*;
+use crate::asm::preprocess::SourceLine;
+use crate::asm::{Header, Task, Word};
+use crate::debug::TaskDebug;
+use crate::vm::instruments::{self, Instrument};
+use crate::vm::registers::{Registers, Value};
+use crate::vm::scheduler::Frequency;
+
+/// Execution status of a task, as checked by `TSX_EQ`/`TSX_NE`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Status {
+  /// The task has not run yet.
+  Ready,
+  /// The task reached `OP_HLT`.
+  Success,
+  /// An `OP_CMP` guard was false and the task was aborted.
+  Unmet,
+  /// The task stopped on an execution error.
+  Fault,
+}
+
+impl Status {
+  /// The value a `TSX_*` comparison sees; `test_c3` waits for 255.
+  pub fn code(self) -> u8 {
+    match self {
+      Status::Ready => 0,
+      Status::Success => 255,
+      Status::Unmet => 1,
+      Status::Fault => 2,
+    }
+  }
+}
+
+impl fmt::Display for Status {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let name = match self {
+      Status::Ready => "ready",
+      Status::Success => "success",
+      Status::Unmet => "unmet",
+      Status::Fault => "fault",
+    };
+
+    write!(f, "{}", name)
+  }
+}
+
+/// A loaded task: its code and its RAM, which starts as the data section.
+#[derive(Clone, Debug)]
+pub struct TaskState {
+  pub header: Header,
+  pub code: Vec<Word>,
+  pub ram: Vec<Word>,
+  pub status: Status,
+  /// Source line of every word for assembled tasks, empty for `.splx` ones.
+  pub sources: Vec<SourceLine>,
+  /// The debug information of compiled tasks.
+  pub debug: Option<TaskDebug>,
+}
+
+/// A value written by `OP_STR`, in the format given by its prefix.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OutputValue {
+  Int(i32),
+  Float(f32),
+  Bin(u32),
+}
+
+impl fmt::Display for OutputValue {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      OutputValue::Int(value) => write!(f, "{}", value),
+      OutputValue::Float(value) => write!(f, "{:?}", value),
+      OutputValue::Bin(value) => write!(f, "{:032b}", value),
+    }
+  }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Output {
+  /// Mission time of the run, in seconds.
+  pub time: u32,
+  pub group_id: u8,
+  pub task_id: u8,
+  /// Index of the `OP_STR` word within the task, the header being word 0.
+  pub word: usize,
+  pub value: OutputValue,
+}
+
+/// What the machine does after an instruction.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Step {
+  Next,
+  Halt,
+  Abort,
+}
+
+#[derive(Debug)]
+pub struct VmError {
+  pub group_id: u8,
+  pub task_id: u8,
+  /// Index of the failing word within the task, the header being word 0.
+  pub word: usize,
+  pub message: String,
+}
+
+impl fmt::Display for VmError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(
+      f,
+      "task {}.{}, word {}: {}",
+      self.group_id, self.task_id, self.word, self.message
+    )
+  }
+}
+
+impl std::error::Error for VmError {}
+
+/// The reference interpreter: both register files, every loaded task,
+/// ordered by group and task id, and the instruments keyed by `INST_*` id.
+#[derive(Debug, Default)]
+pub struct Machine {
+  pub registers: Registers,
+  pub tasks: Vec<TaskState>,
+  pub output: Vec<Output>,
+  /// Mission time in seconds, as seen by the instruments.
+  pub time: u32,
+  instruments: BTreeMap<u8, Box<dyn Instrument>>,
+}
+
+fn name(table: Table, prefix: &str, value: u8) -> String {
+  name_of(table, prefix, value)
+    .map(str::to_string)
+    .unwrap_or_else(|| format!("{:#04x}", value))
+}
+
+impl Machine {
+  /// Loads tasks, e.g. from `splx::parse` or the units of a linked image,
+  /// with the simulated instruments attached.
+  pub fn new(tasks: Vec<Task>) -> Result<Machine, String> {
+    let mut machine = Machine::default();
+
+    for (id, instrument) in instruments::simulated() {
+      machine.attach(id, instrument);
+    }
+
+    for task in tasks {
+      let header = task.header;
+
+      if machine.find(header.group_id, header.task_id).is_some() {
+        return Err(format!("task {}.{} is loaded twice", header.group_id, header.task_id));
+      }
+      if Frequency::from_byte(header.freq).is_none() {
+        return Err(format!(
+          "task {}.{}: {} is not a frequency (0 once, 1-59 seconds, 60-118 minutes, 119-126 hours, 127 always)",
+          header.group_id, header.task_id, header.freq
+        ));
+      }
+
+      machine.tasks.push(TaskState {
+        header,
+        code: task.code,
+        ram: task.data,
+        status: Status::Ready,
+        sources: task.sources,
+        debug: task.debug,
+      });
+    }
+
+    machine
+      .tasks
+      .sort_by_key(|task| (task.header.group_id, task.header.task_id));
+
+    Ok(machine)
+  }
+
+  /// Attaches an instrument backend, replacing the one with the same id.
+  pub fn attach(&mut self, id: u8, instrument: Box<dyn Instrument>) {
+    self.instruments.insert(id, instrument);
+  }
+
+  /// Removes an instrument backend, e.g. to wrap it and attach it again.
+  pub fn detach(&mut self, id: u8) -> Option<Box<dyn Instrument>> {
+    self.instruments.remove(&id)
+  }
+
+  /// The ids of the attached instruments.
+  pub fn instrument_ids(&self) -> Vec<u8> {
+    self.instruments.keys().copied().collect()
+  }
+
+  /// Index of a task in `tasks`.
+  pub fn find(&self, group_id: u8, task_id: u8) -> Option<usize> {
+    self
+      .tasks
+      .iter()
+      .position(|task| task.header.group_id == group_id && task.header.task_id == task_id)
+  }
+
+  /// Runs every task once, in order, and returns the errors of the tasks
+  /// that stopped on one.
+  pub fn run(&mut self) -> Vec<VmError> {
+    (0..self.tasks.len())
+      .filter_map(|index| self.run_task(index).err())
+      .collect()
+  }
+
+  /// Runs a task from its first instruction until `OP_HLT` or a false guard,
+  /// and records its status.
+  pub fn run_task(&mut self, index: usize) -> Result<Status, VmError> {
+    let mut pc = 0;
+
+    loop {
+      match self.step(index, pc) {
+        Ok(Step::Next) => pc += 1,
+        Ok(Step::Halt) => return Ok(self.finish(index, Status::Success)),
+        Ok(Step::Abort) => return Ok(self.finish(index, Status::Unmet)),
+        Err(err) => {
+          self.finish(index, Status::Fault);
+          return Err(err);
+        },
+      }
+    }
+  }
+
+  fn finish(&mut self, index: usize, status: Status) -> Status {
+    self.tasks[index].status = status;
+    status
+  }
+
+  /// Executes the instruction at `pc` (0-based within the code section) of
+  /// the task at `index`.
+  pub fn step(&mut self, index: usize, pc: usize) -> Result<Step, VmError> {
+    let header = self.tasks[index].header;
+    let error = |message: String| VmError {
+      group_id: header.group_id,
+      task_id: header.task_id,
+      word: 1 + pc,
+      message,
+    };
+
+    let word = *self.tasks[index]
+      .code
+      .get(pc)
+      .ok_or_else(|| error("execution ran past the code section".to_string()))?;
+    let [opcode, b1, b2, b3] = unpack32to4x8(word);
+
+    let register = |id: u8| {
+      if Registers::is_valid(id) {
+        Ok(id)
+      }
+      else {
+        Err(error(format!("invalid register {:#04x}", id)))
+      }
+    };
+
+    match opcode {
+      OP_NOP => {},
+      OP_HLT => return Ok(Step::Halt),
+      OP_MOV => {
+        let value = self.registers.get(register(b2)?);
+
+        match b1 {
+          PRE_MOV_REG => self.registers.set(register(b3)?, value),
+          PRE_MOV_RAM => *self.ram(index, b3).map_err(error)? = value.to_word(),
+          _ => return Err(error(format!("unsupported OP_MOV prefix {}", name(PREFIXES, "PRE_MOV_", b1)))),
+        }
+      },
+      OP_LEA => {
+        let target = self
+          .find(header.group_id, b2)
+          .ok_or_else(|| error(format!("task {}.{} is not loaded", header.group_id, b2)))?;
+        let word = *self.ram(target, b3).map_err(error)?;
+
+        self.registers.load(register(b1)?, word);
+      },
+      OP_CMP => {
+        let holds = match b1 {
+          TSX_EQ | TSX_NE => {
+            let target = self
+              .find(header.group_id, b2)
+              .ok_or_else(|| error(format!("task {}.{} is not loaded", header.group_id, b2)))?;
+            let status = i32::from(self.tasks[target].status.code());
+            let expected = self.registers.get(register(b3)?).as_int();
+
+            (status == expected) == (b1 == TSX_EQ)
+          },
+          0x01..=0x06 => {
+            let a = self.registers.get(register(b2)?).as_int();
+            let b = self.registers.get(register(b3)?).as_int();
+
+            match b1 {
+              0x01 => a == b,
+              0x02 => a != b,
+              0x03 => a > b,
+              0x04 => a < b,
+              0x05 => a >= b,
+              _ => a <= b,
+            }
+          },
+          0x07..=0x0A => {
+            let a = self.registers.get(register(b2)?).as_float();
+            let b = self.registers.get(register(b3)?).as_float();
+
+            match b1 {
+              0x07 => a == b,
+              0x08 => a != b,
+              0x09 => a > b,
+              _ => a < b,
+            }
+          },
+          _ => return Err(error(format!("invalid OP_CMP operator {:#04x}", b1))),
+        };
+
+        if !holds {
+          return Ok(Step::Abort);
+        }
+      },
+      OP_SET | OP_GET | OP_ACT => {
+        let time = self.time;
+        let reg = register(b3)?;
+        let instrument = self
+          .instruments
+          .get_mut(&b1)
+          .ok_or_else(|| error(format!("instrument {} is not attached", name(INSTRUMENTS, "INST_", b1))))?;
+        let value = self.registers.get(reg);
+
+        let result = match opcode {
+          OP_GET => instrument.get(b2, time).map(|value| self.registers.set(reg, value)),
+          OP_SET => instrument.set(b2, value, time),
+          _ => instrument.act(b2, value, time),
+        };
+
+        result.map_err(|message| error(format!("{}: {}", name(INSTRUMENTS, "INST_", b1), message)))?;
+      },
+      OP_STR => {
+        let value = self.registers.get(register(b3)?);
+        let value = match b1 {
+          0x01 => OutputValue::Int(value.as_int()),
+          0x02 => OutputValue::Float(value.as_float()),
+          0x03 => OutputValue::Bin(value.as_int() as u32),
+          _ => return Err(error(format!("invalid OP_STR prefix {:#04x}", b1))),
+        };
+
+        self.output.push(Output {
+          time: self.time,
+          group_id: header.group_id,
+          task_id: header.task_id,
+          word: 1 + pc,
+          value,
+        });
+      },
+      OP_FMA | OP_FSD | OP_NOR => {
+        let a = self.registers.get(register(b1)?);
+        let b = self.registers.get(register(b2)?);
+        let c = register(b3)?;
+
+        let value = match (opcode, self.registers.get(c)) {
+          (OP_NOR, _) => Value::Int(!(a.as_int() | b.as_int())),
+          (OP_FMA, Value::Float(c)) => Value::Float(c * b.as_float() + a.as_float()),
+          (OP_FMA, Value::Int(c)) => Value::Int(c.wrapping_mul(b.as_int()).wrapping_add(a.as_int())),
+          (_, Value::Float(c)) => Value::Float(c / b.as_float() - a.as_float()),
+          (_, Value::Int(c)) => match c.checked_div(b.as_int()) {
+            Some(quotient) => Value::Int(quotient.wrapping_sub(a.as_int())),
+            None => return Err(error("integer division by zero".to_string())),
+          },
+        };
+
+        self.registers.set(c, value);
+      },
+      OP_SIN | OP_COS | OP_TAN | OP_POW => {
+        let a = self.registers.get(register(b2)?).as_float();
+        let b = register(b3)?;
+        let value = self.registers.get(b).as_float();
+
+        let result = match (opcode, b1) {
+          (OP_SIN, 0x01) => a.sin(),
+          (OP_SIN, 0x02) => a.asin(),
+          (OP_COS, 0x01) => a.cos(),
+          (OP_COS, 0x02) => a.acos(),
+          (OP_TAN, 0x01) => a.tan(),
+          (OP_TAN, 0x02) => a.atan(),
+          // REG_B = REG_B ^ REG_A, and its inverse the base REG_A logarithm.
+          (OP_POW, 0x01) => value.powf(a),
+          (OP_POW, 0x02) => value.log(a),
+          _ => return Err(error(format!("invalid prefix {:#04x}", b1))),
+        };
+
+        self.registers.set(b, Value::Float(result));
+      },
+      _ => return Err(error(format!("invalid opcode {:#04x}", opcode))),
+    }
+
+    Ok(Step::Next)
+  }
+
+  /// The RAM word at 1-based `address` of task `target`. Task ids of
+  /// `OP_LEA` and `TSX_*` name tasks of the same group, so tasks only see the
+  /// data of their own group.
+  fn ram(&mut self, target: usize, address: u8) -> Result<&mut Word, String> {
+    let header = self.tasks[target].header;
+    let ram = &mut self.tasks[target].ram;
+    let len = ram.len();
+
+    usize::from(address)
+      .checked_sub(1)
+      .and_then(move |offset| ram.get_mut(offset))
+      .ok_or_else(|| {
+        format!(
+          "address {} is outside the {} data words of task {}.{}",
+          address, len, header.group_id, header.task_id
+        )
+      })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::asm;
+
+  /// Loads the assembled tasks and runs each of them once.
+  fn run(sources: &[&str]) -> Machine {
+    let tasks = sources
+      .iter()
+      .map(|source| asm::assemble(source).unwrap_or_else(|err| panic!("{}", err)))
+      .collect();
+    let mut machine = Machine::new(tasks).unwrap();
+
+    machine.run();
+    machine
+  }
+
+  fn output(source: &str) -> Vec<OutputValue> {
+    run(&[source]).output.iter().map(|output| output.value).collect()
+  }
+
+  #[test]
+  fn fused_multiply_add_adds_register_a_to_c_times_b() {
+    let floats = "1, 1, 0
+      OP_LEA, FREG_A, self.a
+      OP_LEA, FREG_B, self.b
+      OP_LEA, FREG_C, self.c
+      OP_FMA, FREG_A, FREG_B, FREG_C
+      OP_STR, PRE_STR_FPU, FREG_C
+      OP_HLT
+      a: 1.5f
+      b: 2.0f
+      c: 3.0f";
+    let ints = "1, 1, 0
+      OP_LEA, IREG_A, self.a
+      OP_LEA, IREG_B, self.b
+      OP_LEA, IREG_C, self.c
+      OP_FMA, IREG_A, IREG_B, IREG_C
+      OP_STR, PRE_STR_ALU, IREG_C
+      OP_HLT
+      a: 1i
+      b: 2i
+      c: 3i";
+
+    assert_eq!(output(floats), [OutputValue::Float(7.5)]);
+    assert_eq!(output(ints), [OutputValue::Int(7)]);
+  }
+
+  #[test]
+  fn fused_divide_subtract_subtracts_register_a_from_c_over_b() {
+    let floats = "1, 1, 0
+      OP_LEA, FREG_A, self.a
+      OP_LEA, FREG_B, self.b
+      OP_LEA, FREG_C, self.c
+      OP_FSD, FREG_A, FREG_B, FREG_C
+      OP_STR, PRE_STR_FPU, FREG_C
+      OP_HLT
+      a: 1.0f
+      b: 4.0f
+      c: 10.0f";
+    let ints = "1, 1, 0
+      OP_LEA, IREG_A, self.a
+      OP_LEA, IREG_B, self.b
+      OP_LEA, IREG_C, self.c
+      OP_FSD, IREG_A, IREG_B, IREG_C
+      OP_STR, PRE_STR_ALU, IREG_C
+      OP_HLT
+      a: 1i
+      b: 4i
+      c: 10i";
+
+    assert_eq!(output(floats), [OutputValue::Float(1.5)]);
+    assert_eq!(output(ints), [OutputValue::Int(1)]);
+  }
+
+  #[test]
+  fn integer_division_by_zero_is_a_fault() {
+    let mut machine = run(&["1, 1, 0
+      OP_LEA, IREG_C, self.c
+      OP_FSD, IREG_A, IREG_B, IREG_C
+      OP_HLT
+      c: 10i"]);
+
+    assert_eq!(machine.tasks[0].status, Status::Fault);
+    assert_eq!(machine.run_task(0).unwrap_err().to_string(), "task 1.1, word 2: integer division by zero");
+  }
+
+  #[test]
+  fn power_raises_register_b_and_its_inverse_takes_the_logarithm() {
+    let source = "1, 1, 0
+      OP_LEA, FREG_A, self.exponent
+      OP_LEA, FREG_B, self.base
+      OP_POW, PRE_NORMAL, FREG_A, FREG_B
+      OP_STR, PRE_STR_FPU, FREG_B
+      OP_LEA, FREG_A, self.base
+      OP_LEA, FREG_B, self.power
+      OP_POW, PRE_INVERT, FREG_A, FREG_B
+      OP_STR, PRE_STR_FPU, FREG_B
+      OP_HLT
+      exponent: 3.0f
+      base: 2.0f
+      power: 8.0f";
+
+    assert_eq!(output(source), [OutputValue::Float(8.0), OutputValue::Float(3.0)]);
+  }
+
+  #[test]
+  fn trigonometry_writes_register_b_from_register_a() {
+    let source = "1, 1, 0
+      OP_LEA, FREG_A, self.x
+      OP_SIN, PRE_NORMAL, FREG_A, FREG_B
+      OP_STR, PRE_STR_FPU, FREG_B
+      OP_SIN, PRE_INVERT, FREG_A, FREG_B
+      OP_STR, PRE_STR_FPU, FREG_B
+      OP_COS, PRE_NORMAL, FREG_A, FREG_B
+      OP_STR, PRE_STR_FPU, FREG_B
+      OP_COS, PRE_INVERT, FREG_A, FREG_B
+      OP_STR, PRE_STR_FPU, FREG_B
+      OP_TAN, PRE_NORMAL, FREG_A, FREG_B
+      OP_STR, PRE_STR_FPU, FREG_B
+      OP_TAN, PRE_INVERT, FREG_A, FREG_B
+      OP_STR, PRE_STR_FPU, FREG_B
+      OP_HLT
+      x: 0.5f";
+    let x = 0.5f32;
+    let expected = [x.sin(), x.asin(), x.cos(), x.acos(), x.tan(), x.atan()].map(OutputValue::Float);
+
+    assert_eq!(output(source), expected);
+  }
+
+  #[test]
+  fn nor_negates_the_or_of_registers_a_and_b() {
+    let source = "1, 1, 0
+      OP_LEA, IREG_A, self.a
+      OP_LEA, IREG_B, self.b
+      OP_NOR, IREG_A, IREG_B, IREG_C
+      OP_STR, PRE_STR_BIN, IREG_C
+      OP_HLT
+      a: 12i
+      b: 3i";
+
+    assert_eq!(output(source), [OutputValue::Bin(!15)]);
+  }
+
+  #[test]
+  fn moves_between_registers_and_stores_to_ram() {
+    let mut machine = run(&["1, 1, 0
+      OP_LEA, IREG_A, self.a
+      OP_MOV, PRE_MOV_REG, IREG_A, IREG_B
+      OP_MOV, PRE_MOV_RAM, IREG_B, self.b
+      OP_STR, PRE_STR_ALU, IREG_B
+      OP_HLT
+      a: 42i
+      b: 0i"]);
+
+    assert_eq!(machine.output[0].value, OutputValue::Int(42));
+    assert_eq!(machine.tasks[0].ram, [42, 42]);
+
+    machine.registers = Registers::default();
+    machine.run();
+    assert_eq!(machine.tasks[0].ram, [42, 42]);
+  }
+
+  #[test]
+  fn loads_the_data_of_other_tasks_of_the_group() {
+    let machine = run(&[
+      "1, 1, 0
+      OP_LEA, FREG_A, 2, 1
+      OP_STR, PRE_STR_FPU, FREG_A
+      OP_HLT",
+      "1, 2, 0
+      OP_HLT
+      2.5f",
+    ]);
+
+    assert_eq!(machine.output[0].value, OutputValue::Float(2.5));
+  }
+
+  #[test]
+  fn a_false_comparison_aborts_the_task() {
+    let machine = run(&[
+      "1, 1, 0
+      OP_LEA, IREG_A, self.one
+      OP_LEA, IREG_B, self.two
+      OP_CMP, ALU_LT, IREG_A, IREG_B
+      OP_STR, PRE_STR_ALU, IREG_A
+      OP_CMP, ALU_GT, IREG_A, IREG_B
+      OP_STR, PRE_STR_ALU, IREG_B
+      OP_HLT
+      one: 1i
+      two: 2i",
+      "1, 2, 0
+      OP_LEA, IREG_A, self.success
+      OP_CMP, TSX_EQ, 1, IREG_A
+      OP_STR, PRE_STR_ALU, IREG_A
+      OP_HLT
+      success: 255i",
+    ]);
+    let values: Vec<OutputValue> = machine.output.iter().map(|output| output.value).collect();
+
+    assert_eq!(values, [OutputValue::Int(1)]);
+    assert_eq!(machine.tasks[0].status, Status::Unmet);
+    assert_eq!(machine.tasks[1].status, Status::Unmet);
+  }
+
+  #[test]
+  fn waits_for_the_status_of_another_task() {
+    let machine = run(&[
+      "1, 1, 0
+      OP_HLT",
+      "1, 2, 0
+      OP_LEA, IREG_A, self.success
+      OP_CMP, TSX_EQ, 1, IREG_A
+      OP_STR, PRE_STR_ALU, IREG_A
+      OP_HLT
+      success: 255i",
+    ]);
+
+    assert_eq!(machine.output[0].value, OutputValue::Int(255));
+    assert_eq!(machine.tasks[1].status, Status::Success);
+  }
+
+  #[test]
+  fn reads_instruments() {
+    let mut machine = run(&["1, 1, 0
+      OP_GET, INST_FPU, P_FPU_PIE, FREG_A
+      OP_STR, PRE_STR_FPU, FREG_A
+      OP_HLT"]);
+
+    assert_eq!(machine.output[0].value, OutputValue::Float(std::f32::consts::PI));
+
+    machine.detach(INST_FPU);
+    assert_eq!(
+      machine.run_task(0).unwrap_err().to_string(),
+      "task 1.1, word 1: instrument INST_FPU is not attached"
+    );
+  }
+
+  #[test]
+  fn rejects_invalid_frequencies_and_duplicate_tasks() {
+    let task = |source: &str| asm::assemble(source).unwrap();
+
+    assert_eq!(
+      Machine::new(vec![task("1, 1, 128\nOP_HLT")]).unwrap_err(),
+      "task 1.1: 128 is not a frequency (0 once, 1-59 seconds, 60-118 minutes, 119-126 hours, 127 always)"
+    );
+    assert_eq!(
+      Machine::new(vec![task("1, 1, 0\nOP_HLT"), task("1, 1, 5\nOP_HLT")]).unwrap_err(),
+      "task 1.1 is loaded twice"
+    );
+  }
+}
@@ -0,0 +1,17 @@
+pub mod cost;
+pub mod debugger;
+pub mod decode;
+pub mod instruments;
+pub mod loader;
+pub mod machine;
+pub mod orbit;
+pub mod registers;
+pub mod scheduler;
+pub mod telemetry;
+pub mod trace;
+
+pub use debugger::Debugger;
+pub use instruments::Instrument;
+pub use machine::*;
+pub use registers::{Registers, Value};
+pub use scheduler::{Frequency, Scheduler};
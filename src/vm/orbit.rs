@@ -0,0 +1,208 @@
+use std::f64::consts::PI;
+
+/// Earth gravitational parameter, km³/s².
+const MU: f64 = 398_600.441_8;
+/// WGS84 equatorial radius, km.
+const EARTH_RADIUS: f64 = 6378.137;
+/// WGS84 flattening.
+const FLATTENING: f64 = 1.0 / 298.257_223_563;
+/// Second zonal harmonic of the Earth gravity field.
+const J2: f64 = 1.082_626_68e-3;
+/// Earth rotation rate, rad/s.
+const EARTH_ROTATION: f64 = 7.292_115_0e-5;
+/// Julian date of J2000.0 and of the GPS epoch (1980-01-06).
+const JD_J2000: f64 = 2_451_545.0;
+const JD_GPS_EPOCH: f64 = 2_444_244.5;
+/// GPS time is ahead of UTC by the leap seconds since 1980.
+const GPS_LEAP_SECONDS: f64 = 18.0;
+const SECONDS_PER_DAY: f64 = 86_400.0;
+const SECONDS_PER_WEEK: f64 = 604_800.0;
+
+/// Mean Keplerian elements at an epoch.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Elements {
+  /// Km.
+  pub semi_major_axis: f64,
+  pub eccentricity: f64,
+  /// Degrees.
+  pub inclination: f64,
+  /// Right ascension of the ascending node, degrees.
+  pub raan: f64,
+  /// Argument of perigee, degrees.
+  pub perigee: f64,
+  /// Degrees.
+  pub mean_anomaly: f64,
+  /// Julian date (UTC) of the elements, which is mission time 0.
+  pub epoch: f64,
+}
+
+/// Geodetic position over the WGS84 ellipsoid.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Position {
+  /// Degrees, north positive.
+  pub latitude: f64,
+  /// Degrees in `-180..180`, east positive.
+  pub longitude: f64,
+  /// Km above the ellipsoid.
+  pub altitude: f64,
+}
+
+/// Julian date of January 1st, 00:00 UTC, of a Gregorian year.
+fn julian_year_start(year: i64) -> f64 {
+  let y = year - 1;
+  1_721_425.5 + (365 * y + y / 4 - y / 100 + y / 400) as f64
+}
+
+/// A TLE field, given by the 1-based columns of the format.
+fn field(line: &str, first: usize, last: usize) -> Result<&str, String> {
+  line
+    .get(first - 1..last)
+    .map(str::trim)
+    .ok_or_else(|| format!("line `{}` is too short for a TLE", line))
+}
+
+fn number(line: &str, first: usize, last: usize) -> Result<f64, String> {
+  let text = field(line, first, last)?;
+
+  text.parse().map_err(|_| format!("invalid TLE field `{}`", text))
+}
+
+fn check_checksum(line: &str) -> Result<(), String> {
+  let expected = field(line, 69, 69)?;
+  let sum: u32 = line[..68]
+    .chars()
+    .map(|ch| match ch {
+      '-' => 1,
+      ch => ch.to_digit(10).unwrap_or(0),
+    })
+    .sum();
+
+  if expected == (sum % 10).to_string() {
+    Ok(())
+  }
+  else {
+    Err(format!("wrong checksum on TLE line `{}`", line))
+  }
+}
+
+impl Elements {
+  /// Reads the mean elements of a two-line element set, with or without its
+  /// title line. They are propagated with the J2 model of `Orbit`, not SGP4,
+  /// which is close enough for the few days after the TLE epoch.
+  pub fn from_tle(text: &str) -> Result<Elements, String> {
+    let lines: Vec<&str> = text.lines().map(str::trim_end).filter(|line| !line.is_empty()).collect();
+    let line1 = lines.iter().find(|line| line.starts_with("1 "));
+    let line2 = lines.iter().find(|line| line.starts_with("2 "));
+    let (line1, line2) = match (line1, line2) {
+      (Some(line1), Some(line2)) => (*line1, *line2),
+      _ => return Err("expected the two lines of a TLE, starting with `1 ` and `2 `".to_string()),
+    };
+
+    check_checksum(line1)?;
+    check_checksum(line2)?;
+
+    let year = number(line1, 19, 20)? as i64;
+    let year = if year < 57 { 2000 + year } else { 1900 + year };
+    let day = number(line1, 21, 32)?;
+    let revolutions_per_day = number(line2, 53, 63)?;
+    let mean_motion = revolutions_per_day * 2.0 * PI / SECONDS_PER_DAY;
+
+    Ok(Elements {
+      semi_major_axis: (MU / (mean_motion * mean_motion)).cbrt(),
+      eccentricity: format!("0.{}", field(line2, 27, 33)?)
+        .parse()
+        .map_err(|_| "invalid TLE eccentricity".to_string())?,
+      inclination: number(line2, 9, 16)?,
+      raan: number(line2, 18, 25)?,
+      perigee: number(line2, 35, 42)?,
+      mean_anomaly: number(line2, 44, 51)?,
+      epoch: julian_year_start(year) + day - 1.0,
+    })
+  }
+}
+
+/// Keplerian orbit with the secular J2 drift of the node, the perigee and
+/// the mean anomaly.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Orbit {
+  pub elements: Elements,
+}
+
+fn solve_kepler(mean_anomaly: f64, eccentricity: f64) -> f64 {
+  let mut eccentric = mean_anomaly;
+
+  for _ in 0..20 {
+    let delta = (eccentric - eccentricity * eccentric.sin() - mean_anomaly) / (1.0 - eccentricity * eccentric.cos());
+    eccentric -= delta;
+
+    if delta.abs() < 1e-12 {
+      break;
+    }
+  }
+
+  eccentric
+}
+
+impl Orbit {
+  pub fn new(elements: Elements) -> Orbit {
+    Orbit { elements }
+  }
+
+  /// Position `seconds` after the epoch of the elements.
+  pub fn position(&self, seconds: f64) -> Position {
+    let elements = &self.elements;
+    let a = elements.semi_major_axis;
+    let e = elements.eccentricity;
+    let i = elements.inclination.to_radians();
+    let n = (MU / (a * a * a)).sqrt();
+    let p = a * (1.0 - e * e);
+    let k = 1.5 * J2 * (EARTH_RADIUS / p).powi(2) * n;
+    let cos_i = i.cos();
+
+    let raan = elements.raan.to_radians() - k * cos_i * seconds;
+    let perigee = elements.perigee.to_radians() + 0.5 * k * (5.0 * cos_i * cos_i - 1.0) * seconds;
+    let mean_anomaly = elements.mean_anomaly.to_radians()
+      + (n + 0.5 * k * (1.0 - e * e).sqrt() * (3.0 * cos_i * cos_i - 1.0)) * seconds;
+
+    let eccentric = solve_kepler(mean_anomaly.rem_euclid(2.0 * PI), e);
+    let true_anomaly = 2.0 * ((1.0 + e).sqrt() * (eccentric / 2.0).sin()).atan2((1.0 - e).sqrt() * (eccentric / 2.0).cos());
+    let r = a * (1.0 - e * eccentric.cos());
+    let u = perigee + true_anomaly;
+
+    // Inertial position, then rotated by the sidereal angle into Earth-fixed.
+    let x = r * (raan.cos() * u.cos() - raan.sin() * u.sin() * cos_i);
+    let y = r * (raan.sin() * u.cos() + raan.cos() * u.sin() * cos_i);
+    let z = r * u.sin() * i.sin();
+
+    let gmst = (280.460_618_37 + 360.985_647_366_29 * (elements.epoch - JD_J2000)).to_radians() + EARTH_ROTATION * seconds;
+    let (xe, ye) = (x * gmst.cos() + y * gmst.sin(), y * gmst.cos() - x * gmst.sin());
+
+    geodetic(xe, ye, z)
+  }
+
+  /// GPS time of week, in seconds, `seconds` after the epoch of the elements.
+  pub fn gps_time_of_week(&self, seconds: f64) -> f64 {
+    ((self.elements.epoch - JD_GPS_EPOCH) * SECONDS_PER_DAY + GPS_LEAP_SECONDS + seconds).rem_euclid(SECONDS_PER_WEEK)
+  }
+}
+
+/// Converts Earth-fixed coordinates, in km, to WGS84 geodetic ones.
+fn geodetic(x: f64, y: f64, z: f64) -> Position {
+  let e2 = FLATTENING * (2.0 - FLATTENING);
+  let p = (x * x + y * y).sqrt();
+  let mut latitude = z.atan2(p * (1.0 - e2));
+  let mut altitude = 0.0;
+
+  for _ in 0..5 {
+    let n = EARTH_RADIUS / (1.0 - e2 * latitude.sin().powi(2)).sqrt();
+
+    altitude = p / latitude.cos() - n;
+    latitude = z.atan2(p * (1.0 - e2 * n / (n + altitude)));
+  }
+
+  Position {
+    latitude: latitude.to_degrees(),
+    longitude: y.atan2(x).to_degrees(),
+    altitude,
+  }
+}
@@ -0,0 +1,101 @@
+use std::fmt;
+
+use crate::asm::Word;
+
+/// Number of registers in each of the integer and float register files.
+pub const REGISTER_COUNT: usize = 16;
+
+/// Register ids `0x00..=0x0F` are the integer (ALU) registers `IREG_A..`,
+/// `0x10..=0x1F` the float (FPU) registers `FREG_A..`.
+pub const FREG_BASE: u8 = 0x10;
+
+/// The content of a register. Memory words carry no type: a word loaded by
+/// `OP_LEA` is read as an `i32` or as the bits of an `f32` depending on the
+/// destination register.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Value {
+  Int(i32),
+  Float(f32),
+}
+
+impl Value {
+  pub fn as_int(self) -> i32 {
+    match self {
+      Value::Int(value) => value,
+      Value::Float(value) => value as i32,
+    }
+  }
+
+  pub fn as_float(self) -> f32 {
+    match self {
+      Value::Int(value) => value as f32,
+      Value::Float(value) => value,
+    }
+  }
+
+  /// The raw memory representation of the value.
+  pub fn to_word(self) -> Word {
+    match self {
+      Value::Int(value) => value as Word,
+      Value::Float(value) => value.to_bits(),
+    }
+  }
+}
+
+impl fmt::Display for Value {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Value::Int(value) => write!(f, "{}", value),
+      Value::Float(value) => write!(f, "{:?}", value),
+    }
+  }
+}
+
+/// The integer and float register files. They belong to the VM, not to a
+/// task: the VM context is not saved between tasks.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Registers {
+  pub int: [i32; REGISTER_COUNT],
+  pub float: [f32; REGISTER_COUNT],
+}
+
+impl Registers {
+  pub fn is_valid(id: u8) -> bool {
+    usize::from(id) < 2 * REGISTER_COUNT
+  }
+
+  pub fn is_float(id: u8) -> bool {
+    id >= FREG_BASE
+  }
+
+  /// Reads a register; `id` must be valid.
+  pub fn get(&self, id: u8) -> Value {
+    if Registers::is_float(id) {
+      Value::Float(self.float[usize::from(id - FREG_BASE)])
+    }
+    else {
+      Value::Int(self.int[usize::from(id)])
+    }
+  }
+
+  /// Writes a register, converting the value to the register's type; `id`
+  /// must be valid.
+  pub fn set(&mut self, id: u8, value: Value) {
+    if Registers::is_float(id) {
+      self.float[usize::from(id - FREG_BASE)] = value.as_float();
+    }
+    else {
+      self.int[usize::from(id)] = value.as_int();
+    }
+  }
+
+  /// Writes a memory word to a register, without conversion.
+  pub fn load(&mut self, id: u8, word: Word) {
+    if Registers::is_float(id) {
+      self.float[usize::from(id - FREG_BASE)] = f32::from_bits(word);
+    }
+    else {
+      self.int[usize::from(id)] = word as i32;
+    }
+  }
+}
@@ -0,0 +1,325 @@
+use std::fmt;
+use std::ops::Range;
+
+use crate::vm::machine::{Machine, Status, Step};
+
+/// Task frequency, encoded in the `freq` byte of the task header. The
+/// whitepaper ("Task frequency") has tasks run once, as fast as the scheduler
+/// allows, or every 1 to 59 seconds, 1 to 59 minutes or 1 to 8 hours, in one
+/// byte; its examples give `20` for every 20 seconds and `60` for every
+/// minute. These 126 intervals take the bytes `1..=126`, leaving `0` and
+/// `127` for once and always.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Frequency {
+  /// `0`: runs at the first tick only.
+  Once,
+  /// Every N seconds: `1..=59` for 1 to 59 seconds, `60..=118` for 1 to 59
+  /// minutes and `119..=126` for 1 to 8 hours.
+  Every(u32),
+  /// `127`: at every tick, as fast as the scheduler allows.
+  Always,
+}
+
+const FREQ_MINUTES: u8 = 60;
+const FREQ_HOURS: u8 = 119;
+const FREQ_ALWAYS: u8 = 127;
+
+const MINUTE: u32 = 60;
+const HOUR: u32 = 3600;
+
+impl Frequency {
+  /// The frequency of a header byte, if it is one; bytes above 127 are not.
+  pub fn from_byte(freq: u8) -> Option<Frequency> {
+    let frequency = match freq {
+      0 => Frequency::Once,
+      1..=59 => Frequency::Every(u32::from(freq)),
+      FREQ_MINUTES..=118 => Frequency::Every(MINUTE * u32::from(freq - FREQ_MINUTES + 1)),
+      FREQ_HOURS..=126 => Frequency::Every(HOUR * u32::from(freq - FREQ_HOURS + 1)),
+      FREQ_ALWAYS => Frequency::Always,
+      _ => return None,
+    };
+
+    Some(frequency)
+  }
+
+  /// The header byte of a frequency, if it can be encoded.
+  pub fn to_byte(self) -> Option<u8> {
+    match self {
+      Frequency::Once => Some(0),
+      Frequency::Always => Some(FREQ_ALWAYS),
+      Frequency::Every(seconds @ 1..=59) => Some(seconds as u8),
+      Frequency::Every(seconds) if seconds % MINUTE == 0 && (1..=59).contains(&(seconds / MINUTE)) => {
+        Some(FREQ_MINUTES + (seconds / MINUTE - 1) as u8)
+      },
+      Frequency::Every(seconds) if seconds % HOUR == 0 && (1..=8).contains(&(seconds / HOUR)) => {
+        Some(FREQ_HOURS + (seconds / HOUR - 1) as u8)
+      },
+      Frequency::Every(_) => None,
+    }
+  }
+
+  /// Whether a task is due at `time`, `start` being the first tick.
+  pub fn is_due(self, start: u32, time: u32) -> bool {
+    match self {
+      Frequency::Once => time == start,
+      Frequency::Every(0) => time == start,
+      Frequency::Every(seconds) => (time - start) % seconds == 0,
+      Frequency::Always => true,
+    }
+  }
+}
+
+impl fmt::Display for Frequency {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Frequency::Once => write!(f, "once"),
+      Frequency::Always => write!(f, "always"),
+      Frequency::Every(seconds) if *seconds > 0 && seconds % HOUR == 0 => write!(f, "{}h", seconds / HOUR),
+      Frequency::Every(seconds) if *seconds > 0 && seconds % MINUTE == 0 => write!(f, "{}m", seconds / MINUTE),
+      Frequency::Every(seconds) => write!(f, "{}s", seconds),
+    }
+  }
+}
+
+/// One run of a task.
+#[derive(Clone, Debug)]
+pub struct LogEntry {
+  pub time: u32,
+  pub group_id: u8,
+  pub task_id: u8,
+  pub status: Status,
+  /// The `OP_STR` records of the run, as a range of `Machine::output`.
+  pub output: Range<usize>,
+  pub error: Option<String>,
+}
+
+impl fmt::Display for LogEntry {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(
+      f,
+      "{:>6}s  task {}.{}  {}",
+      self.time, self.group_id, self.task_id, self.status
+    )?;
+
+    if let Some(error) = &self.error {
+      write!(f, ": {}", error)?;
+    }
+
+    Ok(())
+  }
+}
+
+/// What `Scheduler::step` did.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Event {
+  /// The task at `index` is due and starts; no instruction ran yet.
+  Started { index: usize },
+  /// The instruction at `pc` ran and the task goes on.
+  Executed { index: usize, pc: usize },
+  /// The instruction at `pc` ended the run, logged in `log[entry]`.
+  Finished { index: usize, pc: usize, entry: usize },
+  /// No task is left to run at `time`; the clock has advanced.
+  Tick { time: u32 },
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Run {
+  index: usize,
+  pc: usize,
+  first_output: usize,
+}
+
+/// Discrete-time scheduler with a one second tick. At every tick the tasks
+/// due are run in group and task order, so a task sees the data and the
+/// status left by the tasks before it; `OP_LEA` and `TSX_*` only reach tasks
+/// of the same group, and a task only writes its own data.
+#[derive(Debug)]
+pub struct Scheduler {
+  pub machine: Machine,
+  pub log: Vec<LogEntry>,
+  start: u32,
+  /// The next task to consider in the current tick.
+  next: usize,
+  running: Option<Run>,
+}
+
+impl Scheduler {
+  /// Starts the timeline at the machine's current time.
+  pub fn new(machine: Machine) -> Scheduler {
+    Scheduler {
+      start: machine.time,
+      machine,
+      log: Vec::new(),
+      next: 0,
+      running: None,
+    }
+  }
+
+  /// The task index and instruction of the run in progress, if any.
+  pub fn current(&self) -> Option<(usize, usize)> {
+    self.running.map(|run| (run.index, run.pc))
+  }
+
+  /// Advances by one event: starts the next due task, executes one
+  /// instruction of the running task or ends the tick.
+  pub fn step(&mut self) -> Event {
+    let run = match self.running {
+      Some(run) => run,
+      None => {
+        let time = self.machine.time;
+        let start = self.start;
+        let tasks = &self.machine.tasks;
+        let due = |index: &usize| Frequency::from_byte(tasks[*index].header.freq).is_some_and(|freq| freq.is_due(start, time));
+
+        match (self.next..tasks.len()).find(due) {
+          Some(index) => {
+            self.next = index + 1;
+            self.running = Some(Run {
+              index,
+              pc: 0,
+              first_output: self.machine.output.len(),
+            });
+
+            return Event::Started { index };
+          },
+          None => {
+            self.next = 0;
+            self.machine.time += 1;
+
+            return Event::Tick { time };
+          },
+        }
+      },
+    };
+
+    let (status, error) = match self.machine.step(run.index, run.pc) {
+      Ok(Step::Next) => {
+        self.running = Some(Run { pc: run.pc + 1, ..run });
+
+        return Event::Executed {
+          index: run.index,
+          pc: run.pc,
+        };
+      },
+      Ok(Step::Halt) => (Status::Success, None),
+      Ok(Step::Abort) => (Status::Unmet, None),
+      Err(err) => (Status::Fault, Some(err.message)),
+    };
+
+    let header = self.machine.tasks[run.index].header;
+
+    self.machine.tasks[run.index].status = status;
+    self.running = None;
+    self.log.push(LogEntry {
+      time: self.machine.time,
+      group_id: header.group_id,
+      task_id: header.task_id,
+      status,
+      output: run.first_output..self.machine.output.len(),
+      error,
+    });
+
+    Event::Finished {
+      index: run.index,
+      pc: run.pc,
+      entry: self.log.len() - 1,
+    }
+  }
+
+  /// Runs the rest of the current tick, then advances the clock.
+  pub fn tick(&mut self) {
+    while !matches!(self.step(), Event::Tick { .. }) {}
+  }
+  /// Runs the timeline for `seconds` ticks.
+  pub fn run_for(&mut self, seconds: u32) {
+    for _ in 0..seconds {
+      self.tick();
+    }
+  }
+
+  /// Per-task counts of runs by status, in task order.
+  pub fn summary(&self) -> String {
+    let mut output = String::from("TASK   FREQ    RUNS  SUCCESS  UNMET  FAULT\n");
+
+    for task in &self.machine.tasks {
+      let header = task.header;
+      let runs = || {
+        self
+          .log
+          .iter()
+          .filter(move |entry| entry.group_id == header.group_id && entry.task_id == header.task_id)
+      };
+      let count = |status| runs().filter(|entry| entry.status == status).count();
+
+      output.push_str(&format!(
+        "{:<5}  {:<6}  {:>4}  {:>7}  {:>5}  {:>5}\n",
+        format!("{}.{}", header.group_id, header.task_id),
+        Frequency::from_byte(header.freq).map_or_else(|| header.freq.to_string(), |freq| freq.to_string()),
+        runs().count(),
+        count(Status::Success),
+        count(Status::Unmet),
+        count(Status::Fault)
+      ));
+    }
+
+    output
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn decodes_the_intervals_of_the_whitepaper() {
+    assert_eq!(Frequency::from_byte(0), Some(Frequency::Once));
+    assert_eq!(Frequency::from_byte(1), Some(Frequency::Every(1)));
+    assert_eq!(Frequency::from_byte(20), Some(Frequency::Every(20)));
+    assert_eq!(Frequency::from_byte(59), Some(Frequency::Every(59)));
+    assert_eq!(Frequency::from_byte(60), Some(Frequency::Every(60)));
+    assert_eq!(Frequency::from_byte(61), Some(Frequency::Every(120)));
+    assert_eq!(Frequency::from_byte(118), Some(Frequency::Every(59 * 60)));
+    assert_eq!(Frequency::from_byte(119), Some(Frequency::Every(3600)));
+    assert_eq!(Frequency::from_byte(126), Some(Frequency::Every(8 * 3600)));
+    assert_eq!(Frequency::from_byte(127), Some(Frequency::Always));
+    assert_eq!(Frequency::from_byte(128), None);
+    assert_eq!(Frequency::from_byte(255), None);
+  }
+
+  #[test]
+  fn encodes_every_byte_it_decodes() {
+    for byte in 0..=127 {
+      let freq = Frequency::from_byte(byte).unwrap();
+
+      assert_eq!(freq.to_byte(), Some(byte), "{}", freq);
+    }
+  }
+
+  #[test]
+  fn rejects_intervals_between_the_steps() {
+    for seconds in [0, 61, 90, 59 * 60 + 30, 3600 + 60, 9 * 3600, 24 * 3600] {
+      assert_eq!(Frequency::Every(seconds).to_byte(), None, "{}s", seconds);
+    }
+  }
+
+  #[test]
+  fn shows_the_unit_of_the_interval() {
+    let shown: Vec<String> = [20, 60, 119, 126]
+      .iter()
+      .map(|byte| Frequency::from_byte(*byte).unwrap().to_string())
+      .collect();
+
+    assert_eq!(shown, ["20s", "1m", "1h", "8h"]);
+  }
+
+  #[test]
+  fn runs_a_task_every_interval_from_its_start() {
+    let minute = Frequency::from_byte(60).unwrap();
+
+    assert!(minute.is_due(5, 5));
+    assert!(!minute.is_due(5, 64));
+    assert!(minute.is_due(5, 65));
+    assert!(Frequency::Once.is_due(5, 5));
+    assert!(!Frequency::Once.is_due(5, 65));
+  }
+}
@@ -0,0 +1,237 @@
+use std::rc::Rc;
+
+use crate::asm::isa::*;
+use crate::json::Json;
+use crate::vm::instruments::Instrument;
+use crate::vm::machine::Machine;
+use crate::vm::registers::Value;
+
+/// The samples of one instrument parameter, ordered by time.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Series {
+  pub instrument: u8,
+  pub param: u8,
+  pub samples: Vec<(f64, f64)>,
+}
+
+/// Timestamped instrument readings, e.g. downlinked OPS-SAT telemetry. Mission
+/// time 0 is the earliest timestamp of the file.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Telemetry {
+  pub start: f64,
+  pub series: Vec<Series>,
+}
+
+/// The instrument and parameter of a column such as `P_GPS_LATT`.
+fn column(name: &str) -> Option<(u8, u8)> {
+  let family = name.get(2..6)?.to_ascii_uppercase();
+  let instrument = lookup(INSTRUMENTS, &format!("INST_{}", family.trim_end_matches('_')))?;
+
+  Some((instrument, lookup(PARAMETERS, name)?))
+}
+
+impl Telemetry {
+  /// Parses CSV with a header line: a `time` column in seconds and one
+  /// column per parameter, named by its mnemonic. Empty cells are missing
+  /// samples and lines starting with `#` are comments.
+  pub fn parse_csv(text: &str) -> Result<Telemetry, String> {
+    let mut lines = text
+      .lines()
+      .enumerate()
+      .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'));
+
+    let header: Vec<&str> = lines
+      .next()
+      .ok_or_else(|| "the file has no header line".to_string())?
+      .1
+      .split(',')
+      .map(str::trim)
+      .collect();
+    let time_column = header
+      .iter()
+      .position(|name| name.eq_ignore_ascii_case("time"))
+      .ok_or_else(|| "the header has no `time` column".to_string())?;
+
+    let mut records = Vec::new();
+    for (index, line) in lines {
+      let cells: Vec<&str> = line.split(',').map(str::trim).collect();
+      let number = |cell: &str| {
+        cell
+          .parse::<f64>()
+          .map_err(|_| format!("line {}: invalid number `{}`", index + 1, cell))
+      };
+
+      let time = number(cells.get(time_column).copied().unwrap_or(""))?;
+      let mut values = Vec::new();
+
+      for (name, cell) in header.iter().zip(&cells) {
+        if !name.eq_ignore_ascii_case("time") && !cell.is_empty() {
+          values.push((name.to_string(), number(cell)?));
+        }
+      }
+
+      records.push((time, values));
+    }
+
+    Telemetry::from_records(records)
+  }
+
+  /// Parses a JSON array of records such as
+  /// `{"time": 0.0, "P_GPS_LATT": 48.1, "P_ADC_MODE": 5}`.
+  pub fn parse_json(text: &str) -> Result<Telemetry, String> {
+    let json = Json::parse(text).map_err(|err| err.to_string())?;
+    let mut records = Vec::new();
+
+    for (index, record) in json
+      .as_array()
+      .ok_or_else(|| "expected an array of records".to_string())?
+      .iter()
+      .enumerate()
+    {
+      let members = match record {
+        Json::Object(members) => members,
+        _ => return Err(format!("record {}: expected an object", index)),
+      };
+      let time = record
+        .get("time")
+        .and_then(Json::as_f64)
+        .ok_or_else(|| format!("record {}: missing a numeric `time`", index))?;
+      let mut values = Vec::new();
+
+      for (name, value) in members {
+        if name != "time" {
+          let value = value
+            .as_f64()
+            .ok_or_else(|| format!("record {}: `{}` is not a number", index, name))?;
+          values.push((name.clone(), value));
+        }
+      }
+
+      records.push((time, values));
+    }
+
+    Telemetry::from_records(records)
+  }
+
+  fn from_records(records: Vec<(f64, Vec<(String, f64)>)>) -> Result<Telemetry, String> {
+    let mut telemetry = Telemetry {
+      start: records.iter().map(|(time, _)| *time).fold(f64::INFINITY, f64::min),
+      series: Vec::new(),
+    };
+
+    for (time, values) in records {
+      for (name, value) in values {
+        let (instrument, param) = column(&name).ok_or_else(|| format!("unknown parameter `{}`", name))?;
+
+        match telemetry
+          .series
+          .iter_mut()
+          .find(|series| series.instrument == instrument && series.param == param)
+        {
+          Some(series) => series.samples.push((time, value)),
+          None => telemetry.series.push(Series {
+            instrument,
+            param,
+            samples: vec![(time, value)],
+          }),
+        }
+      }
+    }
+
+    for series in &mut telemetry.series {
+      series
+        .samples
+        .sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+    }
+
+    Ok(telemetry)
+  }
+
+  fn series(&self, instrument: u8, param: u8) -> Option<&Series> {
+    self
+      .series
+      .iter()
+      .find(|series| series.instrument == instrument && series.param == param)
+  }
+
+  /// Wraps every instrument of `machine` that has telemetry, so that its
+  /// reads are served from the telemetry.
+  pub fn attach(self, machine: &mut Machine) {
+    let telemetry = Rc::new(self);
+    let mut instruments: Vec<u8> = telemetry.series.iter().map(|series| series.instrument).collect();
+
+    instruments.sort_unstable();
+    instruments.dedup();
+    for id in instruments {
+      if let Some(fallback) = machine.detach(id) {
+        machine.attach(
+          id,
+          Box::new(Replay {
+            id,
+            telemetry: telemetry.clone(),
+            fallback,
+          }),
+        );
+      }
+    }
+  }
+}
+
+impl Series {
+  /// The value at `time`: interpolated linearly between samples, or the last
+  /// sample for discrete parameters, and held before the first and after the
+  /// last sample.
+  pub fn at(&self, time: f64, discrete: bool) -> f64 {
+    let after = self.samples.iter().position(|(sample, _)| *sample > time);
+
+    match after {
+      Some(0) => self.samples[0].1,
+      None => self.samples.last().map_or(0.0, |(_, value)| *value),
+      Some(index) => {
+        let (t0, v0) = self.samples[index - 1];
+        let (t1, v1) = self.samples[index];
+
+        if discrete {
+          v0
+        }
+        else {
+          v0 + (v1 - v0) * (time - t0) / (t1 - t0)
+        }
+      },
+    }
+  }
+}
+
+/// Serves the parameters found in the telemetry and leaves the others, as
+/// well as writes and actions, to the instrument it wraps. A parameter is
+/// discrete, and not interpolated, when the wrapped instrument reads it as
+/// an integer, like `P_ADC_MODE`.
+#[derive(Debug)]
+struct Replay {
+  id: u8,
+  telemetry: Rc<Telemetry>,
+  fallback: Box<dyn Instrument>,
+}
+
+impl Instrument for Replay {
+  fn get(&mut self, param: u8, time: u32) -> Result<Value, String> {
+    let series = match self.telemetry.series(self.id, param) {
+      Some(series) => series,
+      None => return self.fallback.get(param, time),
+    };
+    let at = self.telemetry.start + f64::from(time);
+
+    match self.fallback.get(param, time) {
+      Ok(Value::Int(_)) => Ok(Value::Int(series.at(at, true).round() as i32)),
+      _ => Ok(Value::Float(series.at(at, false) as f32)),
+    }
+  }
+
+  fn set(&mut self, param: u8, value: Value, time: u32) -> Result<(), String> {
+    self.fallback.set(param, value, time)
+  }
+
+  fn act(&mut self, action: u8, argument: Value, time: u32) -> Result<(), String> {
+    self.fallback.act(action, argument, time)
+  }
+}
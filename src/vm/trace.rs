@@ -0,0 +1,582 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::asm::isa::*;
+use crate::asm::Word;
+use crate::vm::instruments::Instrument;
+use crate::vm::machine::{OutputValue, Status};
+use crate::vm::registers::{Value, REGISTER_COUNT};
+use crate::vm::scheduler::{Event, Scheduler};
+
+const MAGIC: &str = "# splice trace";
+
+/// One line of a trace. The events of an instruction follow its
+/// `Instruction` event: instrument reads, register and data writes, output
+/// and, for the last instruction of a run, the task status.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TraceEvent {
+  Instruction {
+    time: u32,
+    group_id: u8,
+    task_id: u8,
+    /// Index of the word within the task, the header being word 0.
+    word: usize,
+    encoding: Word,
+  },
+  Read { instrument: u8, param: u8, value: Value },
+  /// A call to an instrument that returned an error; `param` is the action
+  /// of an `Access::Act`, and `value` the value given to a set or an action.
+  Failed {
+    instrument: u8,
+    access: Access,
+    param: u8,
+    value: Option<Value>,
+    message: String,
+  },
+  Register { id: u8, value: Value },
+  Memory { group_id: u8, task_id: u8, address: u8, value: Word },
+  Output { group_id: u8, task_id: u8, value: OutputValue },
+  Finished { group_id: u8, task_id: u8, status: Status },
+}
+
+/// The instrument call of a `TraceEvent::Failed`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Access {
+  Get,
+  Set,
+  Act,
+}
+
+impl fmt::Display for Access {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Access::Get => write!(f, "get"),
+      Access::Set => write!(f, "set"),
+      Access::Act => write!(f, "act"),
+    }
+  }
+}
+
+/// Float values are written as their bits, so a replay reproduces them
+/// exactly, followed by their decimal value for the reader.
+struct TraceValue(Value);
+
+impl fmt::Display for TraceValue {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self.0 {
+      Value::Int(value) => write!(f, "i {}", value),
+      Value::Float(value) => write!(f, "f {:08x} {:?}", value.to_bits(), value),
+    }
+  }
+}
+
+fn instrument_name(id: u8) -> String {
+  name_of(INSTRUMENTS, "INST_", id).map_or_else(|| id.to_string(), str::to_string)
+}
+
+fn param_name(instrument: u8, param: u8) -> String {
+  let prefix = format!("P_{}_", instrument_name(instrument).trim_start_matches("INST_"));
+
+  name_of(PARAMETERS, &prefix, param).map_or_else(|| param.to_string(), str::to_string)
+}
+
+fn action_name(instrument: u8, action: u8) -> String {
+  let prefix = format!("A_{}_", instrument_name(instrument).trim_start_matches("INST_"));
+
+  name_of(ACTIONS, &prefix, action).map_or_else(|| action.to_string(), str::to_string)
+}
+
+impl fmt::Display for TraceEvent {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      TraceEvent::Instruction {
+        time,
+        group_id,
+        task_id,
+        word,
+        encoding,
+      } => write!(f, "I {} {}.{} {} {:08x}", time, group_id, task_id, word, encoding),
+      TraceEvent::Read {
+        instrument,
+        param,
+        value,
+      } => write!(
+        f,
+        "G {} {} {}",
+        instrument_name(*instrument),
+        param_name(*instrument, *param),
+        TraceValue(*value)
+      ),
+      TraceEvent::Failed {
+        instrument,
+        access,
+        param,
+        value,
+        message,
+      } => {
+        let param = match access {
+          Access::Act => action_name(*instrument, *param),
+          Access::Get | Access::Set => param_name(*instrument, *param),
+        };
+
+        write!(f, "F {} {} {}", instrument_name(*instrument), access, param)?;
+        if let Some(value) = value {
+          write!(f, " {}", TraceValue(*value))?;
+        }
+        write!(f, ": {}", message)
+      },
+      TraceEvent::Register { id, value } => write!(
+        f,
+        "R {} {}",
+        name_of(REGISTERS, "", *id).unwrap_or("?"),
+        TraceValue(*value)
+      ),
+      TraceEvent::Memory {
+        group_id,
+        task_id,
+        address,
+        value,
+      } => write!(f, "M {}.{} {} {:08x}", group_id, task_id, address, value),
+      TraceEvent::Output {
+        group_id,
+        task_id,
+        value,
+      } => match value {
+        OutputValue::Int(value) => write!(f, "O {}.{} ALU {}", group_id, task_id, value),
+        OutputValue::Float(value) => write!(f, "O {}.{} FPU {:08x} {:?}", group_id, task_id, value.to_bits(), value),
+        OutputValue::Bin(value) => write!(f, "O {}.{} BIN {:08x}", group_id, task_id, value),
+      },
+      TraceEvent::Finished {
+        group_id,
+        task_id,
+        status,
+      } => write!(f, "E {}.{} {}", group_id, task_id, status),
+    }
+  }
+}
+
+fn parse_task(field: Option<&str>) -> Option<(u8, u8)> {
+  let (group, task) = field?.split_once('.')?;
+
+  Some((group.parse().ok()?, task.parse().ok()?))
+}
+
+fn parse_hex(field: Option<&str>) -> Option<Word> {
+  Word::from_str_radix(field?, 16).ok()
+}
+
+fn parse_value<'a>(fields: &mut impl Iterator<Item = &'a str>) -> Option<Value> {
+  match fields.next()? {
+    "i" => Some(Value::Int(fields.next()?.parse().ok()?)),
+    "f" => Some(Value::Float(f32::from_bits(parse_hex(fields.next())?))),
+    _ => None,
+  }
+}
+
+fn parse_name(table: Table, field: Option<&str>) -> Option<u8> {
+  let field = field?;
+
+  lookup(table, field).or_else(|| field.parse().ok())
+}
+
+impl TraceEvent {
+  fn parse(line: &str) -> Option<TraceEvent> {
+    if let Some(failed) = line.strip_prefix("F ") {
+      return TraceEvent::parse_failed(failed);
+    }
+
+    let mut fields = line.split_whitespace();
+
+    let event = match fields.next()? {
+      "I" => {
+        let time = fields.next()?.parse().ok()?;
+        let (group_id, task_id) = parse_task(fields.next())?;
+
+        TraceEvent::Instruction {
+          time,
+          group_id,
+          task_id,
+          word: fields.next()?.parse().ok()?,
+          encoding: parse_hex(fields.next())?,
+        }
+      },
+      "G" => TraceEvent::Read {
+        instrument: parse_name(INSTRUMENTS, fields.next())?,
+        param: parse_name(PARAMETERS, fields.next())?,
+        value: parse_value(&mut fields)?,
+      },
+      "R" => TraceEvent::Register {
+        id: parse_name(REGISTERS, fields.next())?,
+        value: parse_value(&mut fields)?,
+      },
+      "M" => {
+        let (group_id, task_id) = parse_task(fields.next())?;
+
+        TraceEvent::Memory {
+          group_id,
+          task_id,
+          address: fields.next()?.parse().ok()?,
+          value: parse_hex(fields.next())?,
+        }
+      },
+      "O" => {
+        let (group_id, task_id) = parse_task(fields.next())?;
+        let value = match fields.next()? {
+          "ALU" => OutputValue::Int(fields.next()?.parse().ok()?),
+          "FPU" => OutputValue::Float(f32::from_bits(parse_hex(fields.next())?)),
+          "BIN" => OutputValue::Bin(parse_hex(fields.next())?),
+          _ => return None,
+        };
+
+        TraceEvent::Output {
+          group_id,
+          task_id,
+          value,
+        }
+      },
+      "E" => {
+        let (group_id, task_id) = parse_task(fields.next())?;
+        let status = match fields.next()? {
+          "ready" => Status::Ready,
+          "success" => Status::Success,
+          "unmet" => Status::Unmet,
+          "fault" => Status::Fault,
+          _ => return None,
+        };
+
+        TraceEvent::Finished {
+          group_id,
+          task_id,
+          status,
+        }
+      },
+      _ => return None,
+    };
+
+    Some(event)
+  }
+
+  /// `INSTRUMENT ACCESS PARAM [VALUE]: MESSAGE`, the message running to the
+  /// end of the line.
+  fn parse_failed(line: &str) -> Option<TraceEvent> {
+    let (call, message) = line.split_once(": ")?;
+    let mut fields = call.split_whitespace();
+    let instrument = parse_name(INSTRUMENTS, fields.next())?;
+    let (access, table) = match fields.next()? {
+      "get" => (Access::Get, PARAMETERS),
+      "set" => (Access::Set, PARAMETERS),
+      "act" => (Access::Act, ACTIONS),
+      _ => return None,
+    };
+    let param = parse_name(table, fields.next())?;
+    let value = match access {
+      Access::Get => None,
+      Access::Set | Access::Act => Some(parse_value(&mut fields)?),
+    };
+
+    Some(TraceEvent::Failed {
+      instrument,
+      access,
+      param,
+      value,
+      message: message.to_string(),
+    })
+  }
+}
+
+/// A recorded run of a scheduler over `duration` seconds.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Trace {
+  pub duration: u32,
+  pub events: Vec<TraceEvent>,
+}
+
+impl Trace {
+  /// One event per line, so that two traces can be compared with `diff`.
+  pub fn to_text(&self) -> String {
+    let mut text = format!("{} {}s\n", MAGIC, self.duration);
+
+    for event in &self.events {
+      text.push_str(&format!("{}\n", event));
+    }
+
+    text
+  }
+
+  pub fn parse(text: &str) -> Result<Trace, String> {
+    let mut lines = text.lines().enumerate();
+    let duration = lines
+      .next()
+      .and_then(|(_, line)| line.strip_prefix(MAGIC))
+      .and_then(|duration| duration.trim().strip_suffix('s')?.parse().ok())
+      .ok_or_else(|| format!("not a trace, expected `{} <seconds>s` on the first line", MAGIC))?;
+
+    let events = lines
+      .filter(|(_, line)| !line.trim().is_empty())
+      .map(|(index, line)| TraceEvent::parse(line).ok_or_else(|| format!("line {}: invalid event `{}`", index + 1, line)))
+      .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(Trace { duration, events })
+  }
+
+  /// The first difference between two traces, with its line number. Events
+  /// are compared as written, so that NaN values compare equal.
+  pub fn diff(&self, other: &Trace) -> Option<String> {
+    let line = |index: usize| index + 2;
+    let show = |event: Option<&TraceEvent>| event.map_or("end of trace".to_string(), |event| event.to_string());
+
+    (0..self.events.len().max(other.events.len()))
+      .find(|&index| show(self.events.get(index)) != show(other.events.get(index)))
+      .map(|index| {
+        format!(
+          "line {}: expected `{}`, found `{}`",
+          line(index),
+          show(self.events.get(index)),
+          show(other.events.get(index))
+        )
+      })
+  }
+}
+
+/// Wraps an instrument to record the values it returns and the calls that
+/// fail.
+#[derive(Debug)]
+struct Recording {
+  id: u8,
+  inner: Box<dyn Instrument>,
+  reads: Rc<RefCell<Vec<TraceEvent>>>,
+}
+
+impl Recording {
+  fn failed(&self, access: Access, param: u8, value: Option<Value>, message: &str) {
+    self.reads.borrow_mut().push(TraceEvent::Failed {
+      instrument: self.id,
+      access,
+      param,
+      value,
+      message: message.to_string(),
+    });
+  }
+}
+
+impl Instrument for Recording {
+  fn get(&mut self, param: u8, time: u32) -> Result<Value, String> {
+    let result = self.inner.get(param, time);
+
+    match &result {
+      Ok(value) => self.reads.borrow_mut().push(TraceEvent::Read {
+        instrument: self.id,
+        param,
+        value: *value,
+      }),
+      Err(message) => self.failed(Access::Get, param, None, message),
+    }
+
+    result
+  }
+
+  fn set(&mut self, param: u8, value: Value, time: u32) -> Result<(), String> {
+    let result = self.inner.set(param, value, time);
+
+    if let Err(message) = &result {
+      self.failed(Access::Set, param, Some(value), message);
+    }
+
+    result
+  }
+
+  fn act(&mut self, action: u8, argument: Value, time: u32) -> Result<(), String> {
+    let result = self.inner.act(action, argument, time);
+
+    if let Err(message) = &result {
+      self.failed(Access::Act, action, Some(argument), message);
+    }
+
+    result
+  }
+}
+
+/// Serves the reads and the failed calls of a trace in order, in place of an
+/// instrument. Other writes and actions have no effect, as every value read
+/// back comes from the trace.
+#[derive(Debug)]
+struct Playback {
+  id: u8,
+  reads: VecDeque<TraceEvent>,
+}
+
+impl Playback {
+  /// The error of a set or an action if the trace has it failing here.
+  fn fail(&mut self, access: Access, param: u8, value: Value) -> Result<(), String> {
+    match self.reads.front() {
+      Some(TraceEvent::Failed {
+        access: recorded,
+        param: failed,
+        value: Some(argument),
+        message,
+        ..
+      }) if *recorded == access && *failed == param && argument.to_word() == value.to_word() => {
+        let message = message.clone();
+
+        self.reads.pop_front();
+        Err(message)
+      },
+      _ => Ok(()),
+    }
+  }
+}
+
+impl Instrument for Playback {
+  fn get(&mut self, param: u8, _time: u32) -> Result<Value, String> {
+    match self.reads.pop_front() {
+      Some(TraceEvent::Read {
+        param: recorded,
+        value,
+        ..
+      }) if recorded == param => Ok(value),
+      Some(TraceEvent::Failed {
+        access: Access::Get,
+        param: recorded,
+        message,
+        ..
+      }) if recorded == param => Err(message),
+      Some(TraceEvent::Read { param: recorded, .. }) => Err(format!(
+        "the trace reads {} here, not {}",
+        param_name(self.id, recorded),
+        param_name(self.id, param)
+      )),
+      Some(event) => Err(format!("the trace has `{}` here, not a read of {}", event, param_name(self.id, param))),
+      None => Err("the trace has no more reads of this instrument".to_string()),
+    }
+  }
+
+  fn set(&mut self, param: u8, value: Value, _time: u32) -> Result<(), String> {
+    self.fail(Access::Set, param, value)
+  }
+
+  fn act(&mut self, action: u8, argument: Value, _time: u32) -> Result<(), String> {
+    self.fail(Access::Act, action, argument)
+  }
+}
+
+/// Runs `scheduler` for `duration` seconds and records every instruction.
+pub fn record(scheduler: &mut Scheduler, duration: u32) -> Trace {
+  let reads = Rc::new(RefCell::new(Vec::new()));
+  let machine = &mut scheduler.machine;
+
+  for id in machine.instrument_ids() {
+    if let Some(inner) = machine.detach(id) {
+      machine.attach(
+        id,
+        Box::new(Recording {
+          id,
+          inner,
+          reads: reads.clone(),
+        }),
+      );
+    }
+  }
+
+  let mut trace = Trace {
+    duration,
+    events: Vec::new(),
+  };
+  let end = scheduler.machine.time + duration;
+
+  while scheduler.current().is_some() || scheduler.machine.time < end {
+    let registers = scheduler.machine.registers.clone();
+    let ram = scheduler
+      .current()
+      .map(|(index, _)| scheduler.machine.tasks[index].ram.clone());
+    let outputs = scheduler.machine.output.len();
+    let time = scheduler.machine.time;
+
+    let (index, pc) = match scheduler.step() {
+      Event::Executed { index, pc } | Event::Finished { index, pc, .. } => (index, pc),
+      Event::Started { .. } | Event::Tick { .. } => continue,
+    };
+
+    let machine = &scheduler.machine;
+    let task = &machine.tasks[index];
+    let (group_id, task_id) = (task.header.group_id, task.header.task_id);
+
+    if let Some(&encoding) = task.code.get(pc) {
+      trace.events.push(TraceEvent::Instruction {
+        time,
+        group_id,
+        task_id,
+        word: pc + 1,
+        encoding,
+      });
+    }
+    trace.events.append(&mut reads.borrow_mut());
+
+    for id in 0..2 * REGISTER_COUNT as u8 {
+      let value = machine.registers.get(id);
+
+      if value.to_word() != registers.get(id).to_word() {
+        trace.events.push(TraceEvent::Register { id, value });
+      }
+    }
+
+    let before = ram.unwrap_or_default();
+    for (offset, value) in task.ram.iter().enumerate() {
+      if before.get(offset) != Some(value) {
+        trace.events.push(TraceEvent::Memory {
+          group_id,
+          task_id,
+          address: (offset + 1) as u8,
+          value: *value,
+        });
+      }
+    }
+
+    for output in &machine.output[outputs..] {
+      trace.events.push(TraceEvent::Output {
+        group_id,
+        task_id,
+        value: output.value,
+      });
+    }
+
+    if scheduler.current().is_none() {
+      trace.events.push(TraceEvent::Finished {
+        group_id,
+        task_id,
+        status: task.status,
+      });
+    }
+  }
+
+  trace
+}
+
+/// Runs `scheduler` again over the timeline of `trace`, with every attached
+/// instrument replaced by the values the trace read from it, and returns the
+/// new trace or the first difference with the recorded one.
+pub fn replay(scheduler: &mut Scheduler, trace: &Trace) -> Result<Trace, String> {
+  let machine = &mut scheduler.machine;
+
+  for id in machine.instrument_ids() {
+    let reads = trace
+      .events
+      .iter()
+      .filter(|event| match event {
+        TraceEvent::Read { instrument, .. } | TraceEvent::Failed { instrument, .. } => *instrument == id,
+        _ => false,
+      })
+      .cloned()
+      .collect();
+
+    machine.attach(id, Box::new(Playback { id, reads }));
+  }
+
+  let replayed = record(scheduler, trace.duration);
+
+  match trace.diff(&replayed) {
+    Some(difference) => Err(format!("the replay diverges from the trace at {}", difference)),
+    None => Ok(replayed),
+  }
+}
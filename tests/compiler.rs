@@ -0,0 +1,180 @@
+//! Compiles the `.spl` examples and checks the assembly of every task, and
+//! what the compiled tasks do on the reference VM.
+
+use std::fs;
+
+use splice::compiler::{self, Unit};
+use splice::vm::loader;
+use splice::vm::machine::{Machine, OutputValue, Status};
+
+const ORBIT: &str = "src/tools/examples/orbit.spl";
+
+const POSITION: &str = "; group imaging, task position (src/tools/examples/orbit.spl:3)
+1,1,10
+; src/tools/examples/orbit.spl:11 lat = gps.latt;
+OP_GET, INST_GPS, P_GPS_LATT, FREG_A
+OP_MOV, PRE_MOV_RAM, FREG_A, self.lat
+; src/tools/examples/orbit.spl:12 alt = gps.altt;
+OP_GET, INST_GPS, P_GPS_ALTT, FREG_A
+OP_MOV, PRE_MOV_RAM, FREG_A, self.alt
+; src/tools/examples/orbit.spl:13 return lat, (alt - earth) / 2 * -1.5, sqrt(16.0), i32(alt);
+OP_LEA, FREG_A, self.lat
+OP_STR, PRE_STR_FPU, FREG_A
+OP_LEA, FREG_A, self.alt
+OP_LEA, FREG_B, self.earth
+OP_LEA, FREG_C, self._k1
+OP_FSD, FREG_B, FREG_C, FREG_A
+OP_LEA, FREG_B, self._k2
+OP_LEA, FREG_C, self._k3
+OP_FSD, FREG_C, FREG_B, FREG_A
+OP_LEA, FREG_B, self._k4
+OP_LEA, FREG_C, self._k3
+OP_FMA, FREG_C, FREG_B, FREG_A
+OP_STR, PRE_STR_FPU, FREG_A
+OP_LEA, FREG_A, self._k5
+OP_LEA, FREG_B, self._k6
+OP_POW, PRE_NORMAL, FREG_B, FREG_A
+OP_STR, PRE_STR_FPU, FREG_A
+OP_LEA, FREG_A, self.alt
+OP_MOV, PRE_MOV_REG, FREG_A, IREG_A
+OP_STR, PRE_STR_ALU, IREG_A
+OP_HLT
+lat: 0.0f
+alt: 0.0f
+earth: 6371.0f
+_k1: 1.0f
+_k2: 2.0f
+_k3: 0.0f
+_k4: -1.5f
+_k5: 16.0f
+_k6: 0.5f
+";
+
+const SHOOT: &str = "; group imaging, task shoot (src/tools/examples/orbit.spl:17)
+1,2,60
+; src/tools/examples/orbit.spl:23 position.status == 255;
+OP_LEA, IREG_A, self._k1
+OP_CMP, TSX_EQ, 1, IREG_A
+; src/tools/examples/orbit.spl:24 position.lat > 20.0;
+OP_LEA, FREG_A, t1.lat
+OP_LEA, FREG_B, self._k2
+OP_CMP, FPU_GT, FREG_A, FREG_B
+; src/tools/examples/orbit.spl:25 position.lat < 50;
+OP_LEA, FREG_A, t1.lat
+OP_LEA, FREG_B, self._k3
+OP_CMP, FPU_LT, FREG_A, FREG_B
+; src/tools/examples/orbit.spl:28 shots = shots + 1;
+OP_LEA, IREG_A, self.shots
+OP_LEA, IREG_B, self._k4
+OP_LEA, IREG_C, self._k4
+OP_FMA, IREG_B, IREG_C, IREG_A
+OP_MOV, PRE_MOV_RAM, IREG_A, self.shots
+; src/tools/examples/orbit.spl:29 img.do_jpg();
+OP_ACT, INST_IMG, A_IMG_DO_JPG, IREG_A
+; src/tools/examples/orbit.spl:30 return shots, pow(2.0, 10), log(1024.0, 2), sin(0.5), exp(1.0);
+OP_LEA, IREG_A, self.shots
+OP_STR, PRE_STR_ALU, IREG_A
+OP_LEA, FREG_A, self._k5
+OP_LEA, FREG_B, self._k6
+OP_POW, PRE_NORMAL, FREG_B, FREG_A
+OP_STR, PRE_STR_FPU, FREG_A
+OP_LEA, FREG_A, self._k7
+OP_LEA, FREG_B, self._k5
+OP_POW, PRE_INVERT, FREG_B, FREG_A
+OP_STR, PRE_STR_FPU, FREG_A
+OP_LEA, FREG_A, self._k8
+OP_SIN, PRE_NORMAL, FREG_A, FREG_A
+OP_STR, PRE_STR_FPU, FREG_A
+OP_GET, INST_FPU, P_FPU_EXP, FREG_A
+OP_LEA, FREG_B, self._k9
+OP_POW, PRE_NORMAL, FREG_B, FREG_A
+OP_STR, PRE_STR_FPU, FREG_A
+OP_HLT
+shots: 0i
+_k1: 255i
+_k2: 20.0f
+_k3: 50.0f
+_k4: 1i
+_k5: 2.0f
+_k6: 10.0f
+_k7: 1024.0f
+_k8: 0.5f
+_k9: 1.0f
+";
+
+fn compile(file: &str, source: &str) -> Vec<Unit> {
+  compiler::compile(file, source).unwrap_or_else(|errors| {
+    panic!("{}", errors.iter().map(|error| error.to_string()).collect::<Vec<_>>().join("\n"))
+  })
+}
+
+/// The header line of the only task of `source`.
+fn header(source: &str) -> String {
+  let units = compile("test.spl", source);
+
+  units[0]
+    .assembly
+    .lines()
+    .find(|line| !line.starts_with(';'))
+    .unwrap()
+    .to_string()
+}
+
+#[test]
+fn compiles_orbit_to_one_unit_per_task() {
+  let units = compile(ORBIT, &fs::read_to_string(ORBIT).unwrap());
+  let names: Vec<String> = units.iter().map(Unit::file_name).collect();
+
+  assert_eq!(names, ["imaging_position.splc", "imaging_shoot.splc"]);
+  assert_eq!(units[0].assembly, POSITION);
+  assert_eq!(units[1].assembly, SHOOT);
+}
+
+#[test]
+fn runs_the_compiled_orbit_example() {
+  let tasks = loader::load(&[ORBIT.to_string()]).unwrap();
+  let mut machine = Machine::new(tasks).unwrap();
+
+  assert!(machine.run().is_empty());
+  assert_eq!(machine.tasks[0].status, Status::Success);
+  assert_eq!(machine.tasks[1].status, Status::Success);
+
+  let shoot: Vec<OutputValue> = machine
+    .output
+    .iter()
+    .filter(|output| output.task_id == 2)
+    .map(|output| output.value)
+    .collect();
+  let floats = [2f32.powf(10.0), 1024f32.log(2.0), 0.5f32.sin(), std::f32::consts::E.powf(1.0)];
+
+  assert_eq!(shoot[0], OutputValue::Int(1));
+  assert_eq!(shoot[1..], floats.map(OutputValue::Float));
+}
+
+#[test]
+fn encodes_the_frequency_of_a_task_in_its_header() {
+  let task = |freq: &str| format!("group g {{ task t {{ freq: {}; exec {{ }} }} }}", freq);
+
+  assert_eq!(header(&task("once")), "1,1,0");
+  assert_eq!(header(&task("20s")), "1,1,20");
+  assert_eq!(header(&task("60s")), "1,1,60");
+  assert_eq!(header(&task("1m")), "1,1,60");
+  assert_eq!(header(&task("59m")), "1,1,118");
+  assert_eq!(header(&task("2h")), "1,1,120");
+  assert_eq!(header(&task("always")), "1,1,127");
+}
+
+#[test]
+fn rejects_frequencies_the_vm_cannot_run() {
+  for freq in ["0s", "90s", "9h"] {
+    let source = format!("group g {{ task t {{ freq: {}; exec {{ }} }} }}", freq);
+    let errors = compiler::compile("test.spl", &source).unwrap_err();
+
+    assert_eq!(
+      errors[0].message,
+      "the VM runs tasks every 1 to 59 seconds, 1 to 59 minutes or 1 to 8 hours",
+      "{}",
+      freq
+    );
+  }
+}
@@ -0,0 +1,89 @@
+//! Assembles and links the OPS-SAT test programs of `src/tools/examples`
+//! and checks the result against the executables committed to its `bin`.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use splice::asm::{self, Task};
+use splice::link::Linker;
+use splice::splx;
+
+const EXAMPLES: &str = "src/tools/examples";
+
+/// The `.splc` examples, sorted by name.
+fn sources() -> Vec<PathBuf> {
+  let mut sources: Vec<PathBuf> = fs::read_dir(EXAMPLES)
+    .unwrap()
+    .map(|entry| entry.unwrap().path())
+    .filter(|path| path.extension().is_some_and(|extension| extension == "splc"))
+    .collect();
+
+  sources.sort();
+  assert!(!sources.is_empty(), "no .splc file in {}", EXAMPLES);
+  sources
+}
+
+fn executable(source: &Path) -> String {
+  let path = Path::new(EXAMPLES)
+    .join("bin")
+    .join(source.with_extension("splx").file_name().unwrap());
+
+  fs::read_to_string(&path).unwrap_or_else(|err| panic!("{}: {}", path.display(), err))
+}
+
+fn assemble(source: &Path) -> Task {
+  asm::assemble_file(source).unwrap_or_else(|err| panic!("{}", err))
+}
+
+#[test]
+fn assembles_every_example_to_its_executable() {
+  for source in sources() {
+    let task = assemble(&source);
+
+    assert_eq!(
+      format!("{}\n", splx::format_task(&task.words())),
+      executable(&source),
+      "{}",
+      source.display()
+    );
+  }
+}
+
+#[test]
+fn links_the_examples_to_their_executables_in_task_table_order() {
+  let mut linker = Linker::new();
+  let mut expected = Vec::new();
+
+  // test_b4a and test_b4b are two versions of task 2.4
+  for source in sources().into_iter().filter(|source| !source.ends_with("test_b4b.splc")) {
+    let task = assemble(&source);
+
+    expected.push(((task.header.group_id, task.header.task_id), executable(&source)));
+    linker.add(&source.display().to_string(), task);
+  }
+  expected.sort();
+
+  let image = linker.link().unwrap_or_else(|errors| panic!("{:?}", errors));
+  let expected: String = expected.into_iter().map(|(_, splx)| splx).collect();
+
+  assert_eq!(image.to_splx(), expected);
+}
+
+#[test]
+fn refuses_to_link_two_versions_of_a_task() {
+  let mut linker = Linker::new();
+
+  for name in ["test_b4a.splc", "test_b4b.splc"] {
+    let source = Path::new(EXAMPLES).join(name);
+
+    linker.add(&source.display().to_string(), assemble(&source));
+  }
+
+  let errors = match linker.link() {
+    Ok(_) => panic!("task 2.4 is linked twice"),
+    Err(errors) => errors,
+  };
+
+  assert_eq!(errors.len(), 1);
+  assert!(errors[0].to_string().contains("test_b4b.splc"), "{}", errors[0]);
+}
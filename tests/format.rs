@@ -0,0 +1,109 @@
+//! `splice fmt` on the examples and on untidy sources: formatting twice
+//! gives what formatting once does, and formatted assembly assembles to the
+//! same words.
+
+use std::fs;
+use std::path::PathBuf;
+
+use splice::asm;
+use splice::compiler;
+
+const EXAMPLES: &str = "src/tools/examples";
+
+const UNTIDY_SPL: &str = "// a comment before the group
+group  imaging{task position{
+  data{var lat:f32=0.0;   const earth : f32 = 6371.0; // the radius
+  }
+
+
+  freq:10s;
+exec{lat=gps.latt;return lat,( lat-earth )/2*-1.5,sqrt( 16.0 );}}
+  task shoot {
+    freq: 1m;
+    preq { position.lat>20.0; position.status==255; }
+    exec { img.do_jpg(); }
+  }
+}
+";
+
+const UNTIDY_SPLC: &str = "
+
+  1,1,10 ; position
+  .equ  EARTH,6371.0f
+OP_LEA,FREG_A,self.lat,
+     OP_STR , PRE_STR_FPU,FREG_A   // the latitude
+
+
+OP_HLT,
+lat :  0.0f
+earth:EARTH
+";
+
+fn examples(extension: &str) -> Vec<PathBuf> {
+  let mut paths: Vec<PathBuf> = fs::read_dir(EXAMPLES)
+    .unwrap()
+    .map(|entry| entry.unwrap().path())
+    .filter(|path| path.extension().is_some_and(|candidate| candidate == extension))
+    .collect();
+
+  paths.sort();
+  assert!(!paths.is_empty(), "no .{} file in {}", extension, EXAMPLES);
+  paths
+}
+
+fn format_spl(source: &str) -> String {
+  compiler::format::format("test.spl", source).unwrap_or_else(|err| panic!("{}", err))
+}
+
+/// The assembly of every task, without the comments naming source lines.
+fn code(name: &str, source: &str) -> Vec<String> {
+  let units = compiler::compile("test.spl", source).unwrap_or_else(|errors| panic!("{}: {}", name, errors[0]));
+
+  units
+    .iter()
+    .map(|unit| unit.assembly.lines().filter(|line| !line.starts_with(';')).collect::<Vec<_>>().join("\n"))
+    .collect()
+}
+
+fn assert_idempotent_assembly(name: &str, source: &str) {
+  let once = asm::format::format(source);
+
+  assert_eq!(asm::format::format(&once), once, "{}", name);
+
+  let words = |source: &str| asm::assemble(source).unwrap_or_else(|err| panic!("{}: {}", name, err)).words();
+  assert_eq!(words(&once), words(source), "{}", name);
+}
+
+#[test]
+fn formats_task_list_files_once_and_for_all() {
+  let mut sources: Vec<(String, String)> = examples("spl")
+    .into_iter()
+    .map(|path| (path.display().to_string(), fs::read_to_string(&path).unwrap()))
+    .collect();
+  sources.push(("untidy".to_string(), UNTIDY_SPL.to_string()));
+
+  for (name, source) in sources {
+    let once = format_spl(&source);
+
+    assert_eq!(format_spl(&once), once, "{}", name);
+    assert_eq!(code(&name, &once), code(&name, &source), "{}", name);
+  }
+}
+
+#[test]
+fn leaves_formatted_examples_as_they_are() {
+  for path in examples("spl") {
+    let source = fs::read_to_string(&path).unwrap();
+
+    assert_eq!(format_spl(&source), source, "{}", path.display());
+  }
+}
+
+#[test]
+fn formats_assembly_once_and_for_all() {
+  for path in examples("splc") {
+    assert_idempotent_assembly(&path.display().to_string(), &fs::read_to_string(&path).unwrap());
+  }
+
+  assert_idempotent_assembly("untidy", UNTIDY_SPLC);
+}